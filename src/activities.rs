@@ -0,0 +1,855 @@
+//! the activities file: the activities that `tt add` knows, with their shortnames.
+//!
+//! It has one of two formats. In lines, every line is `<shortname> <activity> [tags]` or just
+//! `<activity>`. In TOML, every activity is a table with its name, and the attributes that
+//! lines cannot hold:
+//!
+//! ```toml
+//! # activities of tt
+//! [JIRA-123]
+//! shortname = "api"
+//! aliases = ["pagination", "p"]
+//! project = "acme/portal"
+//! tags = ["review", "phase=dev"]
+//! description = "the new API"
+//! customer = "acme"
+//! billable = true
+//! rate = 95.0
+//! estimate = 40.0  # hours
+//! archived = false
+//! ```
+//!
+//! The shortname is the preferred name of the activity in reports, the aliases are other
+//! names for it. In lines, every further line with another shortname for the activity adds an
+//! alias.
+//!
+//! The project is a path of projects and sub-projects, in lines it is the tag `project=...`.
+//! Archived activities are not shown by `tt list` and the interactive menu, in lines they have
+//! the tag `archived=true`. `tt activities restore` takes an activity out of the archive and
+//! keeps the day in `restored`, in lines the tag `restored=2024-03-05`, so that archiving it
+//! automatically waits for the days of archive-activities-after again.
+//!
+//! Both formats have comments, lines that start with `#` and a space. A file is read as TOML
+//! if its first line that is neither empty nor a comment is the header of a table.
+//! `tt activities convert` converts lines to TOML.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::BufRead;
+use std::result::Result;
+
+use chrono::{Duration, NaiveDate};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{TTError, TTErrorKind};
+
+/// an activity of the activities file
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Activity {
+    pub name: String,
+    /// the preferred name to show instead of the name
+    pub shortname: Option<String>,
+    /// more names that `tt add` knows the activity by
+    pub aliases: Vec<String>,
+    /// the project and its sub-projects, like `acme/portal`
+    pub project: Option<String>,
+    /// tags and metadata like `customer=acme` for the log entries of the activity
+    pub tags: Vec<String>,
+    pub description: Option<String>,
+    /// the customer, the log entries get it as metadata `customer=...`
+    pub customer: Option<String>,
+    pub billable: Option<bool>,
+    /// the hourly rate
+    pub rate: Option<f64>,
+    /// how long the activity is expected to take in total
+    pub estimate: Option<Duration>,
+    /// hidden from `tt list` and the interactive menu, `tt add` still knows it
+    pub archived: bool,
+    /// the day the activity was last taken out of the archive
+    pub restored: Option<NaiveDate>,
+}
+
+impl Activity {
+    /// an activity with only a name
+    pub fn new(name: &str) -> Activity {
+        Activity {
+            name: name.to_string(),
+            ..Activity::default()
+        }
+    }
+
+    // the shortname and the aliases
+    fn other_names(&self) -> impl Iterator<Item = &String> {
+        self.shortname.iter().chain(self.aliases.iter())
+    }
+
+    /// the metadata that the activity adds to log entries: the ones in its tags and the customer
+    pub fn metadata_tags(&self) -> impl Iterator<Item = String> + '_ {
+        self.tags
+            .iter()
+            .cloned()
+            .chain(self.customer.iter().map(|c| format!("customer={}", c)))
+    }
+
+    /// the projects the activity belongs to, from the top-level project to the innermost one
+    /// ```
+    /// use timetracker::activities::Activity;
+    /// let activity = Activity {
+    ///     project: Some("acme/portal".to_string()),
+    ///     ..Activity::new("JIRA-123")
+    /// };
+    /// assert_eq!(activity.projects(), vec!["acme", "portal"]);
+    /// assert!(Activity::new("email").projects().is_empty());
+    /// ```
+    pub fn projects(&self) -> Vec<&str> {
+        self.project
+            .iter()
+            .flat_map(|project| project.split('/'))
+            .filter(|project| !project.is_empty())
+            .collect()
+    }
+}
+
+/// the start of a line that `tt merge` adds to the activities file for a definition of the
+/// other machine that conflicts with the one here. It is a comment in both formats, so the
+/// activities are read as before, and `tt check` reports it until it has been removed.
+pub const CONFLICT_MARKER: &str = "# merge conflict";
+
+/// the activities by name and by shortname
+pub type ActivityMap = HashMap<String, Activity>;
+
+/// the format of the activities file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+    Lines,
+    Toml,
+}
+
+impl FileFormat {
+    /// the format of the content of an activities file: TOML if the first line that is not
+    /// empty or a comment is the header of a table or a `key = value`
+    /// ```
+    /// use timetracker::activities::FileFormat;
+    /// assert_eq!(FileFormat::of("mail email\n"), FileFormat::Lines);
+    /// assert_eq!(FileFormat::of("\n[email]\nshortname = \"mail\"\n"), FileFormat::Toml);
+    /// assert_eq!(FileFormat::of("# mine\n[email]\n"), FileFormat::Toml);
+    /// assert_eq!(FileFormat::of("# mine\nmail email\n"), FileFormat::Lines);
+    /// assert_eq!(FileFormat::of("#1 ticket-1\n"), FileFormat::Lines);
+    /// assert_eq!(FileFormat::of(""), FileFormat::Lines);
+    /// ```
+    pub fn of(text: &str) -> FileFormat {
+        lazy_static! {
+            static ref KEY_VALUE_RE: Regex = Regex::new(r#"^[A-Za-z0-9_."'-]+\s*="#).unwrap();
+        }
+        match text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !is_comment(line))
+        {
+            Some(line) if table_name(line).is_some() || KEY_VALUE_RE.is_match(line) => {
+                FileFormat::Toml
+            }
+            _ => FileFormat::Lines,
+        }
+    }
+}
+
+// an activity in TOML, without its name
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlActivity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shortname: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    customer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    billable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate: Option<f64>,
+    /// in hours
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimate: Option<f64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    archived: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restored: Option<NaiveDate>,
+}
+
+impl TomlActivity {
+    fn into_activity(self, name: String) -> Activity {
+        Activity {
+            name,
+            shortname: self.shortname,
+            aliases: self.aliases,
+            project: self.project,
+            tags: self.tags,
+            description: self.description,
+            customer: self.customer,
+            billable: self.billable,
+            rate: self.rate,
+            estimate: self
+                .estimate
+                .map(|hours| Duration::seconds((hours * 3600.0).round() as i64)),
+            archived: self.archived,
+            restored: self.restored,
+        }
+    }
+
+    fn from_activity(activity: &Activity) -> TomlActivity {
+        TomlActivity {
+            shortname: activity.shortname.clone(),
+            aliases: activity.aliases.clone(),
+            project: activity.project.clone(),
+            tags: activity.tags.clone(),
+            description: activity.description.clone(),
+            customer: activity.customer.clone(),
+            billable: activity.billable,
+            rate: activity.rate,
+            estimate: activity
+                .estimate
+                .map(|estimate| estimate.num_seconds() as f64 / 3600.0),
+            archived: activity.archived,
+            restored: activity.restored,
+        }
+    }
+}
+
+/// whether the line of an activities file is a comment: `#` as a word of its own, like
+/// `# my activities`. `#1` is an activity.
+pub fn is_comment(line: &str) -> bool {
+    line.split_whitespace().next() == Some("#")
+}
+
+// the activities of a file in lines
+fn read_lines(text: &str) -> Result<Vec<Activity>, TTError> {
+    let mut activities = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if is_comment(line) {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => {
+                return Err(TTError::new(TTErrorKind::ActivityConfigError(
+                    "Config activity lines wrong, correct format is <left> <right> or <activity>",
+                ))
+                .with_span(line, 0..line.len())
+                .at_line(index + 1)
+                .with_hint("remove empty lines from the activities file"))
+            }
+            (Some(name), None) => activities.push(Activity::new(name)),
+            (Some(shortname), Some(name)) => {
+                let mut activity = Activity {
+                    shortname: Some(shortname.to_string()).filter(|shortname| shortname != name),
+                    ..Activity::new(name)
+                };
+                for word in words {
+                    match word.split_once('=') {
+                        Some(("project", project)) => activity.project = Some(project.to_string()),
+                        Some(("archived", archived)) => activity.archived = archived == "true",
+                        Some(("restored", day)) if day.parse::<NaiveDate>().is_ok() => {
+                            activity.restored = day.parse().ok()
+                        }
+                        _ => activity.tags.push(word.to_string()),
+                    }
+                }
+                activities.push(activity)
+            }
+        }
+    }
+    Ok(activities)
+}
+
+// the activities of a file in TOML, ordered by name
+fn read_toml(text: &str) -> Result<Vec<Activity>, TTError> {
+    let table: BTreeMap<String, TomlActivity> = toml::from_str(text).map_err(|err| {
+        let error = TTError::new(TTErrorKind::ParseError(
+            "the activities file is not valid TOML",
+            err.to_string(),
+        ));
+        match err.line_col() {
+            Some((line, _)) => error.at_line(line + 1),
+            None => error,
+        }
+    })?;
+    let activities: Vec<Activity> = table
+        .into_iter()
+        .map(|(name, activity)| activity.into_activity(name))
+        .collect();
+    for activity in activities.iter() {
+        for shortname in activity.other_names() {
+            let used = activities.iter().any(|other| {
+                other.name != activity.name
+                    && (other.name == *shortname || other.other_names().any(|s| s == shortname))
+            });
+            if used {
+                return Err(TTError::new(TTErrorKind::ActivityConfigError(
+                    "the shortname is already used for another activity",
+                ))
+                .context(format!("shortname {:?} of {:?}", shortname, activity.name)));
+            }
+        }
+    }
+    Ok(activities)
+}
+
+/// reads the activities file in either format. Lines keep their order, TOML is ordered by name.
+pub fn read_activity_list<R: BufRead>(
+    mut reader: R,
+) -> Result<(FileFormat, Vec<Activity>), TTError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let format = FileFormat::of(&text);
+    let activities = match format {
+        FileFormat::Lines => read_lines(&text)?,
+        FileFormat::Toml => read_toml(&text)?,
+    };
+    Ok((format, activities))
+}
+
+/// the activities by name, by shortname and by alias. For a name that is listed more than once,
+/// the first definition with a shortname wins, its shortname is the preferred one and the ones
+/// of the other definitions are aliases.
+pub fn activity_map(activities: &[Activity]) -> ActivityMap {
+    let mut other_names: HashMap<&str, Vec<String>> = HashMap::new();
+    for activity in activities {
+        let names = other_names.entry(&activity.name).or_default();
+        for shortname in activity.other_names() {
+            if !names.contains(shortname) {
+                names.push(shortname.to_string());
+            }
+        }
+    }
+    let mut activity_map = ActivityMap::new();
+    let mut with_shortname: HashSet<&str> = HashSet::new();
+    for activity in activities {
+        let names = &other_names[activity.name.as_str()];
+        let known = Activity {
+            shortname: names.first().cloned(),
+            aliases: names.iter().skip(1).cloned().collect(),
+            ..activity.clone()
+        };
+        for shortname in activity.other_names() {
+            activity_map.insert(shortname.to_string(), known.clone());
+        }
+        if !with_shortname.contains(activity.name.as_str()) {
+            activity_map.insert(activity.name.to_string(), known);
+        }
+        if activity.shortname.is_some() {
+            with_shortname.insert(&activity.name);
+        }
+    }
+    activity_map
+}
+
+/// reads the activities file in either format
+/// ```
+/// use timetracker::activities::read_activities;
+/// let activities = read_activities("mail email customer=acme\nmeeting\n".as_bytes()).unwrap();
+/// assert_eq!(activities["mail"].name, "email");
+/// assert_eq!(activities["email"].shortname, Some("mail".to_string()));
+/// assert_eq!(activities["email"].tags, vec!["customer=acme"]);
+/// assert_eq!(activities["meeting"].shortname, None);
+///
+/// let activities = read_activities("api JIRA-812\npagination JIRA-812\n".as_bytes()).unwrap();
+/// assert_eq!(activities["pagination"].shortname, Some("api".to_string()));
+/// assert_eq!(activities["JIRA-812"].aliases, vec!["pagination"]);
+/// ```
+pub fn read_activities<R: BufRead>(reader: R) -> Result<ActivityMap, TTError> {
+    let (_, activities) = read_activity_list(reader)?;
+    Ok(activity_map(&activities))
+}
+
+/// the lines to append to an activities file of the format for the activity. Lines only hold
+/// the shortname and the tags, there is another line for every alias.
+pub fn activity_lines(activity: &Activity, format: FileFormat) -> Vec<String> {
+    match format {
+        FileFormat::Lines => {
+            let only_name = activity.tags.is_empty()
+                && activity.customer.is_none()
+                && activity.project.is_none()
+                && !activity.archived
+                && activity.restored.is_none();
+            let mut words = match &activity.shortname {
+                Some(shortname) => vec![shortname.as_str(), activity.name.as_str()],
+                None if only_name => vec![activity.name.as_str()],
+                // without the shortname, the name would be taken as one
+                None => vec![activity.name.as_str(), activity.name.as_str()],
+            };
+            let metadata: Vec<String> = activity
+                .project
+                .iter()
+                .map(|project| format!("project={}", project))
+                .chain(activity.metadata_tags())
+                .chain(activity.archived.then(|| "archived=true".to_string()))
+                .chain(activity.restored.map(|day| format!("restored={}", day)))
+                .collect();
+            words.extend(metadata.iter().map(String::as_str));
+            let line = words.join(" ");
+            let aliases = activity.aliases.iter().map(|alias| {
+                let mut words = vec![alias.as_str(), activity.name.as_str()];
+                words.extend(metadata.iter().map(String::as_str));
+                words.join(" ")
+            });
+            std::iter::once(line).chain(aliases).collect()
+        }
+        FileFormat::Toml => to_toml(std::slice::from_ref(activity))
+            .lines()
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+/// the activities in TOML, the content of a new activities file
+/// ```
+/// use timetracker::activities::{to_toml, Activity};
+/// let activity = Activity {
+///     shortname: Some("mail".to_string()),
+///     billable: Some(false),
+///     ..Activity::new("email")
+/// };
+/// assert_eq!(to_toml(&[activity]), "[email]\nshortname = \"mail\"\nbillable = false\n");
+/// ```
+pub fn to_toml(activities: &[Activity]) -> String {
+    let table: BTreeMap<&str, TomlActivity> = activities
+        .iter()
+        .map(|activity| {
+            (
+                activity.name.as_str(),
+                TomlActivity::from_activity(activity),
+            )
+        })
+        .collect();
+    toml::to_string(&table).expect("activities are always valid TOML")
+}
+
+/// the names of the activities that are neither archived nor used, and have not been restored
+/// since the day
+/// ```
+/// use std::collections::HashSet;
+/// use chrono::NaiveDate;
+/// use timetracker::activities::{unused, Activity};
+/// let since = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+/// let archived = Activity { archived: true, ..Activity::new("travel") };
+/// let restored = Activity { restored: Some(since), ..Activity::new("pager") };
+/// let activities = vec![Activity::new("email"), Activity::new("meeting"), archived, restored];
+/// let used: HashSet<String> = vec!["email".to_string()].into_iter().collect();
+/// assert_eq!(unused(&activities, &used, &since), vec!["meeting"]);
+/// ```
+pub fn unused(activities: &[Activity], used: &HashSet<String>, since: &NaiveDate) -> Vec<String> {
+    let mut unused: Vec<String> = Vec::new();
+    for activity in activities {
+        let restored = activity.restored.is_some_and(|day| day >= *since);
+        if !activity.archived
+            && !restored
+            && !used.contains(&activity.name)
+            && !unused.contains(&activity.name)
+        {
+            unused.push(activity.name.to_string());
+        }
+    }
+    unused
+}
+
+// the name of the table if the line is the header of one
+fn table_name(line: &str) -> Option<String> {
+    let line = line.trim();
+    if !line.starts_with('[') || line.starts_with("[[") {
+        return None;
+    }
+    let table: BTreeMap<String, toml::Value> = toml::from_str(line).ok()?;
+    table.into_iter().next().map(|(name, _)| name)
+}
+
+/// the content of the activities file with the activities archived or restored today. The other
+/// lines of the file stay as they are, with their comments.
+pub fn with_archived(
+    text: &str,
+    names: &[String],
+    archived: bool,
+    today: &NaiveDate,
+) -> Result<String, TTError> {
+    let restored = Some(*today).filter(|_| !archived);
+    let format = FileFormat::of(text);
+    let mut content = String::new();
+    let mut in_table = false;
+    for line in text.lines() {
+        match format {
+            FileFormat::Lines => match read_lines(line)?.pop() {
+                Some(activity) if names.contains(&activity.name) => {
+                    let activity = Activity {
+                        archived,
+                        restored,
+                        ..activity
+                    };
+                    content.push_str(&activity_lines(&activity, FileFormat::Lines)[0]);
+                }
+                _ => content.push_str(line),
+            },
+            FileFormat::Toml => match table_name(line) {
+                Some(name) => {
+                    in_table = names.contains(&name);
+                    content.push_str(line);
+                    match restored {
+                        _ if !in_table => (),
+                        None => content.push_str("\narchived = true"),
+                        Some(day) => content.push_str(&format!("\nrestored = \"{}\"", day)),
+                    }
+                }
+                // the old values of archived and restored in the table are replaced
+                None if in_table
+                    && matches!(
+                        line.split('=').next().unwrap_or("").trim(),
+                        "archived" | "restored"
+                    ) =>
+                {
+                    continue
+                }
+                None => content.push_str(line),
+            },
+        }
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+/// the content of an activities file in TOML with the shortname added to the table of the
+/// activity: it becomes the shortname if the activity has none, otherwise an alias. The other
+/// lines of the file stay as they are, with their comments.
+pub fn with_shortname(text: &str, name: &str, shortname: &str) -> Result<String, TTError> {
+    let (_, activities) = read_activity_list(text.as_bytes())?;
+    let mut activity = match activities
+        .into_iter()
+        .find(|activity| activity.name == name)
+    {
+        Some(activity) => activity,
+        None => return Ok(text.to_string()),
+    };
+    match &activity.shortname {
+        None => activity.shortname = Some(shortname.to_string()),
+        Some(first) if first == shortname => (),
+        Some(_) if activity.aliases.iter().any(|alias| alias == shortname) => (),
+        Some(_) => activity.aliases.push(shortname.to_string()),
+    }
+    let names = TomlActivity {
+        shortname: activity.shortname,
+        aliases: activity.aliases,
+        ..TomlActivity::default()
+    };
+    let names = toml::to_string(&names).expect("names are always valid TOML");
+    let mut content = String::new();
+    let mut in_table = false;
+    // the brackets of an old array of aliases that are still open
+    let mut open_brackets = 0;
+    for line in text.lines() {
+        if open_brackets > 0 {
+            open_brackets += bracket_balance(line);
+            continue;
+        }
+        match table_name(line) {
+            Some(table) => {
+                in_table = table == name;
+                content.push_str(line);
+                if in_table {
+                    content.push('\n');
+                    content.push_str(names.trim_end());
+                }
+            }
+            // the old shortname and aliases of the table are replaced
+            None if in_table
+                && matches!(
+                    line.split('=').next().unwrap_or("").trim(),
+                    "shortname" | "aliases"
+                ) =>
+            {
+                open_brackets = bracket_balance(line);
+                continue;
+            }
+            None => content.push_str(line),
+        }
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+// the brackets opened minus the brackets closed in the line of a TOML file, outside of strings
+// and comments
+fn bracket_balance(line: &str) -> i32 {
+    let mut balance = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => break,
+            (None, '[') => balance += 1,
+            (None, ']') => balance -= 1,
+            (None, _) => (),
+        }
+        escaped = false;
+    }
+    balance
+}
+
+/// the activities of a file in lines for a file in TOML: the lines for the same activity are
+/// joined. The first shortname is the preferred one, the others become aliases.
+pub fn joined(activities: &[Activity]) -> Vec<Activity> {
+    let mut joined: Vec<Activity> = Vec::new();
+    for activity in activities {
+        match joined.iter_mut().find(|known| known.name == activity.name) {
+            None => joined.push(activity.clone()),
+            Some(known) => {
+                for shortname in activity.other_names() {
+                    match &known.shortname {
+                        None => known.shortname = Some(shortname.to_string()),
+                        Some(first) if first == shortname => (),
+                        Some(_) if known.aliases.contains(shortname) => (),
+                        Some(_) => known.aliases.push(shortname.to_string()),
+                    }
+                }
+                known.project = known.project.take().or_else(|| activity.project.clone());
+                known.archived = known.archived && activity.archived;
+                known.restored = known.restored.max(activity.restored);
+                for tag in activity.tags.iter() {
+                    if !known.tags.contains(tag) {
+                        known.tags.push(tag.to_string());
+                    }
+                }
+            }
+        }
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = "# activities of tt\n\
+        [JIRA-123]\n\
+        shortname = \"api\"\n\
+        aliases = [\"pagination\"]\n\
+        tags = [\"review\", \"phase=dev\"]\n\
+        description = \"the new API\"\n\
+        customer = \"acme\"\n\
+        billable = true\n\
+        rate = 95\n\
+        estimate = 7.5\n\
+        \n\
+        [email]\n\
+        archived = true\n";
+
+    #[test]
+    fn read_toml() {
+        let (format, activities) = read_activity_list(TOML.as_bytes()).unwrap();
+        assert_eq!(format, FileFormat::Toml);
+        assert_eq!(
+            activities[0],
+            Activity {
+                shortname: Some("api".to_string()),
+                aliases: vec!["pagination".to_string()],
+                tags: vec!["review".to_string(), "phase=dev".to_string()],
+                description: Some("the new API".to_string()),
+                customer: Some("acme".to_string()),
+                billable: Some(true),
+                rate: Some(95.0),
+                estimate: Some(Duration::minutes(450)),
+                ..Activity::new("JIRA-123")
+            }
+        );
+        assert_eq!(
+            activities[1],
+            Activity {
+                archived: true,
+                ..Activity::new("email")
+            }
+        );
+        let activities = activity_map(&activities);
+        assert_eq!(activities["api"], activities["JIRA-123"]);
+        assert_eq!(activities["pagination"], activities["JIRA-123"]);
+    }
+
+    #[test]
+    fn toml_errors() {
+        let err = read_activity_list("[email]\nshortname = 1\n".as_bytes()).unwrap_err();
+        assert_eq!(err.details.line_number, Some(2));
+        let unknown = read_activity_list("[email]\nshortnam = \"mail\"\n".as_bytes());
+        assert!(unknown.is_err());
+        let used = "[email]\nshortname = \"m\"\n[meeting]\nshortname = \"m\"\n";
+        assert!(read_activity_list(used.as_bytes()).is_err());
+        let alias = "[email]\naliases = [\"m\"]\n[meeting]\nshortname = \"m\"\n";
+        assert!(read_activity_list(alias.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn lines_with_comments() {
+        let lines = "# my activities\nmail email\n# not billable\n#1 ticket-1\n";
+        let (format, activities) = read_activity_list(lines.as_bytes()).unwrap();
+        assert_eq!(format, FileFormat::Lines);
+        assert_eq!(
+            activities,
+            vec![
+                Activity {
+                    shortname: Some("mail".to_string()),
+                    ..Activity::new("email")
+                },
+                Activity {
+                    shortname: Some("#1".to_string()),
+                    ..Activity::new("ticket-1")
+                },
+            ]
+        );
+        let toml = "# my activities\n[email]\nshortname = \"mail\"\n";
+        let (format, activities) = read_activity_list(toml.as_bytes()).unwrap();
+        assert_eq!(format, FileFormat::Toml);
+        assert_eq!(activities[0].shortname.as_deref(), Some("mail"));
+    }
+
+    #[test]
+    fn shortname_added() {
+        let toml = "[email]\nshortname = \"mail\"\naliases = [\n  \"m\",  # short\n  \"e]\",\n]\n\
+            description = \"all mail\"\n[meeting]\n";
+        let added = with_shortname(toml, "email", "em").unwrap();
+        assert_eq!(
+            added,
+            "[email]\nshortname = \"mail\"\naliases = [\"m\", \"e]\", \"em\"]\n\
+            description = \"all mail\"\n[meeting]\n"
+        );
+        let (_, activities) = read_activity_list(added.as_bytes()).unwrap();
+        assert_eq!(activities[0].aliases, vec!["m", "e]", "em"]);
+        assert_eq!(
+            with_shortname(&added, "meeting", "mt").unwrap(),
+            format!("{}shortname = \"mt\"\n", added)
+        );
+    }
+
+    #[test]
+    fn converted_to_toml() {
+        let lines = "mail email customer=acme\nmeeting\nemail\nm meeting daily project=intern\n";
+        let (format, activities) = read_activity_list(lines.as_bytes()).unwrap();
+        assert_eq!(format, FileFormat::Lines);
+        let converted = to_toml(&joined(&activities));
+        let (format, converted) = read_activity_list(converted.as_bytes()).unwrap();
+        assert_eq!(format, FileFormat::Toml);
+        assert_eq!(
+            converted,
+            vec![
+                Activity {
+                    shortname: Some("mail".to_string()),
+                    tags: vec!["customer=acme".to_string()],
+                    ..Activity::new("email")
+                },
+                Activity {
+                    shortname: Some("m".to_string()),
+                    project: Some("intern".to_string()),
+                    tags: vec!["daily".to_string()],
+                    ..Activity::new("meeting")
+                },
+            ]
+        );
+        let archived = read_lines("m meeting archived=true\nmeeting\n").unwrap();
+        assert!(archived[0].archived && archived[0].tags.is_empty());
+        assert!(!joined(&archived)[0].archived);
+        let twice = read_lines("m email\nmail email\nm email\n").unwrap();
+        assert_eq!(
+            joined(&twice),
+            vec![Activity {
+                shortname: Some("m".to_string()),
+                aliases: vec!["mail".to_string()],
+                ..Activity::new("email")
+            }]
+        );
+    }
+
+    #[test]
+    fn appended_lines() {
+        let activity = Activity {
+            tags: vec!["review".to_string()],
+            customer: Some("acme".to_string()),
+            ..Activity::new("JIRA-1")
+        };
+        assert_eq!(
+            activity_lines(&activity, FileFormat::Lines),
+            vec!["JIRA-1 JIRA-1 review customer=acme"]
+        );
+        assert_eq!(
+            activity_lines(&activity, FileFormat::Toml),
+            vec!["[JIRA-1]", "tags = [\"review\"]", "customer = \"acme\""]
+        );
+        let with_aliases = Activity {
+            shortname: Some("j1".to_string()),
+            aliases: vec!["one".to_string()],
+            ..Activity::new("JIRA-1")
+        };
+        assert_eq!(
+            activity_lines(&with_aliases, FileFormat::Lines),
+            vec!["j1 JIRA-1", "one JIRA-1"]
+        );
+        let appended = format!(
+            "{}\n{}\n",
+            TOML,
+            activity_lines(&activity, FileFormat::Toml).join("\n")
+        );
+        assert_eq!(read_activity_list(appended.as_bytes()).unwrap().1.len(), 3);
+    }
+
+    #[test]
+    fn archived_in_the_file() {
+        let lines = "mail email customer=acme\nemail\nmeeting\n";
+        let (_, activities) = read_activity_list(lines.as_bytes()).unwrap();
+        let used: HashSet<String> = vec!["meeting".to_string()].into_iter().collect();
+        let today = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let unused = unused(&activities, &used, &today);
+        assert_eq!(unused, vec!["email"]);
+        let archived = with_archived(lines, &unused, true, &today).unwrap();
+        assert_eq!(
+            archived,
+            "mail email customer=acme archived=true\nemail email archived=true\nmeeting\n"
+        );
+        let restored = with_archived(&archived, &unused, false, &today).unwrap();
+        assert_eq!(
+            restored,
+            "mail email customer=acme restored=2024-03-05\nemail email restored=2024-03-05\n\
+            meeting\n"
+        );
+        let (_, activities) = read_activity_list(restored.as_bytes()).unwrap();
+        assert_eq!(activities[0].restored, Some(today));
+        assert_eq!(
+            with_archived(&restored, &unused, true, &today).unwrap(),
+            archived
+        );
+
+        let toml = "# mine\n[email]\narchived = false\n# until June\n[\"JIRA 1\"]\n";
+        let names = vec!["email".to_string(), "JIRA 1".to_string()];
+        let archived = with_archived(toml, &names, true, &today).unwrap();
+        assert_eq!(
+            archived,
+            "# mine\n[email]\narchived = true\n# until June\n[\"JIRA 1\"]\narchived = true\n"
+        );
+        let (_, activities) = read_activity_list(archived.as_bytes()).unwrap();
+        assert!(activities.iter().all(|activity| activity.archived));
+        let restored = with_archived(&archived, &names[..1], false, &today).unwrap();
+        assert_eq!(
+            restored,
+            "# mine\n[email]\nrestored = \"2024-03-05\"\n# until June\n[\"JIRA 1\"]\n\
+            archived = true\n"
+        );
+        let (_, activities) = read_activity_list(restored.as_bytes()).unwrap();
+        assert_eq!(activities[1].restored, Some(today));
+    }
+}
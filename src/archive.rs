@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::result::Result;
+
+use chrono::NaiveDate;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::error::TTError;
+use crate::storage::{dated_line, split_dated_line};
+use crate::utils::FileProxy;
+
+/// the lines of the days in an archive
+pub type ArchivedDays = BTreeMap<NaiveDate, Vec<String>>;
+
+/// the archive of the month of the date, like archive/2020-06.gz in the data directory.
+/// An archive holds the lines of the days like the single-file storage, compressed with gzip.
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use chrono::NaiveDate;
+/// use timetracker::archive::archive_name;
+/// assert_eq!(
+///     archive_name(Path::new("tt"), &NaiveDate::from_ymd_opt(2020, 6, 1).unwrap()),
+///     PathBuf::from("tt/archive/2020-06.gz")
+/// );
+/// ```
+pub fn archive_name(data_dir: &Path, date: &NaiveDate) -> PathBuf {
+    data_dir
+        .join("archive")
+        .join(format!("{}.gz", date.format("%Y-%m")))
+}
+
+/// reads the days of an archive, a missing archive has no days
+pub fn read_archive<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    file: &F,
+) -> Result<ArchivedDays, TTError> {
+    let mut compressed = Vec::new();
+    file.reader()?.read_to_end(&mut compressed)?;
+    let mut days = ArchivedDays::new();
+    if compressed.is_empty() {
+        return Ok(days);
+    }
+    let mut content = Vec::new();
+    GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut content)
+        .map_err(|err| TTError::from(err).context("uncompressing the archive".to_string()))?;
+    for line in content.lines() {
+        if let Some((date, line)) = split_dated_line(&line?) {
+            days.entry(date).or_default().push(line.to_string());
+        }
+    }
+    Ok(days)
+}
+
+/// replaces the archive with the given days
+pub fn write_archive<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    file: &F,
+    days: &ArchivedDays,
+) -> Result<(), TTError> {
+    let content: String = days
+        .iter()
+        .flat_map(|(date, lines)| lines.iter().map(move |line| dated_line(date, line) + "\n"))
+        .collect();
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    let compressed = encoder
+        .write_all(content.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map_err(|err| TTError::from(err).context("compressing the archive".to_string()))?;
+    Ok(file.replace_content(&compressed)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::FakeFile;
+
+    #[test]
+    fn write_and_read() {
+        let archive = FakeFile::new(PathBuf::from("archive/2020-06.gz"));
+        assert!(read_archive(&archive).unwrap().is_empty());
+        let mut days = ArchivedDays::new();
+        days.insert(
+            NaiveDate::from_ymd_opt(2020, 6, 2).unwrap(),
+            vec!["09:00 meeting".to_string(), "".to_string()],
+        );
+        days.insert(
+            NaiveDate::from_ymd_opt(2020, 6, 1).unwrap(),
+            vec!["08:00 email".to_string()],
+        );
+        write_archive(&archive, &days).unwrap();
+        assert_eq!(read_archive(&archive).unwrap(), days);
+    }
+}
@@ -1,25 +1,137 @@
 use chrono::Duration;
-use chrono::NaiveTime;
+use chrono::{FixedOffset, NaiveDate, NaiveTime};
+use itertools::Itertools;
 use std::collections::{BTreeMap, HashMap};
 use std::io;
 use std::mem;
 
-use super::log_parser::{self, Block, BlockData};
-use crate::error::TTError;
+use super::log_parser::{self, Block, BlockData, Metadata};
+use crate::error::{Diagnostic, Severity, TTError, TTErrorKind};
+use crate::utils;
 
 // use crate::SummaryFormat::{Short, Long};
 
 // name -> (duration, map(tag -> duration))
 pub(crate) type ActivityHashMap = HashMap<String, (Duration, BTreeMap<String, Duration>)>;
 
-#[derive(Debug)]
+// name -> map((key, value) -> duration)
+pub(crate) type MetadataHashMap = HashMap<String, BTreeMap<(String, String), Duration>>;
+
+// name -> map(all metadata of an entry -> duration)
+pub(crate) type MetadataCombinationsHashMap = HashMap<String, BTreeMap<Metadata, Duration>>;
+
+// name -> notes in the order of the log
+pub(crate) type NotesHashMap = HashMap<String, Vec<String>>;
+
+#[derive(Debug, Clone)]
 pub struct Summary {
     pub start: NaiveTime,
     pub end: NaiveTime,
     pub breaks: Duration,
     pub work_time: Duration,
     pub activities: ActivityHashMap,
+    pub metadata: MetadataHashMap,
+    /// the durations by the whole metadata of the entries, for filters on several keys
+    pub metadata_combinations: MetadataCombinationsHashMap,
+    pub notes: NotesHashMap,
     pub distribute: Duration,
+    /// the time on secondary tracks, by track name. It is not part of the work time.
+    pub tracks: ActivityHashMap,
+    /// the manual durations by activity, they are part of the activities and the work time
+    pub adjustments: HashMap<String, Duration>,
+    /// the activities and breaks in the order of the log, with the corrections applied.
+    /// Manual durations and tracks are not part of them.
+    pub intervals: Vec<Interval>,
+}
+
+/// a stretch of time on an activity or a break
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interval {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub duration: Duration,
+    pub data: BlockData,
+}
+
+impl Summary {
+    fn starting_at(start: NaiveTime) -> Summary {
+        Summary {
+            start,
+            end: start,
+            breaks: Duration::zero(),
+            work_time: Duration::zero(), // includes "distribute"
+            activities: HashMap::new(),
+            metadata: HashMap::new(),
+            metadata_combinations: HashMap::new(),
+            notes: HashMap::new(),
+            distribute: Duration::zero(),
+            tracks: HashMap::new(),
+            adjustments: HashMap::new(),
+            intervals: Vec::new(),
+        }
+    }
+
+    // adds the time spent on the activity of the block
+    fn add_duration(&mut self, data: &BlockData, duration: Duration) {
+        if log_parser::is_break(&data.activity) {
+            self.breaks += duration;
+        } else {
+            self.work_time += duration;
+        }
+        if data.distribute {
+            self.distribute += duration;
+        }
+        add_activity_duration(&mut self.activities, data, duration);
+        add_metadata_duration(
+            self.metadata.entry(data.activity.to_string()).or_default(),
+            &data.metadata,
+            duration,
+        );
+        *self
+            .metadata_combinations
+            .entry(data.activity.to_string())
+            .or_default()
+            .entry(data.metadata.clone())
+            .or_insert_with(Duration::zero) += duration;
+        self.add_note(data);
+    }
+
+    // remembers the note of a block, repeated notes are only kept once
+    fn add_note(&mut self, data: &BlockData) {
+        if let Some(note) = &data.note {
+            let notes = self.notes.entry(data.activity.to_string()).or_default();
+            if !notes.contains(note) {
+                notes.push(note.to_string());
+            }
+        }
+    }
+}
+
+// adds the duration to all key/value pairs of the metadata
+fn add_metadata_duration(
+    durations: &mut BTreeMap<(String, String), Duration>,
+    metadata: &Metadata,
+    duration: Duration,
+) {
+    for (key, value) in metadata.iter() {
+        let entry = durations
+            .entry((key.clone(), value.clone()))
+            .or_insert_with(Duration::zero);
+        *entry += duration;
+    }
+}
+
+// adds the duration to the activity of the block and to each of its tags
+fn add_activity_duration(activities: &mut ActivityHashMap, data: &BlockData, duration: Duration) {
+    let (total, duration_map) = activities
+        .entry(data.activity.to_string())
+        .or_insert_with(|| (Duration::zero(), BTreeMap::new()));
+    *total += duration;
+    for tag in data.tags.iter() {
+        *duration_map
+            .entry(tag.clone())
+            .or_insert_with(Duration::zero) += duration;
+    }
 }
 
 struct ProgressData {
@@ -28,8 +140,58 @@ struct ProgressData {
     prev: Option<BlockData>, // the data of the previous NormalBlock, already in calculation
 }
 
+impl ProgressData {
+    // ends the last block with the start of the next one after the given duration
+    fn end_last(&mut self, next: BlockData, duration: Duration) {
+        self.summary.end = next.start;
+        self.summary.add_duration(&self.last, duration);
+        self.summary.intervals.push(Interval {
+            start: self.last.start,
+            end: next.start,
+            duration,
+            data: self.last.clone(),
+        });
+        // this sets: self.prev = self.last; self.last=next
+        // but avoids duplicate references to self.last.
+        // unwrap is guaranteed to work since we just set self.prev to something.
+        self.prev = Some(next);
+        mem::swap(self.prev.as_mut().unwrap(), &mut self.last);
+    }
+}
+
+// the elapsed time between two times of a workday, given as time since the start of the workday
+// and their UTC offset from the log. If the date of the workday is known, a missing offset is the
+// one of the local time zone. With both offsets, it is the real elapsed time, also on the days of
+// the switch to and from daylight saving time.
+fn elapsed(
+    workday: Option<NaiveDate>,
+    from: (Duration, Option<FixedOffset>),
+    to: (Duration, Option<FixedOffset>),
+) -> Duration {
+    let utc_offset = |(offset, utc_offset): (Duration, Option<FixedOffset>)| {
+        utc_offset.or_else(|| {
+            workday.map(|date| utils::local_offset(&(date.and_time(utils::day_change()) + offset)))
+        })
+    };
+    match (utc_offset(from), utc_offset(to)) {
+        (Some(from_utc), Some(to_utc)) => {
+            let shift = to_utc.local_minus_utc() - from_utc.local_minus_utc();
+            to.0 - from.0 - Duration::seconds(shift as i64)
+        }
+        _ => to.0 - from.0,
+    }
+}
+
 pub struct BlockCollector {
     state: Option<ProgressData>,
+    workday: Option<NaiveDate>,
+    diagnostics: Vec<Diagnostic>,
+    // the secondary tracks that are running, by name
+    tracks: HashMap<String, BlockData>,
+    // the time on secondary tracks that have ended
+    track_summary: ActivityHashMap,
+    // the manual durations, they are added to the summary at the end
+    adjustments: Vec<(Duration, BlockData)>,
 }
 
 pub struct CollectResult {
@@ -47,7 +209,7 @@ pub struct CollectResult {
 ///
 /// ```
 /// use chrono::NaiveTime;
-/// use timetracker::log_parser::{Block, BlockData};
+/// use timetracker::log_parser::{Block, BlockData, Metadata};
 /// use timetracker::collector::{BlockCollector, CollectResult};
 ///
 /// let mut collector = BlockCollector::new();
@@ -55,13 +217,21 @@ pub struct CollectResult {
 ///     start: NaiveTime::from_hms(8,30,0),
 ///     activity: "email".to_string(),
 ///     tags: vec![],
+///     metadata: Metadata::new(),
+///     note: None,
 ///     distribute: false,
+///     utc_offset: None,
+///     start_seconds: false,
 /// }));
 /// collector.add(Block::NormalBlock(BlockData {
 ///     start: NaiveTime::from_hms(9,15,0),
 ///     activity: "break".to_string(),
 ///     tags: vec![],
+///     metadata: Metadata::new(),
+///     note: None,
 ///     distribute: false,
+///     utc_offset: None,
+///     start_seconds: false,
 /// }));
 /// let CollectResult{summary, final_activity, final_shortname, final_start} = collector.finalize(None).unwrap();
 /// assert_eq!(summary.work_time.num_minutes(), 45);
@@ -71,42 +241,101 @@ pub struct CollectResult {
 /// ```
 impl BlockCollector {
     pub fn new() -> BlockCollector {
-        BlockCollector { state: None }
+        BlockCollector {
+            state: None,
+            workday: None,
+            diagnostics: Vec::new(),
+            tracks: HashMap::new(),
+            track_summary: HashMap::new(),
+            adjustments: Vec::new(),
+        }
+    }
+
+    /// a collector for the log of this workday, times without UTC offset are in the local time zone
+    pub fn for_workday(workday: &NaiveDate) -> BlockCollector {
+        BlockCollector {
+            state: None,
+            workday: Some(*workday),
+            diagnostics: Vec::new(),
+            tracks: HashMap::new(),
+            track_summary: HashMap::new(),
+            adjustments: Vec::new(),
+        }
+    }
+
+    /// parses and adds a line of the log. Lines that cannot be parsed are skipped,
+    /// the problems found are kept as diagnostics with the line number.
+    pub fn add_line(&mut self, line_number: usize, line: io::Result<String>) {
+        let text = line.as_ref().ok().cloned();
+        let known = self.diagnostics.len();
+        match Block::from_line(line) {
+            Ok(block) => self.add(block),
+            Err(err) => self.diagnostics.push(Diagnostic::error(err)),
+        }
+        let found = self.diagnostics.split_off(known);
+        for mut diagnostic in found {
+            // problems of the collector are about the time of the entry, its first word
+            if let (Some(text), None) = (&text, &diagnostic.error.details.line) {
+                let start = text.len() - text.trim_start().len();
+                let end = text[start..]
+                    .find(char::is_whitespace)
+                    .map_or(text.len(), |len| start + len);
+                diagnostic.error = diagnostic.error.with_span(text, start..end);
+            }
+            self.diagnostics.push(diagnostic.at_line(line_number));
+        }
+    }
+
+    /// the problems found so far: blocks that have been skipped (errors), or that probably
+    /// do not do what was intended (warnings)
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// removes the problems found so far and returns them
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        mem::take(&mut self.diagnostics)
     }
 
     pub fn add(&mut self, block: Block) {
-        // TODO: check that start time is monotonic increasing
-        // Idea: Instead of Option<ProgressData>, have an enum (Empty, Progress, Error)
-        // and Error has a list of TTErrors. finalize can then return this.
+        match block {
+            Block::TrackBlock(track, data) => return self.add_track(track, data),
+            Block::DurationBlock(duration, data) => return self.adjustments.push((duration, data)),
+            _ => (),
+        }
         match &mut self.state {
-            None => {
-                match block {
-                    Block::ReallyBlock(_) => (),
-                    Block::TimeCorrection(_) => (),
-                    Block::CommentBlock(_) => (),
-                    Block::NormalBlock(b) => {
-                        let summary = Summary {
-                            start: b.start,
-                            end: b.start,
-                            breaks: Duration::zero(),
-                            work_time: Duration::zero(), // includes "distribute"
-                            activities: HashMap::new(),
-                            distribute: Duration::zero(),
-                        };
-
-                        self.state = Some(ProgressData {
-                            summary,
-                            last: b,
-                            prev: None,
-                        })
-                    }
+            None => match block {
+                Block::ReallyBlock(_) | Block::TimeCorrection(_) => {
+                    self.diagnostics.push(Diagnostic::error(
+                        TTError::new(TTErrorKind::LogError(
+                            "there is no activity before to correct",
+                        ))
+                        .with_hint("a correction with `really` needs an entry before it"),
+                    ))
                 }
-            }
+                Block::CommentBlock(_) | Block::TrackBlock(_, _) | Block::DurationBlock(_, _) => (),
+                Block::NormalBlock(b) => {
+                    let summary = Summary::starting_at(b.start);
+                    self.state = Some(ProgressData {
+                        summary,
+                        last: b,
+                        prev: None,
+                    })
+                }
+            },
             Some(data) => match block {
-                Block::CommentBlock(_) => (),
+                Block::CommentBlock(_) | Block::TrackBlock(_, _) | Block::DurationBlock(_, _) => (),
                 Block::TimeCorrection(real_start) => {
-                    let diff = real_start - data.last.start; // positive means prev ended later
-                                                             // unwrap: We only hold temporarily 2 references on data.last, at the end of this fn
+                    // positive means prev ended later. The corrected start keeps its UTC offset.
+                    let diff = elapsed(
+                        self.workday,
+                        (
+                            utils::workday_offset(&data.last.start),
+                            data.last.utc_offset,
+                        ),
+                        (utils::workday_offset(&real_start), data.last.utc_offset),
+                    );
+                    // unwrap: We only hold temporarily 2 references on data.last, at the end of this fn
                     data.last.start = real_start;
                     data.summary.end = real_start;
                     match &data.prev {
@@ -118,6 +347,19 @@ impl BlockCollector {
                             for duration in act_tags.values_mut() {
                                 *duration = *duration + diff;
                             }
+                            if let Some(act_metadata) =
+                                data.summary.metadata.get_mut(&before.activity)
+                            {
+                                add_metadata_duration(act_metadata, &before.metadata, diff);
+                            }
+                            if let Some(combination) = data
+                                .summary
+                                .metadata_combinations
+                                .get_mut(&before.activity)
+                                .and_then(|combinations| combinations.get_mut(&before.metadata))
+                            {
+                                *combination += diff;
+                            }
                             if log_parser::is_break(&before.activity) {
                                 data.summary.breaks = data.summary.breaks + diff;
                             } else {
@@ -126,6 +368,10 @@ impl BlockCollector {
                             if before.distribute {
                                 data.summary.distribute = data.summary.distribute + diff;
                             }
+                            if let Some(interval) = data.summary.intervals.last_mut() {
+                                interval.end = real_start;
+                                interval.duration += diff;
+                            }
                         }
                         None => {
                             data.summary.start = real_start;
@@ -142,93 +388,190 @@ impl BlockCollector {
                         start: data.last.start,
                         activity: b.activity,
                         tags: b.tags,
+                        metadata: b.metadata,
+                        note: b.note,
                         distribute: b.distribute,
+                        utc_offset: data.last.utc_offset,
+                        start_seconds: data.last.start_seconds,
                     };
                 }
                 Block::NormalBlock(b) => {
-                    assert!(b.start >= data.last.start);
-                    let duration = b.start - data.last.start;
-                    data.summary.end = b.start;
-                    if log_parser::is_break(&data.last.activity) {
-                        data.summary.breaks = data.summary.breaks + duration;
-                    } else {
-                        data.summary.work_time = data.summary.work_time + duration;
-                    }
-                    if data.last.distribute {
-                        data.summary.distribute = data.summary.distribute + duration;
-                    }
-                    match data.summary.activities.get_mut(&data.last.activity) {
-                        Some((old_duration, duration_map)) => {
-                            *old_duration = *old_duration + duration;
-                            for tag in data.last.tags.iter() {
-                                let old_tag_duration =
-                                    duration_map.get(tag).map_or_else(Duration::zero, |d| *d);
-                                duration_map.insert(tag.clone(), old_tag_duration + duration);
-                            }
-                        }
-                        None => {
-                            let duration_map = data
-                                .last
-                                .tags
-                                .iter()
-                                .map(|tag| (tag.clone(), duration))
-                                .collect();
-                            data.summary
-                                .activities
-                                .insert(data.last.activity.to_string(), (duration, duration_map));
-                        }
+                    let duration = elapsed(
+                        self.workday,
+                        (
+                            utils::workday_offset(&data.last.start),
+                            data.last.utc_offset,
+                        ),
+                        (utils::workday_offset(&b.start), b.utc_offset),
+                    );
+                    if duration < Duration::zero() {
+                        self.diagnostics.push(Diagnostic::error(
+                            TTError::new(TTErrorKind::LogError(
+                                "the entry is earlier than the one before",
+                            ))
+                            .with_hint(
+                                "the entries must be in the order of time, \
+                                use `HH:MM really HH:MM` to correct the time of the entry before",
+                            ),
+                        ));
+                        return;
                     }
-                    // this sets: data.prev = data.last; data.last=b
-                    // but avoids duplicate references to data.last.
-                    // unwrap is guaranteed to work since we just set data.prev to something.
-                    data.prev = Some(b);
-                    mem::swap(data.prev.as_mut().unwrap(), &mut data.last);
+                    data.end_last(b, duration);
                 }
             },
         }
     }
 
+    // starts, restarts or ends a secondary track
+    fn add_track(&mut self, track: String, data: BlockData) {
+        let end = (utils::workday_offset(&data.start), data.utc_offset);
+        if !self.tracks.contains_key(&track) && log_parser::is_break(&data.activity) {
+            self.diagnostics.push(Diagnostic::error(
+                TTError::new(TTErrorKind::LogError("the track has not been started"))
+                    .with_hint("start a track with `HH:MM @TRACK`"),
+            ));
+            return;
+        }
+        if let Some(running) = self.tracks.get(&track) {
+            let duration = elapsed(
+                self.workday,
+                (utils::workday_offset(&running.start), running.utc_offset),
+                end,
+            );
+            if duration < Duration::zero() {
+                self.diagnostics.push(Diagnostic::error(
+                    TTError::new(TTErrorKind::LogError(
+                        "the entry is earlier than the start of the track",
+                    ))
+                    .with_hint("the entries of a track must be in the order of time"),
+                ));
+                return;
+            }
+            self.end_track(&track, end);
+        }
+        if !log_parser::is_break(&data.activity) {
+            self.tracks.insert(track, data);
+        }
+    }
+
+    // ends the running track at the given time since the start of the workday
+    fn end_track(&mut self, track: &str, end: (Duration, Option<FixedOffset>)) {
+        if let Some(running) = self.tracks.remove(track) {
+            let duration = elapsed(
+                self.workday,
+                (utils::workday_offset(&running.start), running.utc_offset),
+                end,
+            );
+            add_activity_duration(&mut self.track_summary, &running, duration);
+        }
+    }
+
+    // ends all running tracks at the given time since the start of the workday
+    fn end_tracks(&mut self, end: (Duration, Option<FixedOffset>)) {
+        let tracks: Vec<String> = self.tracks.keys().cloned().collect();
+        for track in tracks {
+            self.end_track(&track, end);
+        }
+    }
+
+    /// the secondary tracks that are running, by name
+    pub fn tracks(&self) -> &HashMap<String, BlockData> {
+        &self.tracks
+    }
+
+    /// the data of the last block, which has not ended yet
+    pub fn last(&self) -> Option<&BlockData> {
+        self.state.as_ref().map(|data| &data.last)
+    }
+
     // summary, last_activity, last_shortname, last_start
-    pub fn finalize(mut self, at: Option<&NaiveTime>) -> Option<CollectResult> {
-        let (last_activity, last_shortname, last_start) = match &self.state {
-            None => (None, None, None),
-            Some(data) => {
-                let save = (
-                    Some(data.last.activity.to_string()),
-                    Some(
-                        data.last
-                            .tags
-                            .iter()
-                            .find(|s| s.starts_with("="))
-                            .map(|s| s.to_string()),
-                    ),
-                    Some(data.last.start),
-                );
-                if let Some(time) = at {
-                    self.add(Block::NormalBlock(BlockData {
-                        start: time.clone(),
-                        activity: "break".to_string(),
-                        tags: vec![],
-                        distribute: false,
-                    }))
+    pub fn finalize(self, at: Option<&NaiveTime>) -> Option<CollectResult> {
+        self.finish(|collector| {
+            if let Some(time) = at {
+                collector.add(Block::NormalBlock(break_at(time)));
+                collector.end_tracks((utils::workday_offset(time), None));
+            }
+        })
+    }
+
+    /// like finalize, but an activity that has not ended with a break lasts until the day change
+    pub fn finalize_at_day_end(self) -> Option<CollectResult> {
+        self.finish(|collector| {
+            if let Some(data) = &mut collector.state {
+                if !log_parser::is_break(&data.last.activity) {
+                    let duration = elapsed(
+                        collector.workday,
+                        (
+                            utils::workday_offset(&data.last.start),
+                            data.last.utc_offset,
+                        ),
+                        (Duration::days(1), None),
+                    );
+                    data.end_last(break_at(&utils::day_change()), duration);
                 }
-                save
             }
-        };
-        match self.state {
-            None => None,
-            Some(data) => {
-                self.state = None;
-                Some(CollectResult {
-                    summary: data.summary,
-                    final_activity: last_activity.unwrap(),
-                    final_shortname: last_shortname.unwrap(),
-                    final_start: last_start.unwrap(),
-                })
+            collector.end_tracks((Duration::days(1), None));
+        })
+    }
+
+    // remembers the final block, lets `close` end it, and returns the results
+    fn finish(mut self, close: impl FnOnce(&mut BlockCollector)) -> Option<CollectResult> {
+        let last = self.state.as_ref().map(|data| {
+            (
+                data.last.activity.to_string(),
+                data.last.shortname().map(|s| format!("={}", s)),
+                data.last.start,
+            )
+        });
+        close(&mut self);
+        let (mut summary, (last_activity, last_shortname, last_start)) = match (self.state, last) {
+            (Some(data), Some(last)) => {
+                let mut summary = data.summary;
+                // if the final block has not ended, its note counts nevertheless
+                summary.add_note(&data.last);
+                (summary, last)
+            }
+            // a day without times in the log, but with manual durations or tracks
+            _ if !self.adjustments.is_empty() || !self.track_summary.is_empty() => {
+                let start = utils::day_change();
+                (
+                    Summary::starting_at(start),
+                    ("break".to_string(), None, start),
+                )
             }
+            _ => return None,
+        };
+        summary.tracks = self.track_summary;
+        for (duration, data) in self.adjustments {
+            summary.add_duration(&data, duration);
+            *summary
+                .adjustments
+                .entry(data.activity.to_string())
+                .or_insert_with(Duration::zero) += duration;
         }
+        Some(CollectResult {
+            summary,
+            final_activity: last_activity,
+            final_shortname: last_shortname,
+            final_start: last_start,
+        })
+    }
+}
+
+// the block that ends the day at the given time
+fn break_at(time: &NaiveTime) -> BlockData {
+    BlockData {
+        start: *time,
+        activity: "break".to_string(),
+        tags: vec![],
+        metadata: Metadata::new(),
+        note: None,
+        distribute: false,
+        utc_offset: None,
+        start_seconds: false,
     }
 }
+
 /// convenience function: parses logs from the string iterator, collect them, finalize.
 /// Returns a tuple (summary, final activity, final shortname, final start time)
 ///
@@ -240,8 +583,9 @@ impl BlockCollector {
 /// use std::vec;
 /// use std::io::{self, BufReader, BufRead};
 /// use std::fs;
+/// use std::path::Path;
 /// let date = chrono::Local::now().date().naive_local();
-/// let pathname = get_logfile_name(& date);
+/// let pathname = get_logfile_name(Path::new("/home/user/.tt"), &date);
 /// # let pathname="/dev/null";
 /// let reader = BufReader::new(fs::File::open(pathname).expect("Cannot open read for reading")).lines();
 /// # let reader = vec![Ok("8:00 write-tests =test".to_string())].into_iter();
@@ -263,13 +607,148 @@ pub fn collect_blocks<T: Iterator<Item = io::Result<String>>>(
             Ok(b) => collector.add(b),
             Err(err) => return Err(err),
         }
+        let mut diagnostics = collector.take_diagnostics();
+        if let Some(pos) = diagnostics
+            .iter()
+            .position(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            return Err(diagnostics.swap_remove(pos).error);
+        }
     }
     Ok(collector.finalize(add_ending_at))
 }
 
+/// like collect_blocks, but lines that cannot be parsed or collected are skipped.
+/// The problems found are returned next to the result, with their line numbers.
+///
+/// ```
+/// use timetracker::collector;
+/// use timetracker::error::Severity;
+/// let lines = vec![
+///     Ok("8:00 email".to_string()),
+///     Ok("9:00 really".to_string()),
+///     Ok("7:30 too-early".to_string()),
+///     Ok("10:00 break".to_string()),
+/// ];
+/// let (collected, diagnostics) = collector::collect_blocks_lenient(lines.into_iter(), None);
+/// assert_eq!(collected.unwrap().summary.work_time.num_minutes(), 120);
+/// assert_eq!(diagnostics.len(), 2);
+/// assert_eq!(diagnostics[0].line_number, Some(2));
+/// assert_eq!(diagnostics[1].severity, Severity::Error);
+/// assert_eq!(diagnostics[1].line_number, Some(3));
+/// ```
+pub fn collect_blocks_lenient<T: Iterator<Item = io::Result<String>>>(
+    line_iterator: T,
+    add_ending_at: Option<&NaiveTime>,
+) -> (Option<CollectResult>, Vec<Diagnostic>) {
+    let mut collector = BlockCollector::new();
+    for (index, line) in line_iterator.enumerate() {
+        collector.add_line(index + 1, line);
+    }
+    let diagnostics = collector.take_diagnostics();
+    (collector.finalize(add_ending_at), diagnostics)
+}
+
+/// returns the activity that is still open at the end of a day's log, i.e. the log does not end
+/// with a break. On the next workday, it continues at the day change.
+/// Lines with problems are skipped, they are reported for the day they belong to.
+pub fn open_activity<T: Iterator<Item = io::Result<String>>>(
+    line_iterator: T,
+) -> Option<BlockData> {
+    carried_over(line_iterator)
+        .into_iter()
+        .find_map(|block| match block {
+            Block::NormalBlock(data) => Some(data),
+            _ => None,
+        })
+}
+
+/// returns the blocks that continue at the day change of the next workday: the open activity
+/// (see open_activity) and the secondary tracks that are still running.
+///
+/// ```
+/// use timetracker::collector;
+/// use timetracker::log_parser::Block;
+/// let lines = vec![Ok("22:00 @oncall".to_string()), Ok("23:00 break".to_string())];
+/// let blocks = collector::carried_over(lines.into_iter());
+/// match &blocks[..] {
+///     [Block::TrackBlock(track, data)] => {
+///         assert_eq!(track, "oncall");
+///         assert_eq!(data.start, chrono::NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+///     }
+///     _ => panic!("expected only the track"),
+/// }
+/// ```
+pub fn carried_over<T: Iterator<Item = io::Result<String>>>(line_iterator: T) -> Vec<Block> {
+    let mut collector = BlockCollector::new();
+    for block in line_iterator.flat_map(log_parser::Block::from_line) {
+        collector.add(block);
+    }
+    let at_day_change = |data: &BlockData| BlockData {
+        start: utils::day_change(),
+        note: None,
+        utc_offset: None,
+        start_seconds: false,
+        ..data.clone()
+    };
+    let activity = collector
+        .last()
+        .filter(|data| !log_parser::is_break(&data.activity))
+        .map(|data| Block::NormalBlock(at_day_change(data)));
+    let tracks = collector
+        .tracks()
+        .iter()
+        .sorted_by_key(|(track, _)| track.to_string())
+        .map(|(track, data)| Block::TrackBlock(track.to_string(), at_day_change(data)));
+    activity.into_iter().chain(tracks).collect()
+}
+
+/// like collect_blocks_lenient, but for a logical workday: the activity that is still open at
+/// the end of the previous day continues at the day change. Without add_ending_at, an activity
+/// that is open at the end of the log lasts until the next day change.
+///
+/// ```
+/// use timetracker::collector::{self, CollectResult};
+/// use chrono::NaiveDate;
+/// let workday = NaiveDate::from_ymd_opt(2020, 6, 16).unwrap();
+/// let previous_day = vec![Ok("22:00 oncall".to_string())].into_iter();
+/// let lines = vec![Ok("1:30 break".to_string()), Ok("9:00 email".to_string())].into_iter();
+/// let (collected, diagnostics) = collector::collect_workday(&workday, previous_day, lines, None);
+/// let CollectResult{summary, final_activity, ..} = collected.unwrap();
+/// assert_eq!(summary.activities["oncall"].0.num_minutes(), 90);
+/// assert_eq!(summary.work_time.num_minutes(), 90 + 15 * 60);
+/// assert_eq!(final_activity, "email");
+/// assert!(diagnostics.is_empty());
+/// ```
+pub fn collect_workday<T, U>(
+    workday: &NaiveDate,
+    previous_day: T,
+    line_iterator: U,
+    add_ending_at: Option<&NaiveTime>,
+) -> (Option<CollectResult>, Vec<Diagnostic>)
+where
+    T: Iterator<Item = io::Result<String>>,
+    U: Iterator<Item = io::Result<String>>,
+{
+    let mut collector = BlockCollector::for_workday(workday);
+    for block in carried_over(previous_day) {
+        collector.add(block);
+    }
+    for (index, line) in line_iterator.enumerate() {
+        collector.add_line(index + 1, line);
+    }
+    let diagnostics = collector.take_diagnostics();
+    let collected = match add_ending_at {
+        Some(_) => collector.finalize(add_ending_at),
+        None => collector.finalize_at_day_end(),
+    };
+    (collected, diagnostics)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::configfile::TTConfig;
     use crate::error::TTErrorKind;
     use crate::utils::setup_line_reader;
     use std::iter::{self, FromIterator};
@@ -285,7 +764,11 @@ mod tests {
             start: NaiveTime::from_hms(8, 30, 0),
             activity: "setup".to_string(),
             tags: vec![],
+            metadata: Metadata::new(),
+            note: None,
             distribute: false,
+            utc_offset: None,
+            start_seconds: false,
         }));
         collector
     }
@@ -297,7 +780,11 @@ mod tests {
             start: NaiveTime::from_hms(10, 0, 0),
             activity: "end".to_string(),
             tags: vec![],
+            metadata: Metadata::new(),
+            note: None,
             distribute: false,
+            utc_offset: None,
+            start_seconds: false,
         }));
         let CollectResult {
             summary,
@@ -328,7 +815,11 @@ mod tests {
             start: NaiveTime::from_hms(9, 0, 0),
             activity: "correct".to_string(),
             tags: vec!["=short".to_string(), "tag1".to_string()],
+            metadata: Metadata::new(),
+            note: None,
             distribute: false,
+            utc_offset: None,
+            start_seconds: false,
         }));
         let CollectResult {
             summary,
@@ -369,7 +860,11 @@ mod tests {
             start: NaiveTime::from_hms(10, 0, 0),
             activity: "_something".to_string(),
             tags: vec!["other".to_string()],
+            metadata: Metadata::new(),
+            note: None,
             distribute: true,
+            utc_offset: None,
+            start_seconds: false,
         }));
         collector.add(Block::TimeCorrection(NaiveTime::from_hms(11, 0, 0)));
         let CollectResult {
@@ -410,12 +905,16 @@ mod tests {
     #[test]
     fn comment_block() {
         let mut collector = setup_test();
-        collector.add(Block::CommentBlock(()));
+        collector.add(Block::CommentBlock("# comment".to_string()));
         collector.add(Block::NormalBlock(BlockData {
             start: NaiveTime::from_hms(10, 0, 0),
             activity: "end".to_string(),
             tags: vec![],
+            metadata: Metadata::new(),
+            note: None,
             distribute: false,
+            utc_offset: None,
+            start_seconds: false,
         }));
         let CollectResult {
             summary,
@@ -519,28 +1018,44 @@ mod tests {
             start: NaiveTime::from_hms(9, 30, 0),
             activity: "setup".to_string(),
             tags: vec!["sometag".to_string()],
+            metadata: Metadata::new(),
+            note: None,
             distribute: false,
+            utc_offset: None,
+            start_seconds: false,
         }));
         collector.add(Block::TimeCorrection(NaiveTime::from_hms(11, 0, 0)));
         collector.add(Block::NormalBlock(BlockData {
             start: NaiveTime::from_hms(11, 5, 0),
             activity: "break".to_string(),
             tags: vec![],
+            metadata: Metadata::new(),
+            note: None,
             distribute: false,
+            utc_offset: None,
+            start_seconds: false,
         }));
         collector.add(Block::TimeCorrection(NaiveTime::from_hms(11, 10, 0)));
         collector.add(Block::NormalBlock(BlockData {
             start: NaiveTime::from_hms(11, 20, 0),
             activity: "_distribute".to_string(),
             tags: vec![],
+            metadata: Metadata::new(),
+            note: None,
             distribute: true,
+            utc_offset: None,
+            start_seconds: false,
         }));
         collector.add(Block::TimeCorrection(NaiveTime::from_hms(11, 22, 0)));
         collector.add(Block::NormalBlock(BlockData {
             start: NaiveTime::from_hms(11, 32, 0),
             activity: "_distribute".to_string(),
             tags: vec!["sometag".to_string()],
+            metadata: Metadata::new(),
+            note: None,
             distribute: true,
+            utc_offset: None,
+            start_seconds: false,
         }));
         collector.add(Block::TimeCorrection(NaiveTime::from_hms(11, 23, 0)));
         let CollectResult {
@@ -612,6 +1127,316 @@ mod tests {
         assert_eq!(final_start, NaiveTime::from_hms(10, 30, 0));
     }
 
+    #[test]
+    fn intervals_with_corrections() {
+        let lines = setup_line_reader(vec![
+            "8:30 write-tests =test",
+            "9:00 email",
+            "9:20 really 9:30",
+            "10:30 blabla",
+            "11:00 really meeting",
+        ]);
+        let CollectResult { summary, .. } =
+            collect_blocks(lines, Some(&NaiveTime::from_hms_opt(12, 0, 0).unwrap()))
+                .unwrap()
+                .unwrap();
+        let intervals: Vec<String> = summary
+            .intervals
+            .iter()
+            .map(|i| {
+                format!(
+                    "{}-{} {} {}",
+                    utils::format_time(&i.start),
+                    utils::format_time(&i.end),
+                    i.duration.num_minutes(),
+                    i.data.activity
+                )
+            })
+            .collect();
+        assert_eq!(
+            intervals,
+            vec![
+                "08:30-09:30 60 write-tests",
+                "09:30-10:30 60 email",
+                "10:30-12:00 90 meeting",
+            ]
+        );
+        assert_eq!(summary.intervals[0].data.shortname(), Some("test"));
+    }
+
+    #[test]
+    fn test_collect_blocks_with_metadata() {
+        let lines = setup_line_reader(vec![
+            "8:30 JIRA-1 customer=acme",
+            "9:00 JIRA-1 customer=other billable=no",
+            "9:20 really 9:30",
+            "10:00 break",
+        ]);
+        let CollectResult { summary, .. } = collect_blocks(lines, None).unwrap().unwrap();
+        let key = |k: &str, v: &str| (k.to_string(), v.to_string());
+        let expected = BTreeMap::from_iter(vec![
+            (key("customer", "acme"), Duration::minutes(60)),
+            (key("customer", "other"), Duration::minutes(30)),
+            (key("billable", "no"), Duration::minutes(30)),
+        ]);
+        assert_eq!(summary.metadata.get("JIRA-1"), Some(&expected));
+        assert_eq!(
+            summary.activities.get("JIRA-1").unwrap().0.num_minutes(),
+            90
+        );
+    }
+
+    #[test]
+    fn test_collect_blocks_with_notes() {
+        let lines = setup_line_reader(vec![
+            "8:30 JIRA-1 -- fixed pagination",
+            "9:00 JIRA-2",
+            "9:30 JIRA-1 -- reviewed",
+            "10:00 JIRA-1 -- reviewed",
+            "10:30 really JIRA-3 -- wrong ticket",
+        ]);
+        let CollectResult { summary, .. } = collect_blocks(lines, None).unwrap().unwrap();
+        assert_eq!(
+            summary.notes.get("JIRA-1"),
+            Some(&vec![
+                "fixed pagination".to_string(),
+                "reviewed".to_string()
+            ])
+        );
+        assert_eq!(summary.notes.get("JIRA-2"), None);
+        assert_eq!(
+            summary.notes.get("JIRA-3"),
+            Some(&vec!["wrong ticket".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_collect_blocks_with_seconds() {
+        let lines = setup_line_reader(vec![
+            "8:30 write-tests",
+            "8:30:40 email",
+            "8:31:10 really 8:31:05",
+            "8:32 break",
+        ]);
+        let CollectResult { summary, .. } = collect_blocks(lines, None).unwrap().unwrap();
+        assert_eq!(summary.work_time.num_seconds(), 60 + 60);
+        assert_eq!(
+            summary.activities.get("write-tests").unwrap().0,
+            Duration::seconds(65)
+        );
+        assert_eq!(
+            summary.activities.get("email").unwrap().0,
+            Duration::seconds(55)
+        );
+        assert_eq!(summary.end, NaiveTime::from_hms_opt(8, 32, 0).unwrap());
+    }
+
+    #[test]
+    fn test_collect_blocks_with_utc_offset() {
+        // switch to daylight saving time at 2:00
+        let lines = setup_line_reader(vec![
+            "1:30+01:00 oncall",
+            "3:30+02:00 break",
+            "3:45 really 3:40", // keeps the offset of the break
+            "4:00 email",
+            "4:30 break",
+        ]);
+        let CollectResult { summary, .. } = collect_blocks(lines, None).unwrap().unwrap();
+        assert_eq!(
+            summary.activities.get("oncall").unwrap().0.num_minutes(),
+            70
+        );
+        assert_eq!(summary.work_time.num_minutes(), 70 + 30);
+        assert_eq!(summary.start, NaiveTime::from_hms_opt(1, 30, 0).unwrap());
+        assert_eq!(summary.end, NaiveTime::from_hms_opt(4, 30, 0).unwrap());
+    }
+
+    #[test]
+    fn test_collect_blocks_with_tracks() {
+        let lines = setup_line_reader(vec![
+            "8:00 email",
+            "9:00 @oncall",
+            "9:30 JIRA-7",
+            "10:00 @deploy JIRA-7",
+            "11:00 @oncall break",
+            "11:30 @deploy break",
+            "12:00 break",
+        ]);
+        let CollectResult { summary, .. } = collect_blocks(lines, None).unwrap().unwrap();
+        assert_eq!(summary.work_time.num_minutes(), 4 * 60);
+        assert!(!summary.activities.contains_key("oncall"));
+        let (oncall, _) = summary.tracks.get("oncall").unwrap();
+        assert_eq!(oncall.num_minutes(), 120);
+        let (deploy, deploy_tags) = summary.tracks.get("deploy").unwrap();
+        assert_eq!(deploy.num_minutes(), 90);
+        assert_eq!(deploy_tags.get("JIRA-7").unwrap().num_minutes(), 90);
+    }
+
+    #[test]
+    fn test_collect_blocks_with_manual_durations() {
+        let lines = setup_line_reader(vec![
+            "8:00 email",
+            "+1:30 JIRA-7 travel customer=acme",
+            "9:00 JIRA-7",
+            "-0:20 email",
+            "10:00 break",
+        ]);
+        let CollectResult {
+            summary,
+            final_activity,
+            ..
+        } = collect_blocks(lines, None).unwrap().unwrap();
+        assert_eq!(summary.start, NaiveTime::from_hms_opt(8, 0, 0).unwrap());
+        assert_eq!(summary.end, NaiveTime::from_hms_opt(10, 0, 0).unwrap());
+        assert_eq!(final_activity, "break");
+        assert_eq!(summary.work_time.num_minutes(), 120 + 90 - 20);
+        let (jira, jira_tags) = summary.activities.get("JIRA-7").unwrap();
+        assert_eq!(jira.num_minutes(), 150);
+        assert_eq!(jira_tags.get("travel").unwrap().num_minutes(), 90);
+        assert_eq!(summary.activities.get("email").unwrap().0.num_minutes(), 40);
+        assert_eq!(summary.adjustments.get("JIRA-7").unwrap().num_minutes(), 90);
+        assert_eq!(summary.adjustments.get("email").unwrap().num_minutes(), -20);
+    }
+
+    #[test]
+    fn test_collect_only_manual_durations() {
+        let lines = setup_line_reader(vec!["+8:00 travel"]);
+        let CollectResult {
+            summary,
+            final_activity,
+            ..
+        } = collect_blocks(lines, None).unwrap().unwrap();
+        assert_eq!(summary.work_time.num_minutes(), 8 * 60);
+        assert_eq!(final_activity, "break");
+    }
+
+    #[test]
+    fn test_collect_tracks_with_problems() {
+        let lines = setup_line_reader(vec![
+            "8:00 email",
+            "9:00 @oncall break",
+            "10:00 @oncall",
+            "9:45 @oncall break",
+            "11:00 break",
+        ]);
+        let (collected, diagnostics) =
+            collect_blocks_lenient(lines, Some(&NaiveTime::from_hms_opt(12, 0, 0).unwrap()));
+        let severities: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.severity, d.line_number))
+            .collect();
+        assert_eq!(
+            severities,
+            vec![(Severity::Error, Some(2)), (Severity::Error, Some(4))]
+        );
+        // the running track ends with the final time
+        let summary = collected.unwrap().summary;
+        assert_eq!(summary.tracks.get("oncall").unwrap().0.num_minutes(), 120);
+    }
+
+    fn workday() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2020, 6, 16).unwrap()
+    }
+
+    #[test]
+    fn test_collect_workday_carries_tracks() {
+        let previous_day = setup_line_reader(vec!["22:00 @oncall", "23:00 break"]);
+        let lines = setup_line_reader(vec!["8:00 email", "9:00 @oncall break", "10:00 break"]);
+        let (collected, diagnostics) = collect_workday(&workday(), previous_day, lines, None);
+        assert!(diagnostics.is_empty());
+        let summary = collected.unwrap().summary;
+        assert_eq!(
+            summary.tracks.get("oncall").unwrap().0.num_minutes(),
+            9 * 60
+        );
+        assert_eq!(summary.work_time.num_minutes(), 120);
+    }
+
+    fn set_day_change_hour(hour: u32) {
+        let config = format!("day-change-hour = {}", hour);
+        TTConfig::init(config.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_collect_workday_carries_open_activity() {
+        let previous_lines = setup_line_reader(vec!["18:00 JIRA-1", "22:00 oncall =call"]);
+        let lines = setup_line_reader(vec!["0:45 break", "9:00 email"]);
+        let CollectResult {
+            summary,
+            final_activity,
+            ..
+        } = collect_workday(
+            &workday(),
+            previous_lines,
+            lines,
+            Some(&NaiveTime::from_hms_opt(10, 0, 0).unwrap()),
+        )
+        .0
+        .unwrap();
+        assert_eq!(summary.start, NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(
+            summary.activities.get("oncall").unwrap().0.num_minutes(),
+            45
+        );
+        assert_eq!(
+            summary.activities.get("oncall").unwrap().1["=call"].num_minutes(),
+            45
+        );
+        assert_eq!(summary.work_time.num_minutes(), 45 + 60);
+        assert!(summary.activities.get("JIRA-1").is_none());
+        assert_eq!(final_activity, "email");
+    }
+
+    #[test]
+    fn test_collect_workday_ends_at_day_change() {
+        let previous_lines = setup_line_reader(vec!["18:00 break"]);
+        let lines = setup_line_reader(vec!["9:00 email", "22:00 oncall"]);
+        let CollectResult {
+            summary,
+            final_activity,
+            final_start,
+            ..
+        } = collect_workday(&workday(), previous_lines, lines, None)
+            .0
+            .unwrap();
+        assert_eq!(summary.start, NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+        assert_eq!(summary.end, NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(
+            summary.activities.get("oncall").unwrap().0.num_minutes(),
+            120
+        );
+        assert_eq!(summary.work_time.num_minutes(), 15 * 60);
+        assert_eq!(final_activity, "oncall");
+        assert_eq!(final_start, NaiveTime::from_hms_opt(22, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_collect_workday_with_day_change_hour() {
+        set_day_change_hour(4);
+        let previous_lines = setup_line_reader(vec!["20:00 oncall", "1:00 break"]);
+        let lines = setup_line_reader(vec!["9:00 email", "23:00 deploy", "1:30 break"]);
+        let CollectResult { summary, .. } =
+            collect_workday(&workday(), previous_lines, lines, None)
+                .0
+                .unwrap();
+        assert!(summary.activities.get("oncall").is_none());
+        assert_eq!(
+            summary.activities.get("deploy").unwrap().0.num_minutes(),
+            150
+        );
+        assert_eq!(summary.work_time.num_minutes(), 14 * 60 + 150);
+        assert_eq!(summary.end, NaiveTime::from_hms_opt(1, 30, 0).unwrap());
+
+        let previous_lines = setup_line_reader(vec!["23:00 oncall"]);
+        let lines = setup_line_reader(vec![]);
+        let CollectResult { summary, .. } =
+            collect_workday(&workday(), previous_lines, lines, None)
+                .0
+                .unwrap();
+        assert_eq!(summary.start, NaiveTime::from_hms_opt(4, 0, 0).unwrap());
+        assert_eq!(summary.activities.get("oncall").unwrap().0.num_hours(), 24);
+    }
+
     #[test]
     fn test_collect_blocks_error() {
         let lines = setup_line_reader(vec!["8:30 write-tests", "10:05 really 10:00 parsing-error"]);
@@ -619,8 +1444,48 @@ mod tests {
             Err(TTError {
                 kind: TTErrorKind::ParseError(_msg, line),
                 context: _,
+                ..
             }) => assert_eq!(line, "10:05 really 10:00 parsing-error"),
             _ => panic!("Parse Error expected"),
         }
     }
+
+    #[test]
+    fn test_collect_blocks_time_goes_backward() {
+        let lines = setup_line_reader(vec!["8:30 write-tests", "8:00 email", "9:00 break"]);
+        match collect_blocks(lines, None) {
+            Err(TTError {
+                kind: TTErrorKind::LogError(_),
+                context: _,
+                ..
+            }) => (),
+            _ => panic!("Log Error expected"),
+        }
+    }
+
+    #[test]
+    fn test_collect_blocks_lenient() {
+        let lines = setup_line_reader(vec![
+            "8:00 really 7:45",
+            "8:30 write-tests",
+            "10:05 really 10:00 parsing-error",
+            "8:00 email",
+            "9:00 break",
+        ]);
+        let (collected, diagnostics) = collect_blocks_lenient(lines, None);
+        let CollectResult { summary, .. } = collected.unwrap();
+        assert_eq!(summary.work_time.num_minutes(), 30);
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.severity, d.line_number))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Severity::Error, Some(1)),
+                (Severity::Error, Some(3)),
+                (Severity::Error, Some(4))
+            ]
+        );
+    }
 }
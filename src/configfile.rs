@@ -1,7 +1,10 @@
 use crate::error::TTError;
+use crate::storage::Layout;
 use config::{Config, ConfigError};
+use regex::Regex;
 use std::cell::RefCell;
 use std::io::BufRead;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::time::Duration;
 
@@ -12,25 +15,94 @@ pub struct WatchI3Config {
     pub timeblock_empty: Duration,
 }
 
+/// maps the summary of a meeting to an activity for `tt import-ics`. The activity can refer to
+/// the groups of the pattern like `$1`.
+#[derive(Debug, Clone)]
+pub struct IcsRule {
+    pub pattern: Regex,
+    pub activity: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportIcsConfig {
+    /// the first rule that matches the summary wins
+    pub rules: Vec<IcsRule>,
+    /// the activity of meetings that no rule matches, they are skipped without it
+    pub default_activity: Option<String>,
+}
+
+/// how the log entries are stored (storage in the config file)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageKind {
+    /// one file per day, the default
+    DailyFiles,
+    /// all days in one file, every line starts with its date
+    SingleFile,
+}
+
 #[derive(Debug, Clone)]
 pub struct TTConfig {
     pub prefix: Option<String>,
+    pub day_change_hour: u32,
+    pub record_utc_offset: bool,
+    pub storage: StorageKind,
+    pub layout: Layout,
+    /// where tt keeps its files, if neither --data-dir nor TT_HOME is given
+    pub data_dir: Option<PathBuf>,
+    /// the days after which `tt add` archives unused activities, and `tt activities prune` if no
+    /// day is given
+    pub archive_activities_after: Option<u32>,
+    /// whether `tt add` logs the alias that was typed as tag `alias:...`
+    pub record_alias: bool,
     pub watch_i3: WatchI3Config,
+    pub import_ics: ImportIcsConfig,
 }
 
 const DEFAULT: TTConfig = TTConfig {
     prefix: None,
+    day_change_hour: 0,
+    record_utc_offset: false,
+    storage: StorageKind::DailyFiles,
+    layout: Layout::Flat,
+    data_dir: None,
+    archive_activities_after: None,
+    record_alias: false,
     watch_i3: WatchI3Config {
         granularity: Duration::from_secs(10),
         timeblock: Duration::from_secs(120),
         timeblock_empty: Duration::from_secs(300),
     },
+    import_ics: ImportIcsConfig {
+        rules: Vec::new(),
+        default_activity: None,
+    },
 };
 
 thread_local! {
     static SETTINGS: RefCell<Rc<TTConfig>> = RefCell::new(Rc::new(TTConfig::default()));
 }
 
+// the rules of import-ics.rules, like { pattern = "^Daily", activity = "_daily" }
+fn ics_rules(config: &Config) -> Result<Vec<IcsRule>, ConfigError> {
+    let mut rules = Vec::new();
+    for rule in or_none(config.get_array("import-ics.rules"))?.unwrap_or_default() {
+        let mut rule = rule.into_table()?;
+        let mut field = |name: &str| match rule.remove(name) {
+            Some(value) => value.into_str(),
+            None => Err(ConfigError::Message(format!(
+                "every rule in import-ics.rules needs a {}",
+                name
+            ))),
+        };
+        let (pattern, activity) = (field("pattern")?, field("activity")?);
+        let pattern = Regex::new(&pattern).map_err(|err| {
+            ConfigError::Message(format!("invalid pattern in import-ics.rules: {}", err))
+        })?;
+        rules.push(IcsRule { pattern, activity });
+    }
+    Ok(rules)
+}
+
 fn or_none<T>(value: Result<T, ConfigError>) -> Result<Option<T>, ConfigError> {
     match value {
         Ok(value) => Ok(Some(value)),
@@ -40,6 +112,16 @@ fn or_none<T>(value: Result<T, ConfigError>) -> Result<Option<T>, ConfigError> {
 }
 
 impl TTConfig {
+    /// reads the config file, values that cannot be read are errors
+    /// ```
+    /// use timetracker::configfile::TTConfig;
+    /// assert!(TTConfig::init("day-change-hour = 4".as_bytes()).is_ok());
+    /// assert_eq!(TTConfig::get().day_change_hour, 4);
+    /// assert!(TTConfig::init("day-change-hour = \"4am\"".as_bytes()).is_err());
+    /// assert!(TTConfig::init("day-change-hour = 24".as_bytes()).is_err());
+    /// assert!(TTConfig::init("record-utc-offset = \"sometimes\"".as_bytes()).is_err());
+    /// assert!(TTConfig::init("record-alias = \"maybe\"".as_bytes()).is_err());
+    /// ```
     pub fn init(mut configfile: impl BufRead) -> Result<(), TTError> {
         let mut config_content = String::new();
         configfile.read_to_string(&mut config_content)?;
@@ -48,8 +130,49 @@ impl TTConfig {
             &config_content,
             config::FileFormat::Toml,
         ))?;
+        let day_change_hour = or_none(config.get_int("day-change-hour"))?.unwrap_or(0);
+        if !(0..24).contains(&day_change_hour) {
+            return Err(ConfigError::Message(format!(
+                "day-change-hour must be between 0 and 23, but is {}",
+                day_change_hour
+            ))
+            .into());
+        }
+        let storage = match or_none(config.get_str("storage"))?.as_deref() {
+            None | Some("daily-files") => StorageKind::DailyFiles,
+            Some("single-file") => StorageKind::SingleFile,
+            Some(other) => {
+                return Err(ConfigError::Message(format!(
+                    "storage must be daily-files or single-file, but is {}",
+                    other
+                ))
+                .into())
+            }
+        };
+        let layout = or_none(config.get_str("layout"))?
+            .map(|layout| layout.parse())
+            .transpose()
+            .map_err(ConfigError::Message)?
+            .unwrap_or(Layout::Flat);
+        let archive_activities_after = match or_none(config.get_int("archive-activities-after"))? {
+            Some(days) if days < 1 => {
+                return Err(ConfigError::Message(format!(
+                    "archive-activities-after must be at least 1 day, but is {}",
+                    days
+                ))
+                .into())
+            }
+            days => days.map(|days| days as u32),
+        };
         let new_config = TTConfig {
             prefix: or_none(config.get_str("prefix"))?,
+            day_change_hour: day_change_hour as u32,
+            record_utc_offset: or_none(config.get_bool("record-utc-offset"))?.unwrap_or(false),
+            storage,
+            layout,
+            data_dir: or_none(config.get_str("data-dir"))?.map(PathBuf::from),
+            archive_activities_after,
+            record_alias: or_none(config.get_bool("record-alias"))?.unwrap_or(false),
             watch_i3: WatchI3Config {
                 granularity: Duration::from_secs(
                     config.get_int("watch-i3.granularity").unwrap_or(10) as u64,
@@ -61,6 +184,10 @@ impl TTConfig {
                     config.get_int("watch-i3.timeblock_empty").unwrap_or(600) as u64,
                 ),
             },
+            import_ics: ImportIcsConfig {
+                rules: ics_rules(config)?,
+                default_activity: or_none(config.get_str("import-ics.default-activity"))?,
+            },
         };
         SETTINGS.with(move |settings| {
             settings.replace(Rc::new(new_config));
@@ -0,0 +1,199 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::result::Result;
+
+use crate::configfile::TTConfig;
+use crate::error::{TTError, TTErrorKind};
+use crate::utils::{FileProxy, NamedFile};
+
+/// where tt keeps its files: the data directory with the logs, the activities file and the
+/// journal, and the config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locations {
+    pub data_dir: PathBuf,
+    pub configfile: PathBuf,
+}
+
+impl Locations {
+    /// finds the data directory, in this order:
+    /// - the `--data-dir` flag
+    /// - the environment variable `TT_HOME`
+    /// - `$XDG_DATA_HOME/tt` (default `~/.local/share/tt`), if that directory exists
+    /// - `data-dir` in the config file `~/.tt/config.toml`
+    /// - `~/.tt`
+    ///
+    /// The config file is in the data directory for the flag and `TT_HOME`, and in
+    /// `$XDG_CONFIG_HOME/tt` (default `~/.config/tt`) for the XDG directories.
+    pub fn resolve(flag: Option<PathBuf>) -> Result<Locations, TTError> {
+        resolve_with(
+            flag,
+            &|name| env::var_os(name).map(PathBuf::from),
+            dirs::home_dir(),
+            &Path::is_dir,
+            &|configfile| {
+                // errors in the config file are reported when tt reads it for the command
+                let reader = NamedFile::new(configfile.to_path_buf()).reader().ok()?;
+                TTConfig::init(reader).ok()?;
+                TTConfig::get().data_dir.clone()
+            },
+        )
+    }
+}
+
+// the resolution of Locations::resolve, with the environment as parameters
+fn resolve_with(
+    flag: Option<PathBuf>,
+    var: &dyn Fn(&str) -> Option<PathBuf>,
+    home: Option<PathBuf>,
+    is_dir: &dyn Fn(&Path) -> bool,
+    configured_data_dir: &dyn Fn(&Path) -> Option<PathBuf>,
+) -> Result<Locations, TTError> {
+    let var = |name: &str| var(name).filter(|value| !value.as_os_str().is_empty());
+    if let Some(data_dir) = flag.or_else(|| var("TT_HOME")) {
+        return Ok(Locations {
+            configfile: data_dir.join("config.toml"),
+            data_dir,
+        });
+    }
+    let home = home.ok_or_else(|| {
+        TTError::new(TTErrorKind::UsageError(
+            "cannot figure out the home directory, use --data-dir or TT_HOME",
+        ))
+    })?;
+    let xdg_data_dir = var("XDG_DATA_HOME")
+        .unwrap_or_else(|| home.join(".local/share"))
+        .join("tt");
+    if is_dir(&xdg_data_dir) {
+        let xdg_config_home = var("XDG_CONFIG_HOME").unwrap_or_else(|| home.join(".config"));
+        return Ok(Locations {
+            data_dir: xdg_data_dir,
+            configfile: xdg_config_home.join("tt").join("config.toml"),
+        });
+    }
+    let default_dir = home.join(".tt");
+    let configfile = default_dir.join("config.toml");
+    // a relative data-dir is relative to the directory of the config file
+    let data_dir = configured_data_dir(&configfile)
+        .map(|data_dir| default_dir.join(data_dir))
+        .unwrap_or(default_dir);
+    Ok(Locations {
+        data_dir,
+        configfile,
+    })
+}
+
+/// removes `--data-dir DIR` or `--data-dir=DIR` before the subcommand from the arguments and
+/// returns the directory
+/// ```
+/// use std::path::PathBuf;
+/// use timetracker::datadir::take_data_dir_arg;
+/// let mut args: Vec<String> = vec!["tt", "--data-dir", "/tmp/tt", "report"]
+///     .into_iter().map(String::from).collect();
+/// assert_eq!(take_data_dir_arg(&mut args).unwrap(), Some(PathBuf::from("/tmp/tt")));
+/// assert_eq!(args, vec!["tt", "report"]);
+/// ```
+pub fn take_data_dir_arg(args: &mut Vec<String>) -> Result<Option<PathBuf>, TTError> {
+    match args.get(1).map(String::as_str) {
+        Some("--data-dir") if args.len() > 2 => {
+            let dir = args.remove(2);
+            args.remove(1);
+            Ok(Some(PathBuf::from(dir)))
+        }
+        Some("--data-dir") => Err(TTError::new(TTErrorKind::UsageError(
+            "--data-dir needs a directory",
+        ))),
+        Some(arg) if arg.starts_with("--data-dir=") => {
+            let dir = PathBuf::from(&arg["--data-dir=".len()..]);
+            args.remove(1);
+            Ok(Some(dir))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn resolve(
+        flag: Option<&str>,
+        vars: &[(&str, &str)],
+        dirs: &[&str],
+        configured: Option<&str>,
+    ) -> Locations {
+        let vars: HashMap<String, PathBuf> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), PathBuf::from(value)))
+            .collect();
+        resolve_with(
+            flag.map(PathBuf::from),
+            &|name| vars.get(name).cloned(),
+            Some(PathBuf::from("/home/user")),
+            &|path| dirs.iter().any(|dir| path == Path::new(dir)),
+            &|_| configured.map(PathBuf::from),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn in_order() {
+        let xdg = "/home/user/.local/share/tt";
+        let all = resolve(
+            Some("/flag"),
+            &[("TT_HOME", "/tt-home")],
+            &[xdg],
+            Some("/configured"),
+        );
+        assert_eq!(all.data_dir, PathBuf::from("/flag"));
+        assert_eq!(all.configfile, PathBuf::from("/flag/config.toml"));
+        let tt_home = resolve(None, &[("TT_HOME", "/tt-home")], &[xdg], None);
+        assert_eq!(tt_home.data_dir, PathBuf::from("/tt-home"));
+        let xdg_dirs = resolve(None, &[], &[xdg], Some("/configured"));
+        assert_eq!(xdg_dirs.data_dir, PathBuf::from(xdg));
+        assert_eq!(
+            xdg_dirs.configfile,
+            PathBuf::from("/home/user/.config/tt/config.toml")
+        );
+        let configured = resolve(None, &[], &[], Some("logs"));
+        assert_eq!(configured.data_dir, PathBuf::from("/home/user/.tt/logs"));
+        assert_eq!(
+            configured.configfile,
+            PathBuf::from("/home/user/.tt/config.toml")
+        );
+        let default = resolve(None, &[("TT_HOME", "")], &[], None);
+        assert_eq!(default.data_dir, PathBuf::from("/home/user/.tt"));
+    }
+
+    #[test]
+    fn xdg_variables() {
+        let locations = resolve(
+            None,
+            &[("XDG_DATA_HOME", "/data"), ("XDG_CONFIG_HOME", "/config")],
+            &["/data/tt"],
+            None,
+        );
+        assert_eq!(locations.data_dir, PathBuf::from("/data/tt"));
+        assert_eq!(
+            locations.configfile,
+            PathBuf::from("/config/tt/config.toml")
+        );
+    }
+
+    #[test]
+    fn without_home() {
+        assert!(resolve_with(None, &|_| None, None, &|_| false, &|_| None).is_err());
+    }
+
+    #[test]
+    fn data_dir_arg() {
+        let mut args: Vec<String> = vec!["tt".to_string(), "--data-dir=/tmp/tt".to_string()];
+        assert_eq!(
+            take_data_dir_arg(&mut args).unwrap(),
+            Some(PathBuf::from("/tmp/tt"))
+        );
+        assert_eq!(args, vec!["tt"]);
+        let mut args: Vec<String> = vec!["tt".to_string(), "--data-dir".to_string()];
+        assert!(take_data_dir_arg(&mut args).is_err());
+    }
+}
@@ -3,11 +3,28 @@ use itertools::Itertools;
 use std::error;
 use std::fmt;
 use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug)]
 pub struct TTError {
     pub kind: TTErrorKind,
     pub context: Vec<String>,
+    // boxed, to keep results with errors small
+    pub details: Box<ErrorDetails>,
+}
+
+/// where an error has been found, as far as it is known, and how to fix it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorDetails {
+    pub path: Option<PathBuf>,
+    /// line number in the file, starting with 1
+    pub line_number: Option<usize>,
+    /// the text of the line
+    pub line: Option<String>,
+    /// byte range of the offending word in the line
+    pub span: Option<Range<usize>>,
+    pub hint: Option<&'static str>,
 }
 
 #[derive(Debug)]
@@ -17,6 +34,8 @@ pub enum TTErrorKind {
     // message, line
     ActivityConfigError(&'static str),
     ParseError(&'static str, String),
+    // the log can be parsed, but does not make sense
+    LogError(&'static str),
     JsonError(serde_json::Error),
     ConfigError(ConfigError),
     Error(String),
@@ -27,13 +46,82 @@ impl TTError {
         TTError {
             kind,
             context: vec![],
+            details: Box::default(),
         }
     }
 
-    pub fn context(self, str: String) -> TTError {
-        let TTError { kind, mut context } = self;
-        context.push(str);
-        TTError { kind, context }
+    pub fn context(mut self, str: String) -> TTError {
+        self.context.push(str);
+        self
+    }
+
+    /// the offending word of a line, given as byte range
+    pub fn with_span(mut self, line: &str, span: Range<usize>) -> TTError {
+        self.details.line = Some(line.to_string());
+        self.details.span = Some(span);
+        self
+    }
+
+    pub fn with_hint(mut self, hint: &'static str) -> TTError {
+        self.details.hint = Some(hint);
+        self
+    }
+
+    /// sets the line number, unless it is already known
+    pub fn at_line(mut self, line_number: usize) -> TTError {
+        self.details.line_number.get_or_insert(line_number);
+        self
+    }
+
+    /// sets the file, unless it is already known
+    pub fn in_file(mut self, path: &Path) -> TTError {
+        self.details.path.get_or_insert_with(|| path.to_path_buf());
+        self
+    }
+
+    // the location like rustc shows it: the file and position, the line with a caret under the
+    // offending word, and the hint
+    fn fmt_location(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ErrorDetails {
+            path,
+            line_number,
+            line,
+            span,
+            hint,
+        } = self.details.as_ref();
+        let number = line_number.map(|n| n.to_string()).unwrap_or_default();
+        let indent = " ".repeat(number.len());
+        if path.is_some() || line_number.is_some() {
+            write!(
+                f,
+                "\n{}--> {}",
+                indent,
+                path.as_ref()
+                    .map_or("<log>".to_string(), |p| p.display().to_string())
+            )?;
+            if let Some(line_number) = line_number {
+                write!(f, ":{}", line_number)?;
+                if let (Some(line), Some(span)) = (line, span) {
+                    write!(f, ":{}", line[..span.start].chars().count() + 1)?;
+                }
+            }
+        }
+        if let Some(line) = line {
+            write!(f, "\n{} |\n{} | {}", indent, number, line)?;
+            if let Some(span) = span {
+                write!(
+                    f,
+                    "\n{} | {}{}",
+                    indent,
+                    " ".repeat(line[..span.start].chars().count()),
+                    "^".repeat(line[span.clone()].chars().count().max(1))
+                )?;
+            }
+        }
+        if let Some(hint) = hint {
+            write!(f, "\n{} = hint: {}", indent, hint)?;
+        }
+        Ok(())
     }
 }
 
@@ -43,11 +131,16 @@ impl fmt::Display for TTError {
             TTErrorKind::IoError(err) => err.fmt(f),
             TTErrorKind::UsageError(s) => write!(f, "Usage error: {}", s),
             TTErrorKind::ActivityConfigError(s) => write!(f, "activity file error: {}", s),
+            TTErrorKind::ParseError(msg, _line) if self.details.line.is_some() => {
+                write!(f, "parse error: {}", msg)
+            }
             TTErrorKind::ParseError(msg, line) => write!(f, "parse error: {} at: {}", msg, line),
+            TTErrorKind::LogError(s) => write!(f, "log error: {}", s),
             TTErrorKind::JsonError(err) => write!(f, "json parse error: {}", err),
             TTErrorKind::ConfigError(err) => write!(f, "configuration error: {}", err),
             TTErrorKind::Error(err) => write!(f, "other error:  {}", err),
         }?;
+        self.fmt_location(f)?;
         if !self.context.is_empty() {
             write!(f, "\n")?;
         }
@@ -60,6 +153,74 @@ impl fmt::Display for TTError {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    // the line has been used, but probably not as intended
+    Warning,
+    // the line has been skipped
+    Error,
+}
+
+/// a problem in a log that did not stop reading it
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// line number in the log file (starting with 1), if known
+    pub line_number: Option<usize>,
+    pub error: TTError,
+}
+
+impl Diagnostic {
+    pub fn warning(error: TTError) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            line_number: None,
+            error,
+        }
+    }
+
+    pub fn error(error: TTError) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            line_number: None,
+            error,
+        }
+    }
+
+    /// the problem has been found in this line
+    pub fn at_line(self, line_number: usize) -> Diagnostic {
+        Diagnostic {
+            line_number: Some(line_number),
+            error: self.error.at_line(line_number),
+            ..self
+        }
+    }
+
+    /// the problem has been found in this file
+    pub fn in_file(self, path: &Path) -> Diagnostic {
+        Diagnostic {
+            error: self.error.in_file(path),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the line number is part of the location of the error
+        write!(f, "{}: {}", self.severity, self.error)
+    }
+}
+
 impl error::Error for TTError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         None
@@ -0,0 +1,727 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::collector::{Interval, Summary};
+use crate::error::{TTError, TTErrorKind};
+use crate::log_parser::{self, Block, BlockData, Metadata};
+use crate::utils;
+
+pub mod ics;
+pub mod timeclock;
+pub mod timewarrior;
+pub mod watson;
+
+/// a stretch of time on an activity or a break, as `tt export` writes it and `tt import` reads it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// the workday
+    pub date: NaiveDate,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub duration: Duration,
+    pub activity: String,
+    pub shortname: Option<String>,
+    /// the tags of the entry without the shortname, and its metadata as key=value
+    pub tags: Vec<String>,
+    pub distributable: bool,
+    pub is_break: bool,
+    pub note: Option<String>,
+}
+
+impl Record {
+    pub fn from_interval(date: &NaiveDate, interval: &Interval) -> Record {
+        let data = &interval.data;
+        Record {
+            date: *date,
+            start: interval.start,
+            end: interval.end,
+            duration: interval.duration,
+            activity: data.activity.clone(),
+            shortname: data.shortname().map(str::to_string),
+            tags: data
+                .tags
+                .iter()
+                .filter(|tag| !tag.starts_with('='))
+                .cloned()
+                .chain(
+                    data.metadata
+                        .iter()
+                        .map(|(key, value)| format!("{}={}", key, value)),
+                )
+                .collect(),
+            distributable: data.distribute,
+            is_break: log_parser::is_break(&data.activity),
+            note: data.note.clone(),
+        }
+    }
+
+    /// the entry of the log that starts the record
+    pub fn block_data(&self) -> BlockData {
+        let mut tags = Vec::new();
+        let mut metadata = Metadata::new();
+        for tag in self.tags.iter() {
+            match log_parser::as_metadata(tag) {
+                Some((key, value)) => {
+                    metadata.insert(key.to_string(), value.to_string());
+                }
+                None => tags.push(tag.clone()),
+            }
+        }
+        if let Some(shortname) = &self.shortname {
+            tags.push(format!("={}", shortname));
+        }
+        BlockData {
+            start: self.start,
+            activity: self.activity.clone(),
+            tags,
+            metadata,
+            note: self.note.clone(),
+            distribute: log_parser::is_distributable(&self.activity),
+            utc_offset: None,
+            start_seconds: false,
+        }
+    }
+
+    /// the records of an activity between two local times, split at the day changes. Tags in
+    /// the form `=shortname` are the shortname, an activity with spaces gets dashes instead.
+    pub fn between(
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+        activity: &str,
+        tags: Vec<String>,
+        note: Option<String>,
+    ) -> Vec<Record> {
+        let activity = activity.split_whitespace().join("-");
+        let shortname = tags
+            .iter()
+            .find_map(|tag| tag.strip_prefix('='))
+            .map(str::to_string);
+        let tags: Vec<String> = tags
+            .into_iter()
+            .filter(|tag| !tag.starts_with('='))
+            .collect();
+        let mut records = Vec::new();
+        let mut from = start;
+        while from < end {
+            let date = utils::workday(&from);
+            let to = end.min((date + Duration::days(1)).and_time(utils::day_change()));
+            records.push(Record {
+                date,
+                start: from.time(),
+                end: to.time(),
+                duration: to - from,
+                activity: activity.clone(),
+                shortname: shortname.clone(),
+                tags: tags.clone(),
+                distributable: log_parser::is_distributable(&activity),
+                is_break: log_parser::is_break(&activity),
+                note: note.clone(),
+            });
+            from = to;
+        }
+        records
+    }
+
+    /// the local date and time of the start
+    pub fn local_start(&self) -> NaiveDateTime {
+        self.date.and_time(utils::day_change()) + utils::workday_offset(&self.start)
+    }
+
+    /// the local date and time of the end
+    pub fn local_end(&self) -> NaiveDateTime {
+        self.local_start() + self.duration
+    }
+
+    /// an id like a uuid without dashes, the same record always gets the same id
+    pub fn id(&self) -> String {
+        record_id(&to_utc(&self.local_start()), &self.activity, self.duration)
+    }
+
+    /// the tags for other tools: the shortname as `=shortname`, without the tags of `tt resume`
+    pub fn tags_with_shortname(&self) -> Vec<String> {
+        self.tags
+            .iter()
+            .filter(|tag| !tag.starts_with("resume:"))
+            .cloned()
+            .chain(self.shortname.iter().map(|s| format!("={}", s)))
+            .collect()
+    }
+}
+
+// the start of an FNV-1a hash
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+// goes on with an FNV-1a hash over the text. Unlike the hashers of the standard library, it
+// stays the same across versions of Rust and tt.
+pub(crate) fn fnv1a(mut hash: u64, text: &str) -> u64 {
+    for byte in text.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+// the id of a record, from FNV-1a hashes: one over the start in UTC and the activity, and one
+// that goes on over the duration
+fn record_id(utc_start: &NaiveDateTime, activity: &str, duration: Duration) -> String {
+    let first = fnv1a(
+        FNV_OFFSET,
+        &format!("{} {}", utc_start.format("%Y%m%dT%H%M%SZ"), activity),
+    );
+    let second = fnv1a(first, &format!(" {}", duration.num_seconds()));
+    format!("{:016x}{:016x}", first, second)
+}
+
+/// the UTC time of a local time
+pub fn to_utc(local: &NaiveDateTime) -> NaiveDateTime {
+    *local - Duration::seconds(utils::local_offset(local).local_minus_utc() as i64)
+}
+
+/// the local time of a UTC time
+pub fn from_utc(utc: &NaiveDateTime) -> NaiveDateTime {
+    Local.from_utc_datetime(utc).naive_local()
+}
+
+/// the local time of seconds since the epoch
+pub fn from_timestamp(timestamp: i64) -> Option<NaiveDateTime> {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .map(|utc| from_utc(&utc.naive_utc()))
+}
+
+/// the records of a workday
+pub fn records(date: &NaiveDate, summary: &Summary) -> Vec<Record> {
+    summary
+        .intervals
+        .iter()
+        .map(|interval| Record::from_interval(date, interval))
+        .collect()
+}
+
+/// the log lines for the records of a day. Each record is an entry with its start; a record
+/// that is not followed by another one at its end is ended with a break. A record that ends at
+/// the day change stays open, it lasts until the day change anyway.
+/// ```
+/// use chrono::{Duration, NaiveDate, NaiveTime};
+/// use timetracker::export::{log_lines, Record};
+/// let record = |start: u32, end: u32, activity: &str| Record {
+///     date: NaiveDate::from_ymd_opt(2020, 6, 1).unwrap(),
+///     start: NaiveTime::from_hms_opt(start, 0, 0).unwrap(),
+///     end: NaiveTime::from_hms_opt(end, 0, 0).unwrap(),
+///     duration: Duration::hours((end - start) as i64),
+///     activity: activity.to_string(),
+///     shortname: None,
+///     tags: vec![],
+///     distributable: false,
+///     is_break: false,
+///     note: None,
+/// };
+/// assert_eq!(
+///     log_lines(&[record(8, 9, "email"), record(9, 10, "meeting"), record(13, 14, "email")]),
+///     vec!["08:00 email", "09:00 meeting", "10:00 break", "13:00 email", "14:00 break"]
+/// );
+/// ```
+pub fn log_lines(records: &[Record]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let records: Vec<&Record> = records
+        .iter()
+        .sorted_by_key(|record| utils::workday_offset(&record.start))
+        .collect();
+    for (index, record) in records.iter().enumerate() {
+        lines.push(Block::NormalBlock(record.block_data()).to_string(&record.start));
+        let continued = records
+            .get(index + 1)
+            .is_some_and(|next| next.start == record.end);
+        let at_day_change = record.end == utils::day_change() && record.duration > Duration::zero();
+        if !record.is_break && !continued && !at_day_change {
+            lines.push(format!("{} break", utils::format_time(&record.end)));
+        }
+    }
+    lines
+}
+
+/// the formats of `tt export` and `tt import`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+    /// the data files of timewarrior
+    Timewarrior,
+    /// the frames file of Watson
+    Watson,
+    /// the timeclock format of ledger and hledger
+    Timeclock,
+    /// iCalendar, only for export
+    Ics,
+}
+
+impl Format {
+    /// the format of a file with this extension
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension {
+            "csv" => Some(Format::Csv),
+            "json" => Some(Format::Json),
+            "data" => Some(Format::Timewarrior),
+            "timeclock" => Some(Format::Timeclock),
+            "ics" => Some(Format::Ics),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "timewarrior" => Ok(Format::Timewarrior),
+            "watson" => Ok(Format::Watson),
+            "timeclock" => Ok(Format::Timeclock),
+            "ics" => Ok(Format::Ics),
+            _ => Err(format!(
+                "format must be csv, json, timewarrior, watson, timeclock or ics, but is {}",
+                s
+            )),
+        }
+    }
+}
+
+const COLUMNS: [&str; 10] = [
+    "date",
+    "start",
+    "end",
+    "duration",
+    "activity",
+    "shortname",
+    "tags",
+    "distributable",
+    "break",
+    "note",
+];
+
+// the columns that a CSV file to import must have, the others are optional
+const REQUIRED_COLUMNS: [&str; 4] = ["date", "start", "end", "activity"];
+
+// a record as it is written, with the duration in seconds and the tags separated by spaces
+#[derive(Serialize, Deserialize, Debug)]
+struct Row {
+    date: String,
+    start: String,
+    end: String,
+    #[serde(default)]
+    duration: i64,
+    activity: String,
+    #[serde(default)]
+    shortname: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    distributable: bool,
+    #[serde(default, rename = "break")]
+    is_break: bool,
+    #[serde(default)]
+    note: Option<String>,
+}
+
+impl Row {
+    fn from_record(record: &Record) -> Row {
+        Row {
+            date: record.date.to_string(),
+            start: record.start.format("%H:%M:%S").to_string(),
+            end: record.end.format("%H:%M:%S").to_string(),
+            duration: record.duration.num_seconds(),
+            activity: record.activity.clone(),
+            shortname: record.shortname.clone(),
+            tags: record.tags.clone(),
+            distributable: record.distributable,
+            is_break: record.is_break,
+            note: record.note.clone(),
+        }
+    }
+
+    // the duration is taken from the start and end, breaks and distribution from the activity
+    fn to_record(&self) -> Result<Record, TTErrorKind> {
+        let date = NaiveDate::parse_from_str(&self.date, "%F")
+            .map_err(|_| TTErrorKind::ParseError("invalid date", self.date.clone()))?;
+        let time = |time: &str| {
+            utils::parse_time(time)
+                .map_err(|_| TTErrorKind::ParseError("invalid time", time.to_string()))
+        };
+        let (start, end) = (time(&self.start)?, time(&self.end)?);
+        // an end at the day change is the end of the workday
+        let duration = match utils::workday_offset(&end) {
+            offset if offset.is_zero() && start != end => Duration::days(1),
+            offset => offset,
+        } - utils::workday_offset(&start);
+        if duration < Duration::zero() {
+            return Err(TTErrorKind::ParseError(
+                "the end is before the start",
+                self.end.clone(),
+            ));
+        }
+        let activity = self.activity.trim();
+        if activity.is_empty() || activity.contains(char::is_whitespace) {
+            return Err(TTErrorKind::ParseError(
+                "the activity must be one word",
+                self.activity.clone(),
+            ));
+        }
+        Ok(Record {
+            date,
+            start,
+            end,
+            duration,
+            activity: activity.to_string(),
+            shortname: self.shortname.clone().filter(|s| !s.is_empty()),
+            tags: self.tags.clone(),
+            distributable: log_parser::is_distributable(activity),
+            is_break: log_parser::is_break(activity),
+            note: self.note.clone().filter(|s| !s.is_empty()),
+        })
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.date.clone(),
+            self.start.clone(),
+            self.end.clone(),
+            self.duration.to_string(),
+            self.activity.clone(),
+            self.shortname.clone().unwrap_or_default(),
+            self.tags.join(" "),
+            self.distributable.to_string(),
+            self.is_break.to_string(),
+            self.note.clone().unwrap_or_default(),
+        ]
+    }
+
+    // the row from the fields of a CSV line, by the positions of the columns in the header
+    fn from_fields(columns: &HashMap<&str, usize>, fields: &[String]) -> Row {
+        let field = |name: &str| {
+            columns
+                .get(name)
+                .and_then(|index| fields.get(*index))
+                .cloned()
+                .unwrap_or_default()
+        };
+        let optional = |name: &str| Some(field(name)).filter(|s| !s.is_empty());
+        Row {
+            date: field("date"),
+            start: field("start"),
+            end: field("end"),
+            duration: field("duration").parse().unwrap_or_default(),
+            activity: field("activity"),
+            shortname: optional("shortname"),
+            tags: field("tags")
+                .split_whitespace()
+                .map(str::to_string)
+                .collect(),
+            distributable: field("distributable") == "true",
+            is_break: field("break") == "true",
+            note: optional("note"),
+        }
+    }
+}
+
+// the rows of CSV text with their line numbers. The header line names the columns, they can
+// be in any order.
+fn csv_rows(text: &str) -> Result<Vec<(Option<usize>, Row)>, TTError> {
+    let mut records = parse_csv(text)?
+        .into_iter()
+        .filter(|(_, fields)| fields.iter().any(|f| !f.is_empty()));
+    let (header_line, header) = match records.next() {
+        Some(header) => header,
+        None => return Ok(vec![]),
+    };
+    let column_error = |msg: &'static str, name: &str| {
+        TTError::new(TTErrorKind::ParseError(msg, name.to_string()))
+            .at_line(header_line)
+            .with_hint(
+                "the first line names the columns: date, start, end and activity, and \
+                optionally duration, shortname, tags, distributable, break and note",
+            )
+    };
+    let mut columns: HashMap<&str, usize> = HashMap::new();
+    for (index, name) in header.iter().enumerate() {
+        let name = COLUMNS
+            .iter()
+            .find(|column| **column == name.trim())
+            .ok_or_else(|| column_error("unknown column", name))?;
+        if columns.insert(name, index).is_some() {
+            return Err(column_error("duplicate column", name));
+        }
+    }
+    if let Some(missing) = REQUIRED_COLUMNS
+        .iter()
+        .find(|column| !columns.contains_key(*column))
+    {
+        return Err(column_error("missing column", missing));
+    }
+    Ok(records
+        .map(|(line, fields)| (Some(line), Row::from_fields(&columns, &fields)))
+        .collect())
+}
+
+// a CSV field, quoted if necessary
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+// the records of CSV text with the line number of each, quoted fields can span lines
+fn parse_csv(text: &str) -> Result<Vec<(usize, Vec<String>)>, TTError> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let (mut line_number, mut record_line) = (1, 1);
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => fields.push(std::mem::take(&mut field)),
+            (false, '\r') => (),
+            (false, '\n') => {
+                fields.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut fields)));
+                line_number += 1;
+                record_line = line_number;
+            }
+            (_, c) => {
+                if c == '\n' {
+                    line_number += 1;
+                }
+                field.push(c)
+            }
+        }
+    }
+    if quoted {
+        return Err(
+            TTError::new(TTErrorKind::ParseError("quoted field without end", field))
+                .at_line(record_line),
+        );
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push((record_line, fields));
+    }
+    Ok(records)
+}
+
+/// the records as CSV with a header line, or as a JSON array
+/// ```
+/// use timetracker::export::{read_records, write_records, Format};
+/// let csv = "date,start,end,duration,activity,shortname,tags,distributable,break,note\n\
+///     2020-06-01,08:00:00,09:30:00,5400,email,mail,customer=acme,false,false,\"inbox, zero\"\n";
+/// let records = read_records(Format::Csv, csv).unwrap();
+/// assert_eq!(records[0].duration.num_minutes(), 90);
+/// assert_eq!(records[0].note, Some("inbox, zero".to_string()));
+/// assert_eq!(write_records(Format::Csv, &records).unwrap(), csv);
+/// ```
+pub fn write_records(format: Format, records: &[Record]) -> Result<String, TTError> {
+    let rows = records.iter().map(Row::from_record);
+    match format {
+        Format::Csv => {
+            let lines = std::iter::once(COLUMNS.iter().map(|c| c.to_string()).collect())
+                .chain(rows.map(|row| row.fields()))
+                .map(|fields: Vec<String>| fields.iter().map(|f| csv_field(f)).join(",") + "\n");
+            Ok(lines.collect())
+        }
+        Format::Json => Ok(serde_json::to_string_pretty(&rows.collect::<Vec<_>>())? + "\n"),
+        Format::Timewarrior => Ok(timewarrior::write(records)),
+        Format::Watson => Ok(watson::write(records)? + "\n"),
+        Format::Timeclock => Ok(timeclock::write(records)),
+        Format::Ics => Ok(ics::write(records)),
+    }
+}
+
+/// reads the records written by write_records. The duration and the distributable and break
+/// columns are only informational, they follow from the times and the activity. CSV text
+/// starts with a header line, its columns can be in any order, see csv_rows.
+pub fn read_records(format: Format, text: &str) -> Result<Vec<Record>, TTError> {
+    let rows: Vec<(Option<usize>, Row)> = match format {
+        Format::Timewarrior => return timewarrior::read(text),
+        Format::Watson => return watson::read(text),
+        Format::Timeclock => return timeclock::read(text),
+        Format::Ics => {
+            return Err(TTError::new(TTErrorKind::UsageError(
+                "calendars are imported with `tt import-ics`",
+            )))
+        }
+        Format::Csv => csv_rows(text)?,
+        Format::Json => serde_json::from_str::<Vec<Row>>(text)?
+            .into_iter()
+            .map(|row| (None, row))
+            .collect(),
+    };
+    rows.iter()
+        .enumerate()
+        .map(|(index, (line, row))| {
+            row.to_record().map_err(|kind| match line {
+                Some(line) => TTError::new(kind).at_line(*line),
+                None => TTError::new(kind).context(format!("reading record {}", index + 1)),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::collect_workday;
+
+    // records with a shortname, tags, metadata, a note, a distributable activity and a break
+    pub(crate) fn sample_records() -> Vec<Record> {
+        let at = |hour, minute| {
+            NaiveDate::from_ymd_opt(2020, 6, 1)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+        };
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect();
+        vec![
+            Record::between(at(8, 0), at(9, 0), "email", tags(&["=mail"]), None),
+            Record::between(
+                at(9, 0),
+                at(10, 0),
+                "JIRA-1",
+                tags(&["review", "customer=acme", "=j1"]),
+                Some("fixed \"it\"".to_string()),
+            ),
+            Record::between(at(10, 0), at(10, 30), "_meeting", vec![], None),
+            Record::between(at(10, 30), at(13, 0), "break", vec![], None),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn split_at_the_day_change() {
+        let start = NaiveDate::from_ymd_opt(2020, 6, 1)
+            .unwrap()
+            .and_hms_opt(22, 0, 0)
+            .unwrap();
+        let records = Record::between(start, start + Duration::hours(3), "oncall", vec![], None);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].end, NaiveTime::from_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(records[0].duration, Duration::hours(2));
+        assert_eq!(
+            records[1].date,
+            NaiveDate::from_ymd_opt(2020, 6, 2).unwrap()
+        );
+        assert_eq!(records[1].local_end(), start + Duration::hours(3));
+        assert_eq!(log_lines(&records[..1]), vec!["22:00 oncall"]);
+        assert_eq!(
+            log_lines(&records[1..]),
+            vec!["00:00 oncall", "01:00 break"]
+        );
+    }
+
+    #[test]
+    fn stable_id() {
+        let start = NaiveDate::from_ymd_opt(2020, 6, 1)
+            .unwrap()
+            .and_hms_opt(6, 0, 0)
+            .unwrap();
+        let id = record_id(&start, "email", Duration::minutes(90));
+        assert_eq!(id, "d0da5d2a8d9d58c43588f6d66e9c8785");
+        assert_ne!(record_id(&start, "email", Duration::minutes(60)), id);
+        assert_ne!(record_id(&start, "meeting", Duration::minutes(90)), id);
+    }
+
+    #[test]
+    fn round_trip() {
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let log = [
+            "08:00 email =mail",
+            "09:00 JIRA-1 review customer=acme -- \"quoted\", with comma",
+            "10:00 _meeting",
+            "10:30 break",
+            "13:00 JIRA-1",
+            "14:00 really JIRA-2",
+            "15:00 break",
+        ];
+        let (collected, diagnostics) = collect_workday(
+            &date,
+            vec![].into_iter(),
+            log.iter().map(|line| Ok(line.to_string())),
+            None,
+        );
+        assert!(diagnostics.is_empty());
+        let records = records(&date, &collected.unwrap().summary);
+        assert_eq!(records.len(), 5);
+        assert!(records[2].distributable);
+        assert!(records[3].is_break);
+        for format in [Format::Csv, Format::Json].iter() {
+            let text = write_records(*format, &records).unwrap();
+            assert_eq!(read_records(*format, &text).unwrap(), records);
+        }
+        assert_eq!(
+            log_lines(&records),
+            vec![
+                "08:00 email =mail",
+                "09:00 JIRA-1 review customer=acme -- \"quoted\", with comma",
+                "10:00 _meeting",
+                "10:30 break",
+                "13:00 JIRA-2",
+                "15:00 break",
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_errors() {
+        let header = "date,start,end,duration,activity\n";
+        let csv = |rows: &str| format!("{}{}", header, rows);
+        let err = read_records(Format::Csv, &csv("2020-06-01,8:00,9:00,,two words\n"));
+        assert_eq!(err.unwrap_err().details.line_number, Some(2));
+        let err = read_records(Format::Csv, &csv("2020-06-01,8:00,9:00,,email,\"open\n"));
+        assert_eq!(err.unwrap_err().details.line_number, Some(2));
+        let err = read_records(Format::Csv, &csv("\n2020-06-31,8:00,9:00,,email\n"));
+        assert_eq!(err.unwrap_err().details.line_number, Some(3));
+    }
+
+    #[test]
+    fn csv_columns_by_name() {
+        let records = read_records(
+            Format::Csv,
+            "activity,note,date,end,start\nemail,inbox,2020-06-01,09:30,08:00\n",
+        )
+        .unwrap();
+        assert_eq!(records[0].activity, "email");
+        assert_eq!(records[0].start, NaiveTime::from_hms_opt(8, 0, 0).unwrap());
+        assert_eq!(records[0].duration.num_minutes(), 90);
+        assert_eq!(records[0].note, Some("inbox".to_string()));
+
+        let kind = |csv: &str| read_records(Format::Csv, csv).unwrap_err().kind;
+        let parse_error = |csv: &str, column: &str| match kind(csv) {
+            TTErrorKind::ParseError(msg, name) => (msg, name == column),
+            other => panic!("expected a parse error, got {:?}", other),
+        };
+        assert_eq!(
+            parse_error("date,start,end,activity,project\n", "project"),
+            ("unknown column", true)
+        );
+        assert_eq!(
+            parse_error("date,start,activity\n", "end"),
+            ("missing column", true)
+        );
+        // without a header the first row names unknown columns
+        assert_eq!(
+            parse_error("2020-06-01,08:00,09:30,5400,email\n", "2020-06-01"),
+            ("unknown column", true)
+        );
+    }
+}
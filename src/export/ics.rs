@@ -0,0 +1,834 @@
+//! iCalendar: `tt export --format ics` writes an event for each interval that is not a break,
+//! with the activity as summary, the note as description and the tags as categories.
+//! `tt import-ics` reads the meetings of a calendar with read_events.
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Weekday};
+use chrono_tz::Tz;
+use itertools::Itertools;
+
+use super::{fnv1a, from_utc, to_utc, Record, FNV_OFFSET};
+use crate::error::{TTError, TTErrorKind};
+
+const UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+// text with the characters escaped that have a meaning in iCalendar
+fn escaped(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescaped(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') | Some('N') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => (),
+            },
+            (c, false) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+// a content line, folded after 75 bytes
+fn content_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded + "\r\n"
+}
+
+/// the calendar with the events of the records that are not breaks
+pub fn write(records: &[Record]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//tt-timetracker//tt export//EN".to_string(),
+    ];
+    for record in records.iter().filter(|record| !record.is_break) {
+        let end = to_utc(&record.local_end()).format(UTC_FORMAT).to_string();
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}@tt-timetracker", record.id()));
+        lines.push(format!("DTSTAMP:{}", end));
+        lines.push(format!(
+            "DTSTART:{}",
+            to_utc(&record.local_start()).format(UTC_FORMAT)
+        ));
+        lines.push(format!("DTEND:{}", end));
+        lines.push(format!("SUMMARY:{}", escaped(&record.activity)));
+        if let Some(note) = &record.note {
+            lines.push(format!("DESCRIPTION:{}", escaped(note)));
+        }
+        let tags = record.tags_with_shortname();
+        if !tags.is_empty() {
+            lines.push(format!(
+                "CATEGORIES:{}",
+                tags.iter().map(|tag| escaped(tag)).join(",")
+            ));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| content_line(line)).collect()
+}
+
+/// a meeting of a calendar, in local time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// the UID of the event, it is the same for all times of a repeated event
+    pub uid: String,
+    pub summary: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl Event {
+    /// a short id of the event for the log, from its UID, or from its summary if it has none
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use timetracker::export::ics::Event;
+    /// let start = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap().and_hms_opt(10, 0, 0).unwrap();
+    /// let event = |uid: &str| Event {
+    ///     uid: uid.to_string(),
+    ///     summary: "Jour fixe".to_string(),
+    ///     start,
+    ///     end: start,
+    /// };
+    /// assert_eq!(event("jf@example.com").id(), "37b6aedc");
+    /// assert_eq!(event("").id(), event("Jour fixe").id());
+    /// ```
+    pub fn id(&self) -> String {
+        let key = match self.uid.is_empty() {
+            true => &self.summary,
+            false => &self.uid,
+        };
+        format!("{:08x}", fnv1a(FNV_OFFSET, key) & 0xffff_ffff)
+    }
+}
+
+// a property of an event: the parameters and the value
+#[derive(Debug, Default, Clone)]
+struct Property {
+    parameters: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// the content lines with the line number where they start, folded lines are joined
+fn content_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match (
+            line.strip_prefix(|c| c == ' ' || c == '\t'),
+            lines.last_mut(),
+        ) {
+            (Some(continued), Some((_, last))) => last.push_str(continued),
+            _ if line.is_empty() => (),
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+// the name and the property of a content line like DTSTART;TZID=Europe/Berlin:20200601T100000
+fn property(line: &str) -> Option<(String, Property)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(index, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(index),
+        _ => None,
+    })?;
+    let mut parts = line[..colon].split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let parameters = parts
+        .filter_map(|parameter| parameter.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.trim_matches('"').to_string()))
+        .collect();
+    let value = line[colon + 1..].to_string();
+    Some((name, Property { parameters, value }))
+}
+
+// the time zone of a date-time value
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Zone {
+    Local,
+    Utc,
+    Named(Tz),
+}
+
+impl Zone {
+    // the local time of a time in the zone
+    fn to_local(self, time: &NaiveDateTime) -> NaiveDateTime {
+        match self {
+            Zone::Local => *time,
+            Zone::Utc => from_utc(time),
+            // a time that is skipped by daylight saving time is taken like the one an hour later
+            Zone::Named(tz) => match tz.from_local_datetime(time).earliest() {
+                Some(zoned) => from_utc(&zoned.naive_utc()),
+                None => self.to_local(&(*time + Duration::hours(1))),
+            },
+        }
+    }
+}
+
+// the time zone of the TZID parameter, like Europe/Berlin. Err with the TZID if it is not in
+// the time zone database, like the Windows names of some calendar apps.
+fn time_zone(property: &Property) -> Result<Zone, String> {
+    match property.parameter("TZID") {
+        None => Ok(Zone::Local),
+        Some(tzid) => tzid
+            .trim_start_matches('/')
+            .parse::<Tz>()
+            .map(Zone::Named)
+            .map_err(|_| tzid.to_string()),
+    }
+}
+
+// a date-time value in its time zone, None for a date. Times with an unknown time zone are
+// taken as local times.
+fn zoned_datetime(property: &Property) -> Result<Option<(NaiveDateTime, Zone)>, ()> {
+    let value = property.value.trim();
+    if property.parameter("VALUE") == Some("DATE") || value.len() == 8 {
+        return Ok(None);
+    }
+    let (value, zone) = match value.strip_suffix('Z') {
+        Some(utc) => (utc, Zone::Utc),
+        None => (value, time_zone(property).unwrap_or(Zone::Local)),
+    };
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .map(|time| Some((time, zone)))
+        .map_err(|_| ())
+}
+
+// the local time of a date-time value, None for a date
+fn datetime(property: &Property) -> Result<Option<NaiveDateTime>, ()> {
+    Ok(zoned_datetime(property)?.map(|(time, zone)| zone.to_local(&time)))
+}
+
+// a duration like PT1H30M or P1D
+fn duration(value: &str) -> Option<Duration> {
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.trim_start_matches(['+', 'P']).chars() {
+        let amount = || number.parse::<i64>().ok();
+        match c {
+            '0'..='9' => {
+                number.push(c);
+                continue;
+            }
+            'T' => in_time = true,
+            'W' => total += Duration::weeks(amount()?),
+            'D' => total += Duration::days(amount()?),
+            'H' if in_time => total += Duration::hours(amount()?),
+            'M' if in_time => total += Duration::minutes(amount()?),
+            'S' if in_time => total += Duration::seconds(amount()?),
+            _ => return None,
+        }
+        number.clear();
+    }
+    Some(total)
+}
+
+// a day of a rule like MO, or 2MO and -1FR for the second Monday and the last Friday
+fn weekday(day: &str) -> Option<(Option<i64>, Weekday)> {
+    let split = day.len().checked_sub(2)?;
+    let weekday = match day.get(split..)? {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    match &day[..split] {
+        "" => Some((None, weekday)),
+        ordinal => Some((Some(ordinal.trim_start_matches('+').parse().ok()?), weekday)),
+    }
+}
+
+// the days of the month that a monthly or yearly rule selects, in order
+fn days_of_month(
+    year: i32,
+    month: u32,
+    month_days: &[i64],
+    days: &[(Option<i64>, Weekday)],
+    default: u32,
+) -> Vec<NaiveDate> {
+    let first = match NaiveDate::from_ymd_opt(year, month, 1) {
+        Some(first) => first,
+        None => return vec![],
+    };
+    let all: Vec<NaiveDate> = first
+        .iter_days()
+        .take_while(|day| day.month() == month)
+        .collect();
+    // the nth day of the list, counted from the end if negative
+    let nth = |list: &[NaiveDate], n: i64| -> Option<NaiveDate> {
+        match n {
+            n if n > 0 => list.get(n as usize - 1).copied(),
+            n if n < 0 => list.len().checked_sub((-n) as usize).map(|i| list[i]),
+            _ => None,
+        }
+    };
+    let mut selected: Vec<NaiveDate> = match (month_days.is_empty(), days.is_empty()) {
+        (false, _) => month_days.iter().filter_map(|n| nth(&all, *n)).collect(),
+        (true, false) => days
+            .iter()
+            .flat_map(|(ordinal, weekday)| {
+                let matching: Vec<NaiveDate> = all
+                    .iter()
+                    .filter(|day| day.weekday() == *weekday)
+                    .copied()
+                    .collect();
+                match ordinal {
+                    Some(n) => nth(&matching, *n).into_iter().collect(),
+                    None => matching,
+                }
+            })
+            .collect(),
+        (true, true) => NaiveDate::from_ymd_opt(year, month, default)
+            .into_iter()
+            .collect(),
+    };
+    selected.sort();
+    selected.dedup();
+    selected
+}
+
+// every interval-th period of a rule (a day, week, month or year) from the one of the start,
+// with what it yields. The periods end with the first one that starts after the limit, or that
+// is out of the range of dates, even if a rule selects no day in any period.
+fn periods<T>(
+    interval: i64,
+    limit: NaiveDateTime,
+    nth: impl Fn(i64) -> Option<(NaiveDateTime, T)>,
+) -> impl Iterator<Item = T> {
+    (0i64..)
+        .map_while(move |i| nth(i.checked_mul(interval)?))
+        .take_while(move |(start, _)| *start <= limit)
+        .map(|(_, yielded)| yielded)
+}
+
+// the local starts of a repeated event until the given local time. The start is in the time
+// zone of the event, the rule is expanded there. None if the rule is not supported.
+fn occurrences(
+    start: NaiveDateTime,
+    zone: Zone,
+    rule: &str,
+    until: NaiveDateTime,
+) -> Option<Vec<NaiveDateTime>> {
+    let rule: Vec<(&str, &str)> = rule
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .collect();
+    let get = |name: &str| {
+        rule.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    };
+    let supported = [
+        "FREQ",
+        "INTERVAL",
+        "COUNT",
+        "UNTIL",
+        "BYDAY",
+        "BYMONTH",
+        "BYMONTHDAY",
+        "WKST",
+    ];
+    if rule
+        .iter()
+        .any(|(key, _)| !supported.iter().any(|s| key.eq_ignore_ascii_case(s)))
+    {
+        return None;
+    }
+    let interval = get("INTERVAL")
+        .and_then(|i| i.parse().ok())
+        .unwrap_or(1i64)
+        .max(1);
+    let count = get("COUNT").and_then(|c| c.parse::<usize>().ok());
+    let until = get("UNTIL")
+        .and_then(|value| {
+            let property = Property {
+                value: value.to_string(),
+                ..Property::default()
+            };
+            match datetime(&property) {
+                Ok(Some(datetime)) => Some(datetime),
+                // a date is the whole day
+                _ => NaiveDate::parse_from_str(value, "%Y%m%d")
+                    .ok()
+                    .map(|date| (date + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap()),
+            }
+        })
+        .map_or(until, |rule_until| rule_until.min(until));
+    let list = |name: &str| -> Option<Vec<i64>> {
+        get(name).map_or(Some(vec![]), |values| {
+            values.split(',').map(|value| value.parse().ok()).collect()
+        })
+    };
+    let days: Vec<(Option<i64>, Weekday)> = match get("BYDAY") {
+        Some(days) => days.split(',').map(weekday).collect::<Option<_>>()?,
+        None => vec![],
+    };
+    let month_days = list("BYMONTHDAY")?;
+    let months = list("BYMONTH")?;
+    let time = start.time();
+    // the zone of the event is less than a day away from the local time
+    let limit = until
+        .checked_add_signed(Duration::days(1))
+        .unwrap_or(NaiveDateTime::MAX);
+    let candidates: Box<dyn Iterator<Item = NaiveDateTime>> = match get("FREQ") {
+        Some("DAILY") if months.is_empty() && month_days.is_empty() => Box::new(
+            periods(interval, limit, move |n| {
+                let day = start.checked_add_signed(Duration::try_days(n)?)?;
+                Some((day, day))
+            })
+            .filter(move |day| days.is_empty() || days.iter().any(|(_, d)| *d == day.weekday())),
+        ),
+        Some("WEEKLY") if days.is_empty() && months.is_empty() && month_days.is_empty() => {
+            Box::new(periods(interval, limit, move |n| {
+                let day = start.checked_add_signed(Duration::try_weeks(n)?)?;
+                Some((day, day))
+            }))
+        }
+        Some("WEEKLY")
+            if days.iter().all(|(ordinal, _)| ordinal.is_none())
+                && months.is_empty()
+                && month_days.is_empty() =>
+        {
+            let monday = start - Duration::days(start.weekday().num_days_from_monday() as i64);
+            let days: Vec<Weekday> = days
+                .into_iter()
+                .map(|(_, day)| day)
+                .sorted_by_key(|day| day.num_days_from_monday())
+                .collect();
+            Box::new(
+                periods(interval, limit, move |n| {
+                    let week = monday.checked_add_signed(Duration::try_weeks(n)?)?;
+                    let days = days
+                        .iter()
+                        .filter_map(|day| {
+                            week.checked_add_signed(Duration::days(
+                                day.num_days_from_monday() as i64
+                            ))
+                        })
+                        .collect::<Vec<_>>();
+                    Some((week, days))
+                })
+                .flatten(),
+            )
+        }
+        Some("MONTHLY") if months.is_empty() => Box::new(
+            periods(interval, limit, move |n| {
+                let month = (start.month0() as i64).checked_add(n)?;
+                let year = i32::try_from(start.year() as i64 + month / 12).ok()?;
+                let first = NaiveDate::from_ymd_opt(year, (month % 12) as u32 + 1, 1)?;
+                let days = days_of_month(
+                    year,
+                    (month % 12) as u32 + 1,
+                    &month_days,
+                    &days,
+                    start.day(),
+                );
+                Some((first.and_hms_opt(0, 0, 0)?, days))
+            })
+            .flatten()
+            .map(move |day| day.and_time(time)),
+        ),
+        // weekdays without a month would count in the whole year
+        Some("YEARLY") if days.is_empty() || !months.is_empty() => {
+            let months: Vec<u32> = match months.is_empty() {
+                true => vec![start.month()],
+                false => months.iter().map(|month| *month as u32).sorted().collect(),
+            };
+            Box::new(
+                periods(interval, limit, move |n| {
+                    let year = start.year().checked_add(i32::try_from(n).ok()?)?;
+                    let first = NaiveDate::from_ymd_opt(year, 1, 1)?;
+                    let days = months
+                        .iter()
+                        .flat_map(|month| {
+                            days_of_month(year, *month, &month_days, &days, start.day())
+                        })
+                        .collect::<Vec<_>>();
+                    Some((first.and_hms_opt(0, 0, 0)?, days))
+                })
+                .flatten()
+                .map(move |day| day.and_time(time)),
+            )
+        }
+        _ => return None,
+    };
+    Some(
+        candidates
+            .skip_while(|occurrence| *occurrence < start)
+            .take(count.unwrap_or(usize::MAX))
+            .map(|occurrence| zone.to_local(&occurrence))
+            .take_while(|occurrence| *occurrence <= until)
+            .collect(),
+    )
+}
+
+/// the events of the calendar that start between the two local times, repeated events for each
+/// time. Events that last all day and cancelled events are skipped. Also returns warnings about
+/// events that cannot be read exactly: times in an unknown time zone are taken as local times,
+/// repeated events with a rule that is not supported only at their start.
+/// ```
+/// use chrono::NaiveDate;
+/// use timetracker::export::ics::read_events;
+/// let calendar = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Daily\r\n\
+///     DTSTART:20200601T091500\r\nDURATION:PT15M\r\nRRULE:FREQ=DAILY;COUNT=5\r\n\
+///     EXDATE:20200602T091500\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+/// let at = |day, hour, minute| {
+///     NaiveDate::from_ymd_opt(2020, 6, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+/// };
+/// let (events, warnings) = read_events(calendar, &at(2, 0, 0), &at(4, 0, 0)).unwrap();
+/// assert!(warnings.is_empty());
+/// assert_eq!(events.len(), 1);
+/// assert_eq!(events[0].summary, "Daily");
+/// assert_eq!(events[0].start, at(3, 9, 15));
+/// assert_eq!(events[0].end, at(3, 9, 30));
+/// ```
+pub fn read_events(
+    text: &str,
+    from: &NaiveDateTime,
+    to: &NaiveDateTime,
+) -> Result<(Vec<Event>, Vec<String>), TTError> {
+    // the properties of the events with the line numbers of their start
+    let mut events: Vec<(usize, Vec<(String, Property)>)> = Vec::new();
+    let mut current: Option<(usize, Vec<(String, Property)>)> = None;
+    for (line_number, line) in content_lines(text) {
+        let (name, property) = property(&line).ok_or_else(|| {
+            TTError::new(TTErrorKind::ParseError("not a content line", line.clone()))
+                .at_line(line_number)
+        })?;
+        match (name.as_str(), property.value.to_ascii_uppercase().as_str()) {
+            ("BEGIN", "VEVENT") => current = Some((line_number, Vec::new())),
+            ("END", "VEVENT") => events.extend(current.take()),
+            // alarms are part of the event, but their properties are not
+            ("BEGIN", _) | ("END", _) => (),
+            _ => {
+                if let Some((_, properties)) = &mut current {
+                    properties.push((name, property));
+                }
+            }
+        }
+    }
+
+    // events that replace an occurrence of a repeated event: the uid and the start it replaces
+    let get = |properties: &[(String, Property)], name: &str| {
+        properties
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, property)| property.clone())
+    };
+    let mut replaced: HashSet<(String, NaiveDateTime)> = HashSet::new();
+    for (_, properties) in events.iter() {
+        let uid = get(properties, "UID").map(|uid| uid.value);
+        let recurrence_id = get(properties, "RECURRENCE-ID").and_then(|id| datetime(&id).ok()?);
+        if let (Some(uid), Some(recurrence_id)) = (uid, recurrence_id) {
+            replaced.insert((uid, recurrence_id));
+        }
+    }
+
+    let mut found = Vec::new();
+    let mut warnings = Vec::new();
+    for (line_number, properties) in events.iter() {
+        let error = |message: &'static str, value: &str| {
+            TTError::new(TTErrorKind::ParseError(message, value.to_string())).at_line(*line_number)
+        };
+        let dtstart = match get(properties, "DTSTART") {
+            Some(start) => start,
+            None => return Err(error("the event has no start", "")),
+        };
+        let zoned_start =
+            zoned_datetime(&dtstart).map_err(|_| error("invalid time", &dtstart.value))?;
+        let cancelled =
+            get(properties, "STATUS").is_some_and(|s| s.value.eq_ignore_ascii_case("CANCELLED"));
+        let (zoned_start, zone) = match (zoned_start, cancelled) {
+            (Some(zoned_start), false) => zoned_start,
+            _ => continue,
+        };
+        let start = zone.to_local(&zoned_start);
+        let length = match (get(properties, "DTEND"), get(properties, "DURATION")) {
+            (Some(end), _) => match datetime(&end) {
+                Ok(Some(end)) => end - start,
+                _ => return Err(error("invalid time", &end.value)),
+            },
+            (None, Some(length)) => {
+                duration(&length.value).ok_or_else(|| error("invalid duration", &length.value))?
+            }
+            (None, None) => Duration::zero(),
+        };
+        let summary = get(properties, "SUMMARY")
+            .map(|summary| unescaped(&summary.value))
+            .unwrap_or_default();
+        if let Err(tzid) = time_zone(&dtstart) {
+            warnings.push(format!(
+                "the time zone {} of \"{}\" is unknown, its times are taken as local times",
+                tzid, summary
+            ));
+        }
+        let uid = get(properties, "UID")
+            .map(|uid| uid.value)
+            .unwrap_or_default();
+        let excluded: HashSet<NaiveDateTime> = properties
+            .iter()
+            .filter(|(name, _)| name == "EXDATE")
+            .flat_map(|(_, exdate)| {
+                exdate.value.split(',').filter_map(move |value| {
+                    let property = Property {
+                        value: value.to_string(),
+                        ..exdate.clone()
+                    };
+                    datetime(&property).ok()?
+                })
+            })
+            .collect();
+        // an event that replaces an occurrence is never replaced itself
+        let replacing = get(properties, "RECURRENCE-ID").is_some();
+        let starts = match get(properties, "RRULE") {
+            Some(rule) if !replacing => occurrences(zoned_start, zone, &rule.value, *to)
+                .unwrap_or_else(|| {
+                    warnings.push(format!(
+                        "the repeat rule {} of \"{}\" is not supported, only its first time \
+                         is read",
+                        rule.value, summary
+                    ));
+                    vec![start]
+                }),
+            _ => vec![start],
+        };
+        for start in starts {
+            let dropped = !replacing
+                && (excluded.contains(&start) || replaced.contains(&(uid.clone(), start)));
+            if start >= *from && start < *to && !dropped {
+                found.push(Event {
+                    uid: uid.clone(),
+                    summary: summary.clone(),
+                    start,
+                    end: start + length,
+                });
+            }
+        }
+    }
+    found.sort_by_key(|event| event.start);
+    warnings.dedup();
+    Ok((found, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::sample_records;
+
+    #[test]
+    fn events_of_the_records() {
+        let records = sample_records();
+        let calendar = write(&records);
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.contains("\r\nSUMMARY:JIRA-1\r\nDESCRIPTION:fixed \"it\"\r\n"));
+        assert!(calendar.contains("\r\nCATEGORIES:review,customer=acme,=j1\r\n"));
+        let day = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let events = read_events(
+            &calendar,
+            &day.and_hms_opt(0, 0, 0).unwrap(),
+            &day.and_hms_opt(23, 0, 0).unwrap(),
+        );
+        let events: Vec<(String, NaiveDateTime, NaiveDateTime)> = events
+            .unwrap()
+            .0
+            .into_iter()
+            .map(|event| (event.summary, event.start, event.end))
+            .collect();
+        let expected: Vec<(String, NaiveDateTime, NaiveDateTime)> = records
+            .iter()
+            .filter(|record| !record.is_break)
+            .map(|r| (r.activity.clone(), r.local_start(), r.local_end()))
+            .collect();
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn folded_and_escaped() {
+        let long = "x".repeat(100);
+        let line = content_line(&format!("SUMMARY:{}", long));
+        assert!(line.lines().all(|line| line.len() <= 75));
+        let lines = content_lines(&line);
+        assert_eq!(lines, vec![(1, format!("SUMMARY:{}", long))]);
+        assert_eq!(unescaped(&escaped("a, b; c\\d\ne")), "a, b; c\\d\ne");
+    }
+
+    #[test]
+    fn weekly_with_replaced_occurrence() {
+        let calendar = "BEGIN:VCALENDAR\n\
+            BEGIN:VEVENT\nUID:jf\nSUMMARY:Jour fixe\nDTSTART;TZID=Europe/Berlin:20200601T100000\n\
+            DTEND;TZID=Europe/Berlin:20200601T110000\n\
+            RRULE:FREQ=WEEKLY;INTERVAL=1;BYDAY=MO,TH;UNTIL=20200611\nEND:VEVENT\n\
+            BEGIN:VEVENT\nUID:jf\nRECURRENCE-ID;TZID=Europe/Berlin:20200604T100000\n\
+            SUMMARY:Jour fixe (moved)\n\
+            DTSTART:20200604T140000\nDTEND:20200604T150000\nEND:VEVENT\n\
+            BEGIN:VEVENT\nSUMMARY:Holiday\nDTSTART;VALUE=DATE:20200605\nEND:VEVENT\n\
+            BEGIN:VEVENT\nSUMMARY:Cancelled\nSTATUS:CANCELLED\nDTSTART:20200605T100000\nEND:VEVENT\n\
+            END:VCALENDAR\n";
+        let day = |day| {
+            NaiveDate::from_ymd_opt(2020, 6, day)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        };
+        let events: Vec<(String, NaiveDateTime)> = read_events(calendar, &day(1), &day(30))
+            .unwrap()
+            .0
+            .into_iter()
+            .map(|event| (event.summary, event.start))
+            .collect();
+        // 10:00 in Berlin is 8:00 UTC in summer
+        let berlin = |day_of_month| from_utc(&(day(day_of_month) + Duration::hours(8)));
+        assert_eq!(
+            events,
+            vec![
+                ("Jour fixe".to_string(), berlin(1)),
+                (
+                    "Jour fixe (moved)".to_string(),
+                    day(4) + Duration::hours(14)
+                ),
+                ("Jour fixe".to_string(), berlin(8)),
+                ("Jour fixe".to_string(), berlin(11)),
+            ]
+        );
+    }
+
+    #[test]
+    fn time_zones() {
+        let calendar = "BEGIN:VCALENDAR\n\
+            BEGIN:VEVENT\nSUMMARY:Call\nDTSTART;TZID=America/New_York:20200105T090000\n\
+            DTEND;TZID=America/New_York:20200105T100000\n\
+            RRULE:FREQ=MONTHLY;COUNT=6\nEND:VEVENT\n\
+            BEGIN:VEVENT\nSUMMARY:Sync\nDTSTART;TZID=W. Europe Standard Time:20200601T100000\n\
+            DURATION:PT30M\nEND:VEVENT\n\
+            END:VCALENDAR\n";
+        let day = |month, day| {
+            NaiveDate::from_ymd_opt(2020, month, day)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        };
+        let (events, warnings) = read_events(calendar, &day(1, 1), &day(12, 31)).unwrap();
+        // 9:00 in New York is 14:00 UTC in winter and 13:00 UTC in summer
+        let utc = |month, hour| from_utc(&(day(month, 5) + Duration::hours(hour)));
+        let starts: Vec<NaiveDateTime> = events.iter().map(|event| event.start).collect();
+        assert_eq!(
+            starts,
+            vec![
+                utc(1, 14),
+                utc(2, 14),
+                utc(3, 14),
+                utc(4, 13),
+                utc(5, 13),
+                day(6, 1) + Duration::hours(10),
+                utc(6, 13),
+            ]
+        );
+        assert_eq!(events[0].end - events[0].start, Duration::hours(1));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("W. Europe Standard Time"));
+    }
+
+    #[test]
+    fn monthly_and_yearly_rules() {
+        let start = NaiveDate::from_ymd_opt(2020, 1, 31)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        let until = NaiveDate::from_ymd_opt(2021, 12, 31)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let dates = |rule| -> Vec<String> {
+            occurrences(start, Zone::Local, rule, until)
+                .unwrap()
+                .iter()
+                .map(|time| time.format("%F").to_string())
+                .collect()
+        };
+        // months without a 31st are left out
+        assert_eq!(
+            dates("FREQ=MONTHLY;COUNT=3"),
+            vec!["2020-01-31", "2020-03-31", "2020-05-31"]
+        );
+        assert_eq!(
+            dates("FREQ=MONTHLY;BYMONTHDAY=-1;COUNT=3"),
+            vec!["2020-01-31", "2020-02-29", "2020-03-31"]
+        );
+        assert_eq!(
+            dates("FREQ=MONTHLY;INTERVAL=2;BYDAY=1MO,-1FR;COUNT=4"),
+            vec!["2020-01-31", "2020-03-02", "2020-03-27", "2020-05-04"]
+        );
+        assert_eq!(dates("FREQ=YEARLY"), vec!["2020-01-31", "2021-01-31"]);
+        assert_eq!(
+            dates("FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU"),
+            vec!["2020-03-29", "2021-03-28"]
+        );
+        assert_eq!(
+            occurrences(start, Zone::Local, "FREQ=MONTHLY;BYSETPOS=-1", until),
+            None
+        );
+        assert_eq!(occurrences(start, Zone::Local, "FREQ=HOURLY", until), None);
+    }
+
+    #[test]
+    fn rules_without_occurrences() {
+        let monday = NaiveDate::from_ymd_opt(2020, 6, 1)
+            .unwrap()
+            .and_hms_opt(10, 0, 0)
+            .unwrap();
+        let until = NaiveDate::from_ymd_opt(2021, 12, 31)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let never = |rule| occurrences(monday, Zone::Local, rule, until).unwrap();
+        // every 7th day is a Monday again
+        assert!(never("FREQ=DAILY;INTERVAL=7;BYDAY=TU").is_empty());
+        assert!(never("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30").is_empty());
+        assert!(never("FREQ=MONTHLY;INTERVAL=12;BYMONTHDAY=31").is_empty());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(duration("P1DT2S"), Some(Duration::seconds(86402)));
+        assert_eq!(duration("PT1X"), None);
+    }
+}
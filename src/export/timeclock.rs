@@ -0,0 +1,167 @@
+//! The timeclock format of ledger and hledger:
+//!
+//! ```text
+//! i 2020/06/01 08:00:00 JIRA-1  fixed the login  ; review:, customer:acme, shortname:j1
+//! o 2020/06/01 09:00:00
+//! ```
+//!
+//! The account is the activity and the description is the note. The tags of tt are tags of
+//! hledger in the comment: `tag:` for a tag, `key:value` for metadata and `shortname:` for the
+//! shortname. Breaks are the gaps between clocking out and in again, they are not written.
+//! Distributable activities keep their `_`.
+
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use super::Record;
+use crate::error::{TTError, TTErrorKind};
+use crate::log_parser;
+
+const TIME_FORMAT: &str = "%Y/%m/%d %H:%M:%S";
+
+// the tag of hledger for a tag of tt
+fn hledger_tag(tag: &str) -> String {
+    if let Some(shortname) = tag.strip_prefix('=') {
+        return format!("shortname:{}", shortname);
+    }
+    match log_parser::as_metadata(tag) {
+        Some((key, value)) => format!("{}:{}", key, value),
+        None => format!("{}:", tag),
+    }
+}
+
+// the tag of tt for a tag of hledger
+fn tt_tag(tag: &str) -> String {
+    match tag.split_once(':') {
+        Some(("shortname", shortname)) => format!("={}", shortname.trim()),
+        Some((name, "")) => name.to_string(),
+        Some((key, value)) => format!("{}={}", key, value.trim()),
+        None => tag.to_string(),
+    }
+}
+
+/// the clock-ins and clock-outs of the records that are not breaks
+pub fn write(records: &[Record]) -> String {
+    records
+        .iter()
+        .filter(|record| !record.is_break)
+        .map(|record| {
+            let mut clock_in = format!(
+                "i {} {}",
+                record.local_start().format(TIME_FORMAT),
+                record.activity
+            );
+            if let Some(note) = &record.note {
+                clock_in += &format!("  {}", note.replace(';', ","));
+            }
+            let tags = record.tags_with_shortname();
+            if !tags.is_empty() {
+                clock_in += &format!("  ; {}", tags.iter().map(|t| hledger_tag(t)).join(", "));
+            }
+            format!(
+                "{}\no {}\n",
+                clock_in,
+                record.local_end().format(TIME_FORMAT)
+            )
+        })
+        .collect()
+}
+
+/// the records of the clocked time, split at the day changes
+pub fn read(text: &str) -> Result<Vec<Record>, TTError> {
+    let mut records = Vec::new();
+    // the clock-in that has not been clocked out: time, activity, tags, note
+    let mut clocked_in: Option<(NaiveDateTime, String, Vec<String>, Option<String>)> = None;
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: &'static str| {
+            TTError::new(TTErrorKind::ParseError(message, line.to_string())).at_line(index + 1)
+        };
+        let (code, rest) = line.split_at(line.chars().next().map_or(0, char::len_utf8));
+        if line.trim().is_empty() || [";", "#", "*"].contains(&code) {
+            continue;
+        }
+        let (entry, comment) = rest.split_once(';').unwrap_or((rest, ""));
+        let words: Vec<&str> = entry.split_whitespace().collect();
+        let time = words
+            .get(0..2)
+            .and_then(|date_time| {
+                NaiveDateTime::parse_from_str(&date_time.join(" "), TIME_FORMAT)
+                    .or_else(|_| {
+                        NaiveDateTime::parse_from_str(&date_time.join(" "), "%Y/%m/%d %H:%M")
+                    })
+                    .ok()
+            })
+            .ok_or_else(|| error("invalid time"))?;
+        match (code, clocked_in.take()) {
+            ("i", None) => {
+                // the account ends with two spaces or a tab, the description follows
+                let account_and_description = entry
+                    .trim_start()
+                    .splitn(3, char::is_whitespace)
+                    .nth(2)
+                    .unwrap_or("");
+                let (account, description) = account_and_description
+                    .split_once("  ")
+                    .or_else(|| account_and_description.split_once('\t'))
+                    .unwrap_or((account_and_description, ""));
+                if account.trim().is_empty() {
+                    return Err(error("the clock-in has no account"));
+                }
+                let tags = comment
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(tt_tag)
+                    .collect();
+                let note = Some(description.trim().to_string()).filter(|d| !d.is_empty());
+                clocked_in = Some((time, account.trim().to_string(), tags, note));
+            }
+            ("i", Some(_)) => {
+                return Err(error("clock-in while clocked in").with_hint("clock out first"))
+            }
+            ("o", Some((start, activity, tags, note)))
+            | ("O", Some((start, activity, tags, note))) => {
+                if time < start {
+                    return Err(error("the clock-out is before the clock-in"));
+                }
+                records.extend(Record::between(start, time, &activity, tags, note));
+            }
+            ("o", None) | ("O", None) => return Err(error("clock-out without clock-in")),
+            _ => return Err(error("not a line of the timeclock format")),
+        }
+    }
+    match clocked_in {
+        Some(_) => Err(TTError::new(TTErrorKind::ParseError(
+            "the last clock-in has no clock-out",
+            text.lines().last().unwrap_or("").to_string(),
+        ))),
+        None => Ok(records),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::sample_records;
+
+    #[test]
+    fn round_trip() {
+        let records = sample_records();
+        let text = write(&records);
+        assert!(text.starts_with("i 2020/06/01 08:00:00 email  ; shortname:mail\n"));
+        assert!(text.contains(" JIRA-1  fixed \"it\"  ; review:, customer:acme, shortname:j1\n"));
+        let work: Vec<Record> = records.into_iter().filter(|r| !r.is_break).collect();
+        assert_eq!(read(&text).unwrap(), work);
+    }
+
+    #[test]
+    fn clock_errors() {
+        let err = read("i 2020/06/01 08:00:00 email\ni 2020/06/01 09:00:00 email\n").unwrap_err();
+        assert_eq!(err.details.line_number, Some(2));
+        assert!(read("o 2020/06/01 08:00:00\n").is_err());
+        assert!(read("i 2020/06/01 08:00:00 email\n").is_err());
+        let records =
+            read("; a comment\n\ni 2020/06/01 08:00 email\no 2020/06/01 08:30\n").unwrap();
+        assert_eq!(records[0].duration.num_minutes(), 30);
+    }
+}
@@ -0,0 +1,154 @@
+//! The data files of timewarrior, like `~/.timewarrior/data/2020-06.data`:
+//!
+//! ```text
+//! inc 20200601T060000Z - 20200601T070000Z # JIRA-1 review customer=acme =j1 # "fixed the login"
+//! ```
+//!
+//! The first tag is the activity, the other tags are the tags of tt with the shortname as
+//! `=shortname` and the metadata as `key=value`. The annotation is the note. Breaks are the gaps
+//! between the intervals, they are not written. Distributable activities keep their `_`.
+
+use std::iter;
+
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+
+use super::{from_utc, to_utc, Record};
+use crate::error::{TTError, TTErrorKind};
+
+// times are in UTC
+const TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+fn quoted(word: &str) -> String {
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// a tag, quoted if it would not be one word
+fn tag(tag: &str) -> String {
+    match tag.is_empty() || tag == "#" || tag.contains(|c: char| c.is_whitespace() || c == '"') {
+        true => quoted(tag),
+        false => tag.to_string(),
+    }
+}
+
+/// the intervals of the records that are not breaks
+pub fn write(records: &[Record]) -> String {
+    records
+        .iter()
+        .filter(|record| !record.is_break)
+        .map(|record| {
+            let tags = iter::once(record.activity.clone())
+                .chain(record.tags_with_shortname())
+                .map(|t| tag(&t))
+                .join(" ");
+            let annotation = match &record.note {
+                Some(note) => format!(" # {}", quoted(note)),
+                None => "".to_string(),
+            };
+            format!(
+                "inc {} - {} # {}{}\n",
+                to_utc(&record.local_start()).format(TIME_FORMAT),
+                to_utc(&record.local_end()).format(TIME_FORMAT),
+                tags,
+                annotation
+            )
+        })
+        .collect()
+}
+
+// the words of a line in its parts, which are separated by `#`. Quoted words can contain spaces.
+fn parts(line: &str) -> Option<Vec<Vec<String>>> {
+    let mut parts = vec![vec![]];
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let word = match chars.next() {
+            None => return Some(parts),
+            Some('"') => {
+                let mut word = String::new();
+                loop {
+                    match chars.next()? {
+                        '\\' => word.push(chars.next()?),
+                        '"' => break,
+                        c => word.push(c),
+                    }
+                }
+                word
+            }
+            Some(c) => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    word.push(c);
+                }
+                if word == "#" {
+                    parts.push(vec![]);
+                    continue;
+                }
+                word
+            }
+        };
+        parts.last_mut()?.push(word);
+    }
+}
+
+/// the records of the intervals, split at the day changes
+pub fn read(text: &str) -> Result<Vec<Record>, TTError> {
+    let mut records = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let error = |message: &'static str| {
+            TTError::new(TTErrorKind::ParseError(message, line.to_string())).at_line(index + 1)
+        };
+        let time = |word: &str| {
+            NaiveDateTime::parse_from_str(word, TIME_FORMAT)
+                .map(|utc| from_utc(&utc))
+                .map_err(|_| error("invalid time"))
+        };
+        let parts = parts(line).ok_or_else(|| error("a quoted word has no end"))?;
+        let (start, end) = match parts[0].as_slice() {
+            [inc, start, dash, end] if inc == "inc" && dash == "-" => (time(start)?, time(end)?),
+            [inc, _] if inc == "inc" => {
+                return Err(error("the interval has not ended")
+                    .with_hint("stop it with `timew stop` first"))
+            }
+            _ => return Err(error("not an interval of timewarrior")),
+        };
+        let mut tags = parts.get(1).cloned().unwrap_or_default().into_iter();
+        let activity = tags.next().ok_or_else(|| {
+            error("the interval has no tags").with_hint("the first tag is the activity")
+        })?;
+        let note = parts
+            .get(2)
+            .map(|words| words.join(" "))
+            .filter(|note| !note.is_empty());
+        records.extend(Record::between(start, end, &activity, tags.collect(), note));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::sample_records;
+
+    #[test]
+    fn round_trip() {
+        let records = sample_records();
+        let text = write(&records);
+        assert_eq!(text.lines().count(), 3);
+        assert!(text.contains(" # JIRA-1 review customer=acme =j1 # \"fixed \\\"it\\\"\""));
+        let work: Vec<Record> = records.into_iter().filter(|r| !r.is_break).collect();
+        assert_eq!(read(&text).unwrap(), work);
+    }
+
+    #[test]
+    fn open_interval() {
+        let err = read("inc 20200601T060000Z # email\n").unwrap_err();
+        assert_eq!(err.details.line_number, Some(1));
+        assert!(read("inc 20200601T060000Z - 20200601T070000Z\n").is_err());
+    }
+}
@@ -0,0 +1,78 @@
+//! The frames file of Watson, like `~/.config/watson/frames`: a JSON array of frames
+//! `[start, stop, project, id, tags, updated_at]` with the times in seconds since the epoch.
+//!
+//! The project is the activity, the tags are the tags of tt with the shortname as `=shortname`
+//! and the metadata as `key=value`. Frames have no notes, they are not written. Breaks are the
+//! gaps between the frames, they are not written either. Distributable activities keep their `_`.
+
+use serde::{Deserialize, Serialize};
+
+use super::{from_timestamp, to_utc, Record};
+use crate::error::{TTError, TTErrorKind};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Frame(i64, i64, String, String, Vec<String>, i64);
+
+/// the frames of the records that are not breaks
+pub fn write(records: &[Record]) -> Result<String, TTError> {
+    let timestamp = |local| to_utc(&local).and_utc().timestamp();
+    let frames: Vec<Frame> = records
+        .iter()
+        .filter(|record| !record.is_break)
+        .map(|record| {
+            let stop = timestamp(record.local_end());
+            Frame(
+                timestamp(record.local_start()),
+                stop,
+                record.activity.clone(),
+                record.id(),
+                record.tags_with_shortname(),
+                stop,
+            )
+        })
+        .collect();
+    Ok(serde_json::to_string_pretty(&frames)?)
+}
+
+/// the records of the frames, split at the day changes
+pub fn read(text: &str) -> Result<Vec<Record>, TTError> {
+    let frames: Vec<Frame> = serde_json::from_str(text)?;
+    let mut records = Vec::new();
+    for (index, Frame(start, stop, project, ..)) in frames.iter().enumerate() {
+        let tags = frames[index].4.clone();
+        match (from_timestamp(*start), from_timestamp(*stop)) {
+            (Some(start), Some(stop)) => {
+                records.extend(Record::between(start, stop, project, tags, None))
+            }
+            _ => {
+                return Err(TTError::new(TTErrorKind::ParseError(
+                    "invalid time",
+                    format!("{} - {}", start, stop),
+                ))
+                .context(format!("reading frame {}", index + 1)))
+            }
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::tests::sample_records;
+
+    #[test]
+    fn round_trip() {
+        let records = sample_records();
+        let text = write(&records).unwrap();
+        let frames: Vec<Frame> = serde_json::from_str(&text).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].3.len(), 32);
+        let work: Vec<Record> = records
+            .into_iter()
+            .filter(|r| !r.is_break)
+            .map(|r| Record { note: None, ..r })
+            .collect();
+        assert_eq!(read(&text).unwrap(), work);
+    }
+}
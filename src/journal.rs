@@ -0,0 +1,381 @@
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::result::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::{DateTime, Local, NaiveDate, SecondsFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::error::TTError;
+use crate::utils::{FileLock, FileProxy};
+
+/// a line that a command has appended to a file, or a rewrite of a file, as recorded in the
+/// journal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// when the command ran, all lines of an operation have the same time
+    pub time: String,
+    /// the command that wrote the line, like `add`
+    pub command: String,
+    /// tells apart the operations of commands that ran in the same second, empty in journals
+    /// of older versions
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    pub path: PathBuf,
+    /// the line without line ending, empty for a rewrite
+    #[serde(default)]
+    pub line: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewrite: Option<Rewrite>,
+}
+
+/// the lines of a file, or of the log of a day in the storage, before and after a command has
+/// replaced them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rewrite {
+    /// the day of the log, None if the whole file has been rewritten
+    pub date: Option<NaiveDate>,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// a change of a file by a command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// the line without line ending has been appended
+    Append(String),
+    Rewrite(Rewrite),
+}
+
+/// the changes that a command has made at one time, it is undone as a whole
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    pub time: String,
+    pub command: String,
+    pub id: String,
+    pub changes: Vec<(PathBuf, Change)>,
+}
+
+impl JournalEntry {
+    fn change(self) -> (PathBuf, Change) {
+        match self.rewrite {
+            Some(rewrite) => (self.path, Change::Rewrite(rewrite)),
+            None => (self.path, Change::Append(self.line)),
+        }
+    }
+}
+
+/// records the lines that a command appends to the log and activities files, so that
+/// `tt undo` can remove exactly these lines again. Rewrites of files and of the log of a day
+/// are recorded with the lines before and after, undo puts back the lines before.
+///
+/// ```
+/// use std::path::PathBuf;
+/// use chrono::Local;
+/// use timetracker::journal::{self, Journal};
+/// use timetracker::utils::{FakeFile, FileProxy};
+/// let journalfile = FakeFile::new(PathBuf::from("journal"));
+/// let logfile = FakeFile::new(PathBuf::from("logfile"));
+/// let journal = Journal::new(&journalfile, &Local::now(), "add");
+/// journal.append_line(&logfile, "08:00 email").unwrap();
+/// journal.append_line(&logfile, "09:00 meeting").unwrap();
+/// let operations = journal::read_operations(journalfile.reader().unwrap()).unwrap();
+/// assert_eq!(operations.len(), 1);
+/// assert_eq!(
+///     operations[0].changes[1],
+///     (PathBuf::from("logfile"), journal::Change::Append("09:00 meeting".to_string()))
+/// );
+/// ```
+pub struct Journal<'a, F> {
+    file: &'a F,
+    time: String,
+    command: String,
+    id: String,
+}
+
+// counts the journals of the process, for the id of the operation
+static JOURNALS: AtomicUsize = AtomicUsize::new(0);
+
+impl<'a, F> Journal<'a, F> {
+    pub fn new(file: &'a F, now: &DateTime<Local>, command: &str) -> Journal<'a, F> {
+        Journal {
+            file,
+            time: now.to_rfc3339_opts(SecondsFormat::Secs, false),
+            command: command.to_string(),
+            id: format!(
+                "{}-{}",
+                std::process::id(),
+                JOURNALS.fetch_add(1, Ordering::Relaxed)
+            ),
+        }
+    }
+
+    /// locks the journal for the command, see FileProxy::lock
+    pub fn lock<R: BufRead, W: Write>(&self) -> Result<FileLock, TTError>
+    where
+        F: FileProxy<R, W>,
+    {
+        Ok(self.file.lock()?)
+    }
+
+    /// appends the line to the file and records it in the journal
+    pub fn append_line<R: BufRead, W: Write>(&self, file: &F, line: &str) -> Result<(), TTError>
+    where
+        F: FileProxy<R, W>,
+    {
+        writeln!(file.writer()?, "{}", line)?;
+        self.write_entry(JournalEntry {
+            time: self.time.to_string(),
+            command: self.command.to_string(),
+            id: self.id.to_string(),
+            path: file.pathname().to_path_buf(),
+            line: line.to_string(),
+            rewrite: None,
+        })
+    }
+
+    /// replaces the lines of the file and records the lines before and after in the journal
+    pub fn rewrite_file<R: BufRead, W: Write>(
+        &self,
+        file: &F,
+        lines: &[String],
+    ) -> Result<(), TTError>
+    where
+        F: FileProxy<R, W>,
+    {
+        let before = file.reader()?.lines().collect::<Result<Vec<String>, _>>()?;
+        let content: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        file.replace_content(content.as_bytes())?;
+        self.record_rewrite(file.pathname(), None, before, lines)
+    }
+
+    /// records that the file, or the log of the day in it, has been rewritten from the lines
+    /// before to the given ones. Nothing is recorded if they are the same.
+    pub fn record_rewrite<R: BufRead, W: Write>(
+        &self,
+        path: &Path,
+        date: Option<NaiveDate>,
+        before: Vec<String>,
+        lines: &[String],
+    ) -> Result<(), TTError>
+    where
+        F: FileProxy<R, W>,
+    {
+        if before == lines {
+            return Ok(());
+        }
+        self.write_entry(JournalEntry {
+            time: self.time.to_string(),
+            command: self.command.to_string(),
+            id: self.id.to_string(),
+            path: path.to_path_buf(),
+            line: String::new(),
+            rewrite: Some(Rewrite {
+                date,
+                before,
+                after: lines.to_vec(),
+            }),
+        })
+    }
+
+    fn write_entry<R: BufRead, W: Write>(&self, entry: JournalEntry) -> Result<(), TTError>
+    where
+        F: FileProxy<R, W>,
+    {
+        writeln!(self.file.writer()?, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// reads the journal, the lines of one run of a command make up an operation.
+/// The most recent operation is the last one.
+pub fn read_operations(reader: impl BufRead) -> Result<Vec<Operation>, TTError> {
+    let mut operations: Vec<Operation> = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: JournalEntry = serde_json::from_str(&line)?;
+        match operations.last_mut() {
+            Some(operation)
+                if operation.id == entry.id
+                    && operation.time == entry.time
+                    && operation.command == entry.command =>
+            {
+                operation.changes.push(entry.change())
+            }
+            _ => operations.push(Operation {
+                time: entry.time.clone(),
+                command: entry.command.clone(),
+                id: entry.id.clone(),
+                changes: vec![entry.change()],
+            }),
+        }
+    }
+    Ok(operations)
+}
+
+/// writes the operations in the format of the journal
+pub fn write_operations(mut writer: impl Write, operations: &[Operation]) -> Result<(), TTError> {
+    for operation in operations {
+        for (path, change) in operation.changes.iter() {
+            let (line, rewrite) = match change {
+                Change::Append(line) => (line.to_string(), None),
+                Change::Rewrite(rewrite) => (String::new(), Some(rewrite.clone())),
+            };
+            let entry = JournalEntry {
+                time: operation.time.to_string(),
+                command: operation.command.to_string(),
+                id: operation.id.to_string(),
+                path: path.to_path_buf(),
+                line,
+                rewrite,
+            };
+            writeln!(writer, "{}", serde_json::to_string(&entry)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// removes the last line of the file that is equal to the given one, the other lines stay as
+/// they are. Returns whether the line has been found.
+pub fn remove_line<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    file: &F,
+    line: &str,
+) -> Result<bool, TTError> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut reader = file.reader()?;
+    loop {
+        let mut original = String::new();
+        if reader.read_line(&mut original)? == 0 {
+            break;
+        }
+        lines.push(original);
+    }
+    let found = lines
+        .iter()
+        .rposition(|original| original.trim_end_matches(&['\r', '\n'][..]) == line);
+    if let Some(index) = found {
+        lines.remove(index);
+        file.replace_content(lines.concat().as_bytes())?;
+    }
+    Ok(found.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::FakeFile;
+    use chrono::TimeZone;
+
+    #[test]
+    fn operations_by_time() {
+        let journalfile = FakeFile::new(PathBuf::from("journal"));
+        let logfile = FakeFile::new(PathBuf::from("logfile"));
+        let activitiesfile = FakeFile::new(PathBuf::from("activities"));
+        let journal = Journal::new(
+            &journalfile,
+            &Local.with_ymd_and_hms(2020, 6, 1, 8, 0, 0).unwrap(),
+            "add",
+        );
+        journal.append_line(&activitiesfile, "mail email").unwrap();
+        journal.append_line(&logfile, "08:00 email =mail").unwrap();
+        let journal = Journal::new(
+            &journalfile,
+            &Local.with_ymd_and_hms(2020, 6, 1, 9, 0, 0).unwrap(),
+            "add",
+        );
+        journal.append_line(&logfile, "09:00 meeting").unwrap();
+
+        let operations = read_operations(journalfile.reader().unwrap()).unwrap();
+        assert_eq!(operations.len(), 2);
+        assert_eq!(operations[0].changes.len(), 2);
+        assert_eq!(operations[1].changes.len(), 1);
+        assert_eq!(activitiesfile.close().unwrap(), "mail email\n");
+
+        let rewritten = FakeFile::new(PathBuf::from("journal"));
+        write_operations(rewritten.writer().unwrap(), &operations[..1]).unwrap();
+        assert_eq!(
+            read_operations(rewritten.reader().unwrap()).unwrap(),
+            operations[..1].to_vec()
+        );
+    }
+
+    #[test]
+    fn operations_in_the_same_second() {
+        let journalfile = FakeFile::new(PathBuf::from("journal"));
+        let logfile = FakeFile::new(PathBuf::from("logfile"));
+        let now = Local.with_ymd_and_hms(2020, 6, 1, 8, 0, 0).unwrap();
+        Journal::new(&journalfile, &now, "add")
+            .append_line(&logfile, "08:00 email")
+            .unwrap();
+        Journal::new(&journalfile, &now, "add")
+            .append_line(&logfile, "08:00 meeting")
+            .unwrap();
+        let operations = read_operations(journalfile.reader().unwrap()).unwrap();
+        assert_eq!(operations.len(), 2);
+        assert_ne!(operations[0].id, operations[1].id);
+
+        // the journal of an older version has no ids
+        let old = FakeFile::with_content(
+            PathBuf::from("journal"),
+            b"{\"time\":\"2020-06-01T08:00:00+02:00\",\"command\":\"add\",\"path\":\"log\",\"line\":\"08:00 email\"}\n\
+            {\"time\":\"2020-06-01T08:00:00+02:00\",\"command\":\"add\",\"path\":\"log\",\"line\":\"08:00 meeting\"}\n",
+        );
+        let operations = read_operations(old.reader().unwrap()).unwrap();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].changes.len(), 2);
+    }
+
+    #[test]
+    fn rewritten_file() {
+        let journalfile = FakeFile::new(PathBuf::from("journal"));
+        let activitiesfile = FakeFile::with_content(PathBuf::from("activities"), b"mail email\n");
+        let journal = Journal::new(
+            &journalfile,
+            &Local.with_ymd_and_hms(2020, 6, 1, 8, 0, 0).unwrap(),
+            "prune",
+        );
+        journal
+            .rewrite_file(&activitiesfile, &["mail email archived".to_string()])
+            .unwrap();
+        journal
+            .rewrite_file(&activitiesfile, &["mail email archived".to_string()])
+            .unwrap();
+
+        let operations = read_operations(journalfile.reader().unwrap()).unwrap();
+        assert_eq!(
+            operations[0].changes,
+            vec![(
+                PathBuf::from("activities"),
+                Change::Rewrite(Rewrite {
+                    date: None,
+                    before: vec!["mail email".to_string()],
+                    after: vec!["mail email archived".to_string()],
+                })
+            )]
+        );
+        let rewritten = FakeFile::new(PathBuf::from("journal"));
+        write_operations(rewritten.writer().unwrap(), &operations).unwrap();
+        assert_eq!(
+            read_operations(rewritten.reader().unwrap()).unwrap(),
+            operations
+        );
+    }
+
+    #[test]
+    fn remove_last_equal_line() {
+        let logfile = FakeFile::with_content(
+            PathBuf::from("logfile"),
+            b"08:00 email\n# a comment\n09:00 email\n10:00 break\n",
+        );
+        writeln!(logfile.writer().unwrap(), "09:00 email").unwrap();
+        assert!(remove_line(&logfile, "09:00 email").unwrap());
+        assert!(!remove_line(&logfile, "11:00 email").unwrap());
+        assert_eq!(
+            logfile.close().unwrap(),
+            "08:00 email\n# a comment\n09:00 email\n10:00 break\n"
+        );
+    }
+}
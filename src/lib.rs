@@ -1,5 +1,5 @@
 use std::io::{BufRead, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::result::Result;
 use std::str;
 
@@ -9,18 +9,34 @@ use chrono::NaiveDate;
 use structopt::StructOpt;
 
 use crate::error::TTError;
+use crate::storage::{DailyFiles, SingleFile, Storage};
 use crate::utils::FileProxy;
 
+use self::subcommands::activities::ActivitiesOpt;
 use self::subcommands::add::AddOpt;
+use self::subcommands::archive::ArchiveOpt;
+use self::subcommands::check::CheckOpt;
 use self::subcommands::report::ReportOpt;
-use crate::configfile::TTConfig;
+use crate::configfile::{StorageKind, TTConfig};
 use crate::subcommands::edit::EditOpt;
+use crate::subcommands::export::ExportOpt;
+use crate::subcommands::import::ImportOpt;
+use crate::subcommands::import_ics::ImportIcsOpt;
+use crate::subcommands::merge::MergeOpt;
+use crate::subcommands::migrate::MigrateOpt;
 use crate::subcommands::resume::ResumeOpt;
+use crate::subcommands::undo::UndoOpt;
 
+pub mod activities;
+pub mod archive;
 pub mod collector;
 pub mod configfile;
+pub mod datadir;
 pub mod error;
+pub mod export;
+pub mod journal;
 pub mod log_parser;
+pub mod storage;
 pub mod subcommands;
 pub mod utils;
 
@@ -50,15 +66,42 @@ enum Subcommand {
     /// edit the log file or activities file
     Edit(EditOpt),
 
+    /// change the activities file
+    Activities(ActivitiesOpt),
+
     /// create a report
     Report(ReportOpt),
 
+    /// check log files and activities file for problems, without changing them
+    Check(CheckOpt),
+
     /// resume the activity before the current one
     Resume(ResumeOpt),
 
+    /// undo the last changes to the log file and activities file
+    Undo(UndoOpt),
+
     /// list all activities from the activities file
     List,
 
+    /// move the daily log files to another layout
+    Migrate(MigrateOpt),
+
+    /// pack the log files of old days into compressed monthly archives
+    Archive(ArchiveOpt),
+
+    /// merge the logs and activities of another machine into these ones
+    Merge(MergeOpt),
+
+    /// print the intervals of the log as csv or json
+    Export(ExportOpt),
+
+    /// add the intervals of a file written by export to the log
+    Import(ImportOpt),
+
+    /// add the meetings of an iCalendar file to the log
+    ImportIcs(ImportIcsOpt),
+
     /// enter interactive mode
     Interactive,
 
@@ -69,29 +112,49 @@ enum Subcommand {
     WatchI3,
 }
 
-pub fn get_activities_file_name() -> PathBuf {
-    let mut path =
-        dirs::home_dir().expect("Cannot figure out your home directory. What's wrong with you?");
-    path.push(".tt");
-    path.push("activities");
-    path
+// the data directory is found by datadir::Locations::resolve
+
+pub fn get_activities_file_name(data_dir: &Path) -> PathBuf {
+    data_dir.join("activities")
+}
+
+/// the log file of the day, in the configured layout
+pub fn get_logfile_name(data_dir: &Path, date: &NaiveDate) -> PathBuf {
+    TTConfig::get().layout.logfile_name(data_dir, date)
+}
+
+/// the log file with all days, if storage = "single-file" is configured
+pub fn get_single_logfile_name(data_dir: &Path) -> PathBuf {
+    data_dir.join("log")
 }
 
-pub fn get_logfile_name(date: &NaiveDate) -> PathBuf {
-    let mut path =
-        dirs::home_dir().expect("Cannot figure out your home directory. What's wrong with you?");
-    let date_str = format!("{}", date.format("%F"));
-    path.push(".tt");
-    path.push(date_str);
-    path
+/// the journal of the lines that have been added to the files, for undo
+pub fn get_journal_file_name(data_dir: &Path) -> PathBuf {
+    data_dir.join("journal")
 }
 
-pub fn get_configfile_name() -> PathBuf {
-    let mut path =
-        dirs::home_dir().expect("Cannot figure out your home directory. What's wrong with you?");
-    path.push(".tt");
-    path.push("config.toml");
-    path
+/// the day that `tt add` last looked for activities to archive
+pub fn get_pruned_file_name(data_dir: &Path) -> PathBuf {
+    data_dir.join("pruned")
+}
+
+/// the storage for the log as configured. The default logfile is used for the current workday
+/// if the logs are kept in daily files.
+pub fn open_storage<'a, R: BufRead + 'a, W: Write + 'a, F: FileProxy<R, W>>(
+    now: &DateTime<Local>,
+    data_dir: &Path,
+    default_logfile: &'a F,
+) -> Box<dyn Storage<R, W, F> + 'a> {
+    let config = TTConfig::get();
+    match config.storage {
+        StorageKind::DailyFiles => Box::new(
+            DailyFiles::new(data_dir.to_path_buf(), config.layout)
+                .with_file(utils::workday(&now.naive_local()), default_logfile),
+        ),
+        StorageKind::SingleFile => {
+            Box::new(SingleFile::new(F::new(get_single_logfile_name(data_dir))))
+        }
+    }
 }
 
 /////////////////////////////////////////////////////
@@ -99,9 +162,11 @@ pub fn get_configfile_name() -> PathBuf {
 pub fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
     args: &Vec<String>,
     now: &DateTime<Local>,
+    data_dir: &Path,
     configfile: &F,
     default_logfile: &F,
     activitiesfile: &F,
+    journalfile: &F,
 ) -> Result<i32, TTError> {
     // we need to hold the config_writer to keep the settings thread safe for tests.
     let _config_writer = TTConfig::init(configfile.reader()?)?;
@@ -109,31 +174,75 @@ pub fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
     let opt = match args.get(1).map(String::as_str) {
         None => Opt::from(Subcommand::Interactive),
         Some("-y") => Opt::from(Subcommand::Report(ReportOpt::from_iter(args))),
-        Some("add") | Some("report") | Some("list") | Some("edit") | Some("resume")
+        Some("add") | Some("activities") | Some("report") | Some("check") | Some("list")
+        | Some("edit") | Some("resume") | Some("undo") | Some("migrate") | Some("archive")
+        | Some("merge") | Some("export") | Some("import") | Some("import-ics")
         | Some("is-active") | Some("watch-i3") | Some("help") | Some("--help") | Some("-h") => {
             Opt::from_iter(args)
         }
         Some(_) => Opt::from(Subcommand::Add(AddOpt::from_iter(args))),
     };
+    let storage = open_storage(now, data_dir, default_logfile);
+    let storage = storage.as_ref();
     match opt.command {
         Subcommand::Add(add_opt) => {
-            subcommands::add::run(add_opt, now, default_logfile, activitiesfile)
+            let prunedfile = F::new(get_pruned_file_name(data_dir));
+            subcommands::add::run(
+                add_opt,
+                now,
+                storage,
+                activitiesfile,
+                journalfile,
+                &prunedfile,
+            )
+        }
+        Subcommand::Activities(activities_opt) => {
+            subcommands::activities::run(activities_opt, now, storage, activitiesfile, journalfile)
         }
         Subcommand::Edit(edit_opt) => {
-            subcommands::edit::run(edit_opt, now, default_logfile, activitiesfile)
+            subcommands::edit::run(edit_opt, now, storage, activitiesfile)
         }
         Subcommand::Interactive => {
-            subcommands::interactive::run(now, default_logfile, activitiesfile)
+            subcommands::interactive::run(now, storage, activitiesfile, journalfile)
+        }
+        Subcommand::List => subcommands::list::run(activitiesfile),
+        Subcommand::Archive(archive_opt) => {
+            subcommands::archive::run(archive_opt, now, data_dir, journalfile)
+        }
+        Subcommand::Merge(merge_opt) => {
+            subcommands::merge::run(merge_opt, now, storage, activitiesfile, journalfile)
+        }
+        Subcommand::Export(export_opt) => subcommands::export::run(export_opt, now, storage),
+        Subcommand::Import(import_opt) => {
+            subcommands::import::run(import_opt, now, storage, journalfile)
+        }
+        Subcommand::ImportIcs(import_ics_opt) => {
+            subcommands::import_ics::run(import_ics_opt, now, storage, journalfile)
+        }
+        Subcommand::Migrate(migrate_opt) => {
+            subcommands::migrate::run(migrate_opt, data_dir, journalfile)
         }
-        Subcommand::List => subcommands::list::run(now, default_logfile, activitiesfile),
         Subcommand::Report(report_opt) => {
-            subcommands::report::run(report_opt, now, default_logfile, activitiesfile)
+            subcommands::report::run(report_opt, now, storage, activitiesfile)
+        }
+        Subcommand::Check(check_opt) => {
+            subcommands::check::run(check_opt, now, storage, activitiesfile)
         }
         Subcommand::Resume(resume_opt) => {
-            subcommands::resume::run(resume_opt, now, default_logfile, activitiesfile)
+            subcommands::resume::run(resume_opt, now, storage, activitiesfile, journalfile)
+        }
+        Subcommand::Undo(undo_opt) => subcommands::undo::run(
+            undo_opt,
+            now,
+            storage,
+            default_logfile,
+            activitiesfile,
+            journalfile,
+        ),
+        Subcommand::IsActive => subcommands::is_active::run(now, storage, activitiesfile),
+        Subcommand::WatchI3 => {
+            subcommands::watch_i3::run(now, storage, activitiesfile, journalfile)
         }
-        Subcommand::IsActive => subcommands::is_active::run(now, default_logfile, activitiesfile),
-        Subcommand::WatchI3 => subcommands::watch_i3::run(now, default_logfile, activitiesfile),
     }
 }
 
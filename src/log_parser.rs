@@ -1,31 +1,150 @@
+use std::collections::BTreeMap;
 use std::io::{BufRead, Write};
+use std::ops::Range;
 use std::result::Result;
 use std::str;
 use std::vec::Vec;
 
-use chrono::NaiveTime;
+use chrono::{Duration, FixedOffset, NaiveTime};
 
+use crate::activities::read_activities;
 use crate::error::{TTError, TTErrorKind};
-use crate::subcommands::add::read_activities;
 use crate::subcommands::watch_i3;
 use crate::utils::FileProxy;
 
 use super::utils;
 
-#[derive(Debug, Eq, PartialEq)]
+/// metadata of a log entry, given as tags in the form `key=value`
+pub type Metadata = BTreeMap<String, String>;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub struct BlockData {
     pub start: NaiveTime,
     pub activity: String,
     pub tags: Vec<String>,
+    pub metadata: Metadata,
+    pub note: Option<String>,
     pub distribute: bool,
+    /// the UTC offset of the start time, if it is recorded in the log
+    pub utc_offset: Option<FixedOffset>,
+    /// whether the start time is written with seconds, so that `08:00:00` keeps them
+    pub start_seconds: bool,
+}
+
+impl BlockData {
+    /// the shortname of the activity, from a tag in the form `=shortname`
+    /// ```
+    /// use timetracker::log_parser::{BlockData, Metadata};
+    /// use chrono::NaiveTime;
+    /// let data = BlockData {
+    ///     start: NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+    ///     activity: "JIRA-12".to_string(),
+    ///     tags: vec!["=api".to_string(), "resume:2".to_string()],
+    ///     metadata: Metadata::new(),
+    ///     note: None,
+    ///     distribute: false,
+    ///     utc_offset: None,
+    ///     start_seconds: false,
+    /// };
+    /// assert_eq!(data.shortname(), Some("api"));
+    /// assert_eq!(data.resume_offset(), Some(2));
+    /// ```
+    pub fn shortname(&self) -> Option<&str> {
+        self.tags.iter().find_map(|t| t.strip_prefix('='))
+    }
+
+    /// the offset on the resume stack, from a tag in the form `resume:N`
+    pub fn resume_offset(&self) -> Option<usize> {
+        self.tags
+            .iter()
+            .find_map(|t| t.strip_prefix("resume:"))
+            .and_then(|n| n.parse::<usize>().ok())
+    }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// splits a tag in the form `key=value` into key and value
+/// ```
+/// use timetracker::log_parser;
+/// assert_eq!(log_parser::as_metadata("customer=acme"), Some(("customer", "acme")));
+/// assert_eq!(log_parser::as_metadata("=shortname"), None);
+/// assert_eq!(log_parser::as_metadata("plain"), None);
+/// ```
+pub fn as_metadata(tag: &str) -> Option<(&str, &str)> {
+    match tag.find('=') {
+        Some(pos) if pos > 0 => Some((&tag[..pos], &tag[pos + 1..])),
+        _ => None,
+    }
+}
+
+// the words of a line together with their byte ranges in the line
+pub(crate) fn words_with_spans(line: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    line.split_whitespace().map(move |word| {
+        let start = word.as_ptr() as usize - line.as_ptr() as usize;
+        (start..start + word.len(), word)
+    })
+}
+
+/// splits off a trailing free-text note, which starts after the word `--`
+/// ```
+/// use timetracker::log_parser;
+/// assert_eq!(
+///     log_parser::split_note("10:15 JIRA-12 =api -- fixed pagination bug"),
+///     ("10:15 JIRA-12 =api ", Some("fixed pagination bug"))
+/// );
+/// assert_eq!(log_parser::split_note("10:15 JIRA-12 --"), ("10:15 JIRA-12 ", None));
+/// assert_eq!(log_parser::split_note("10:15 some--thing"), ("10:15 some--thing", None));
+/// ```
+pub fn split_note(line: &str) -> (&str, Option<&str>) {
+    let is_word_boundary = |c: Option<char>| c.is_none_or(char::is_whitespace);
+    for (pos, sep) in line.match_indices("--") {
+        let end = pos + sep.len();
+        if is_word_boundary(line[..pos].chars().last())
+            && is_word_boundary(line[end..].chars().next())
+        {
+            let note = line[end..].trim();
+            return (
+                &line[..pos],
+                if note.is_empty() { None } else { Some(note) },
+            );
+        }
+    }
+    (line, None)
+}
+
+/// separates the plain tags from the metadata given as `key=value`
+/// ```
+/// use timetracker::log_parser;
+/// let (tags, metadata) = log_parser::split_tags(vec!["=api", "billable=no", "urgent"]);
+/// assert_eq!(tags, vec!["=api", "urgent"]);
+/// assert_eq!(metadata.get("billable").map(String::as_str), Some("no"));
+/// ```
+pub fn split_tags<'a>(words: impl IntoIterator<Item = &'a str>) -> (Vec<String>, Metadata) {
+    let mut tags = Vec::new();
+    let mut metadata = Metadata::new();
+    for word in words {
+        match as_metadata(word) {
+            Some((key, value)) => {
+                metadata.insert(key.to_string(), value.to_string());
+            }
+            None => tags.push(word.to_string()),
+        }
+    }
+    (tags, metadata)
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub enum Block {
     NormalBlock(BlockData),
     ReallyBlock(BlockData),
     TimeCorrection(NaiveTime),
-    CommentBlock(()),
+    // an entry on a secondary track that runs next to the main activities, like `10:00 @oncall`.
+    // The activity of the data is the name of the track, or `break` when the track ends.
+    TrackBlock(String, BlockData),
+    // time for an activity that is not on the timeline, like `+1:30 JIRA-7 travel`.
+    // It is a manual adjustment, the start of the data is not used.
+    DurationBlock(Duration, BlockData),
+    // comment or empty line, keeps the text of the line
+    CommentBlock(String),
 }
 
 /// returns whether the time for this activity will be distributed to the other activities of the day
@@ -66,7 +185,7 @@ impl Block {
     /// Since it uses the passed line string to error messages, it needs to own the line.
     ///
     /// ```
-    /// use timetracker::log_parser::{Block, BlockData};
+    /// use timetracker::log_parser::{Block, BlockData, Metadata};
     /// use chrono::NaiveTime;
     /// use std::vec;
     /// assert_eq!(Block::from_line(Ok("10:13 started".to_string())).unwrap(),
@@ -74,69 +193,146 @@ impl Block {
     ///         start: NaiveTime::from_hms(10,13,0),
     ///         activity: "started".to_string(),
     ///         tags: vec![],
+    ///         metadata: Metadata::new(),
+    ///         note: None,
     ///         distribute: false,
+    ///         utc_offset: None,
+    ///         start_seconds: false,
     /// }));
     /// ```
     ///
     pub fn from_line(line: Result<String, std::io::Error>) -> Result<Block, TTError> {
         let line = line?;
-        let mut words = line.split_whitespace();
-        let mut next_word = match words.next() {
-            None => return Ok(Block::CommentBlock(())),
-            Some(comment) if comment.starts_with("#") => return Ok(Block::CommentBlock(())),
+        let (entry, note) = split_note(&line);
+        let parse_error = |msg: &'static str, span: Range<usize>, hint: &'static str| {
+            TTError::new(TTErrorKind::ParseError(msg, line.to_string()))
+                .with_span(&line, span)
+                .with_hint(hint)
+        };
+        // the position after the last word, for errors about missing words
+        let end = entry.trim_end().len();
+        let words: Vec<_> = words_with_spans(entry).collect();
+        let mut words = words.into_iter();
+        let (first_span, first_word) = match words.next() {
+            None => return Ok(Block::CommentBlock(line)),
+            Some((_, comment)) if comment.starts_with("#") => return Ok(Block::CommentBlock(line)),
             Some(word) => word,
         };
-        let mut start = utils::parse_time(next_word).map_err(|_| {
-            TTError::new(TTErrorKind::ParseError(
-                "cannot parse start time",
-                line.to_string(),
-            ))
-        })?;
-        next_word = words.next().ok_or_else(|| {
-            TTError::new(TTErrorKind::ParseError(
+        if is_conflict_marker(first_word) {
+            return Err(parse_error(
+                "merge conflict",
+                first_span,
+                "keep the right entries of both sides, then remove the lines with \
+                <<<<<<<, ======= and >>>>>>>",
+            ));
+        }
+        if let Ok(duration) = utils::parse_signed_duration(first_word) {
+            let (_, activity) = words.next().ok_or_else(|| {
+                parse_error(
+                    "manual duration does not contain an activity",
+                    end..end,
+                    "add the activity after the duration, like `+1:30 JIRA-12`",
+                )
+            })?;
+            let (tags, metadata) = split_tags(words.map(|(_, word)| word));
+            return Ok(Block::DurationBlock(
+                duration,
+                BlockData {
+                    start: NaiveTime::MIN,
+                    activity: utils::resolve_prefix_for_number(activity),
+                    tags,
+                    metadata,
+                    note: note.map(str::to_string),
+                    distribute: is_distributable(activity),
+                    utc_offset: None,
+                    start_seconds: false,
+                },
+            ));
+        }
+        let mut start_seconds = utils::has_seconds(first_word);
+        let (mut start, mut utc_offset) =
+            utils::parse_time_with_offset(first_word).map_err(|_| {
+                parse_error(
+                    "cannot parse start time",
+                    first_span,
+                    "a log line starts with the time as HH:MM or HH:MM:SS",
+                )
+            })?;
+        let (span, mut next_word) = words.next().ok_or_else(|| {
+            parse_error(
                 "Line does not contain at least 2 words",
-                line.to_string(),
-            ))
+                end..end,
+                "add the activity after the time, like `10:15 JIRA-12`",
+            )
         })?;
         if next_word.starts_with("#") {
-            return Ok(Block::CommentBlock(()));
+            return Ok(Block::CommentBlock(line));
         };
         let is_really_block = next_word == "really";
         if is_really_block {
-            next_word = words.next().ok_or_else(|| {
-                TTError::new(TTErrorKind::ParseError(
+            next_word = words.next().map(|(_, word)| word).ok_or_else(|| {
+                parse_error(
                     "really block does not contain an activity",
-                    line.to_string(),
-                ))
+                    span,
+                    "use `HH:MM really ACTIVITY` to correct the activity, \
+                    or `HH:MM really HH:MM` for time corrections",
+                )
             })?;
         }
-        let manual_time = utils::parse_time(next_word);
-        if let Ok(manual_time) = manual_time {
+        let manual_time = utils::parse_time_with_offset(next_word);
+        if let Ok((manual_time, manual_offset)) = manual_time {
             if is_really_block {
-                return if words.next().is_some() {
-                    Err(TTError::new(TTErrorKind::ParseError("time correction cannot have further data, i.e. use only <time> really <time>", line.to_string())))
-                } else {
-                    Ok(Block::TimeCorrection(manual_time))
+                return match words.next() {
+                    Some((further, _)) => Err(parse_error(
+                        "time correction cannot have further data, i.e. use only <time> really <time>",
+                        further.start..end,
+                        "use `HH:MM really HH:MM` for time corrections",
+                    )),
+                    None if note.is_some() => Err(parse_error(
+                        "time correction cannot have further data, i.e. use only <time> really <time>",
+                        end..line.trim_end().len(),
+                        "use `HH:MM really HH:MM` for time corrections",
+                    )),
+                    None => Ok(Block::TimeCorrection(manual_time)),
                 };
             }
             start = manual_time;
-            next_word = words.next().ok_or_else(|| {
-                TTError::new(TTErrorKind::ParseError(
+            utc_offset = manual_offset;
+            start_seconds = utils::has_seconds(next_word);
+            next_word = words.next().map(|(_, word)| word).ok_or_else(|| {
+                parse_error(
                     "really block does not contain an activity",
-                    line.to_string(),
-                ))
+                    end..end,
+                    "add the activity after the time, like `10:00 09:45 JIRA-12`",
+                )
             })?;
         }
 
+        let track = match next_word.strip_prefix('@') {
+            Some(track) if !track.is_empty() && !is_really_block => Some(track),
+            _ => None,
+        };
+        let mut words = words.map(|(_, word)| word).peekable();
+        if track.is_some() && words.peek().is_some_and(|word| is_break(word)) {
+            next_word = words.next().unwrap();
+        } else if let Some(track) = track {
+            next_word = track;
+        }
+        let (tags, metadata) = split_tags(words);
         let data = BlockData {
             start,
             activity: utils::resolve_prefix_for_number(&next_word),
-            tags: words.map(|s| s.to_string()).collect(),
+            tags,
+            metadata,
+            note: note.map(str::to_string),
             distribute: is_distributable(&next_word),
+            utc_offset,
+            start_seconds,
         };
-        match is_really_block {
-            true => Ok(Block::ReallyBlock(data)),
-            false => Ok(Block::NormalBlock(data)),
+        match (track, is_really_block) {
+            (Some(track), _) => Ok(Block::TrackBlock(track.to_string(), data)),
+            (None, true) => Ok(Block::ReallyBlock(data)),
+            (None, false) => Ok(Block::NormalBlock(data)),
         }
     }
 
@@ -149,17 +345,30 @@ impl Block {
     }
 
     /// Turns the block into a log file line (without trailing '\n')
+    ///
+    /// Times are written with seconds only if the seconds are not zero, or if the start was
+    /// written with seconds (`start_seconds`).
+    ///
+    /// The line is normalised: the plain tags come first, in their order, then the metadata
+    /// sorted by key. A line read with metadata between the tags is written in this order, so
+    /// lines are compared as parsed entries (see `tt merge`), never as text.
     /// ```
-    /// use timetracker::log_parser::{Block, BlockData};
+    /// use timetracker::log_parser::{Block, BlockData, Metadata};
     /// use chrono::NaiveTime;
+    /// let mut metadata = Metadata::new();
+    /// metadata.insert("customer".to_string(), "acme".to_string());
     /// let block = Block::NormalBlock(BlockData{
     ///         start: NaiveTime::from_hms(10,13,0),
     ///         activity: "started".to_string(),
     ///         tags: vec!["tag-one".to_string(),"tag-two".to_string()],
+    ///         metadata,
+    ///         note: Some("what I did".to_string()),
     ///         distribute: false,
+    ///         utc_offset: None,
+    ///         start_seconds: false,
     /// });
     /// let result = block.to_string(&NaiveTime::from_hms(10, 13, 0));
-    /// assert_eq!(result, "10:13 started tag-one tag-two");
+    /// assert_eq!(result, "10:13 started tag-one tag-two customer=acme -- what I did");
     /// ```
     pub fn to_string(&self, timestamp: &NaiveTime) -> String {
         let really = match self {
@@ -167,26 +376,56 @@ impl Block {
             _ => false,
         };
         let msg = match self {
-            Block::NormalBlock(data) | Block::ReallyBlock(data) => {
+            Block::NormalBlock(data)
+            | Block::ReallyBlock(data)
+            | Block::TrackBlock(_, data)
+            | Block::DurationBlock(_, data) => {
                 let BlockData {
                     start,
                     activity,
                     tags,
+                    metadata,
+                    note,
                     distribute: _distribute,
+                    utc_offset,
+                    start_seconds,
                 } = data;
                 let mut words: Vec<&str> = Vec::with_capacity(5 + tags.len());
+                let metadata_words: Vec<String> = metadata
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect();
 
-                let timestamp_str = utils::format_time(timestamp).to_string();
-                let start_str = utils::format_time(&start).to_string();
+                let start_str = utils::format_time_with_offset(start, *utc_offset, *start_seconds);
+                // the UTC offset belongs to the start, it is only written once
+                let timestamp_str = match (self, *start == *timestamp) {
+                    (Block::DurationBlock(duration, _), _) => {
+                        utils::format_signed_duration(duration)
+                    }
+                    (_, true) => start_str.clone(),
+                    (_, false) => utils::format_time(timestamp).to_string(),
+                };
                 words.push(&timestamp_str);
                 if really {
                     words.push("really")
                 };
-                if *start != *timestamp {
+                if *start != *timestamp && !matches!(self, Block::DurationBlock(_, _)) {
                     words.push(&start_str)
                 }
-                words.push(activity);
+                let track_word;
+                if let Block::TrackBlock(track, _) = self {
+                    track_word = format!("@{}", track);
+                    words.push(&track_word);
+                }
+                if !matches!(self, Block::TrackBlock(_, _)) || is_break(activity) {
+                    words.push(activity);
+                }
                 words.extend(tags.iter().map(|t| t as &str));
+                words.extend(metadata_words.iter().map(|t| t as &str));
+                if let Some(note) = note {
+                    words.push("--");
+                    words.push(note);
+                }
                 words.join(" ")
             }
             Block::TimeCorrection(manual_time) => format!(
@@ -194,12 +433,202 @@ impl Block {
                 timestamp = utils::format_time(timestamp),
                 manual_time = utils::format_time(manual_time)
             ),
-            Block::CommentBlock(()) => "".to_string(),
+            Block::CommentBlock(text) => text.to_string(),
         };
         msg.to_string()
     }
 }
 
+/// whether the word starts a line that `tt merge` has written around conflicting entries
+/// ```
+/// use timetracker::log_parser::is_conflict_marker;
+/// assert!(is_conflict_marker("<<<<<<<"));
+/// assert!(is_conflict_marker("======="));
+/// assert!(!is_conflict_marker("10:00"));
+/// ```
+pub fn is_conflict_marker(word: &str) -> bool {
+    ["<<<<<<<", "=======", ">>>>>>>"]
+        .iter()
+        .any(|marker| word.starts_with(marker))
+}
+
+/// A line of a log file together with the block parsed from it.
+///
+/// Lines read from a file keep their original text (including the line ending),
+/// so they are written back byte-identical unless they have been replaced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    /// line number in the file as read (starting with 1), `None` for inserted lines
+    pub line_number: Option<usize>,
+    /// the log time, i.e. the first timestamp of the line; `None` for comments and manual durations
+    pub timestamp: Option<NaiveTime>,
+    pub block: Block,
+    original: Option<String>,
+}
+
+impl LogLine {
+    /// a new line that is not (yet) part of a file
+    pub fn new(timestamp: NaiveTime, block: Block) -> LogLine {
+        let timestamp = match block {
+            Block::CommentBlock(_) | Block::DurationBlock(_, _) => None,
+            _ => Some(timestamp),
+        };
+        LogLine {
+            line_number: None,
+            timestamp,
+            block,
+            original: None,
+        }
+    }
+
+    /// the text of the line without line ending
+    pub fn text(&self) -> String {
+        match &self.original {
+            Some(original) => original.trim_end_matches(&['\r', '\n'][..]).to_string(),
+            // comments have no timestamp, but ignore it anyway
+            None => self
+                .block
+                .to_string(&self.timestamp.unwrap_or(NaiveTime::MIN)),
+        }
+    }
+
+    /// whether the line is unchanged since it has been read
+    pub fn is_original(&self) -> bool {
+        self.original.is_some()
+    }
+}
+
+/// A log file as a document: all its lines, including comments and empty lines.
+///
+/// It can be read, edited and written back without touching the lines that
+/// have not been edited.
+///
+/// ```
+/// use timetracker::log_parser::{Block, LogFile, LogLine};
+/// use chrono::NaiveTime;
+/// let content = "# my day\n08:00 email\n\n10:00   break\n";
+/// let mut logfile = LogFile::read(content.as_bytes()).unwrap();
+/// assert_eq!(logfile.to_string(), content);
+///
+/// let block = Block::from_line(Ok("09:00 JIRA-12".to_string())).unwrap();
+/// logfile.insert(2, LogLine::new(NaiveTime::from_hms_opt(9, 0, 0).unwrap(), block));
+/// logfile.remove(0);
+/// assert_eq!(logfile.to_string(), "08:00 email\n09:00 JIRA-12\n\n10:00   break\n");
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LogFile {
+    lines: Vec<LogLine>,
+}
+
+impl LogFile {
+    pub fn new() -> LogFile {
+        LogFile { lines: Vec::new() }
+    }
+
+    /// reads and parses a log file, an error names the number of the line
+    pub fn read(mut reader: impl BufRead) -> Result<LogFile, TTError> {
+        let mut lines = Vec::new();
+        loop {
+            let mut original = String::new();
+            if reader.read_line(&mut original)? == 0 {
+                break;
+            }
+            let line_number = lines.len() + 1;
+            let text = original.trim_end_matches(&['\r', '\n'][..]).to_string();
+            let block =
+                Block::from_line(Ok(text.clone())).map_err(|err| err.at_line(line_number))?;
+            let timestamp = match block {
+                Block::CommentBlock(_) | Block::DurationBlock(_, _) => None,
+                _ => text
+                    .split_whitespace()
+                    .next()
+                    .and_then(|word| utils::parse_time_with_offset(word).ok())
+                    .map(|(time, _utc_offset)| time),
+            };
+            lines.push(LogLine {
+                line_number: Some(line_number),
+                timestamp,
+                block,
+                original: Some(original),
+            });
+        }
+        Ok(LogFile { lines })
+    }
+
+    pub fn lines(&self) -> &[LogLine] {
+        &self.lines
+    }
+
+    /// the lines with log entries, i.e. without comments and empty lines, with their index
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &LogLine)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line.block, Block::CommentBlock(_)))
+    }
+
+    /// the blocks of all lines, e.g. for the BlockCollector
+    pub fn blocks(&self) -> impl Iterator<Item = &Block> {
+        self.lines.iter().map(|line| &line.block)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// inserts a line before the line at index
+    pub fn insert(&mut self, index: usize, line: LogLine) {
+        self.lines.insert(index, line);
+    }
+
+    /// appends a line at the end
+    pub fn push(&mut self, line: LogLine) {
+        self.lines.push(line);
+    }
+
+    /// replaces the line at index, the new line keeps the line number
+    pub fn replace(&mut self, index: usize, mut line: LogLine) -> LogLine {
+        line.line_number = self.lines[index].line_number;
+        line.original = None;
+        std::mem::replace(&mut self.lines[index], line)
+    }
+
+    pub fn remove(&mut self, index: usize) -> LogLine {
+        self.lines.remove(index)
+    }
+
+    /// writes the file; untouched lines are written as they were read
+    pub fn write(&self, mut writer: impl Write) -> std::io::Result<()> {
+        write!(writer, "{}", self)
+    }
+}
+
+impl std::fmt::Display for LogFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut needs_newline = false;
+        for line in self.lines.iter() {
+            if needs_newline {
+                writeln!(f)?;
+            }
+            match &line.original {
+                Some(original) => {
+                    write!(f, "{}", original)?;
+                    needs_newline = !original.ends_with('\n');
+                }
+                None => {
+                    writeln!(f, "{}", line.text())?;
+                    needs_newline = false;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct TTInfo {
     pub activity: String,
@@ -221,14 +650,11 @@ impl TTInfo {
                 .reader()
                 .ok()
                 .and_then(|file| read_activities(file).ok());
+            // an alias is shown with the preferred shortname
             match activity_map.as_ref().and_then(|map| map.get(name)) {
-                Some((activity, tags)) if activity == name => TTInfo {
-                    activity: activity.to_string(),
-                    shortname: tags.first().map(|s| s.to_string()),
-                },
-                Some((activity, _tags)) => TTInfo {
-                    activity: activity.to_string(),
-                    shortname: Some(name.to_string()),
+                Some(activity) => TTInfo {
+                    activity: activity.name.to_string(),
+                    shortname: activity.shortname.clone(),
                 },
                 None => TTInfo {
                     activity: name.to_string(),
@@ -1,18 +1,37 @@
 use std::vec::Vec;
 use std::{env, process};
-use timetracker::utils::{FileProxy, NamedFile};
-use timetracker::{get_activities_file_name, get_configfile_name, get_logfile_name};
+use timetracker::configfile::TTConfig;
+use timetracker::datadir::{self, Locations};
+use timetracker::error::TTError;
+use timetracker::utils::{self, FileProxy, NamedFile};
+use timetracker::{get_activities_file_name, get_journal_file_name, get_logfile_name};
 
-fn main() {
+fn run(mut args: Vec<String>) -> Result<i32, TTError> {
     let now = chrono::Local::now();
-    let args: Vec<String> = env::args().collect();
-    let result = timetracker::run(
+    let locations = Locations::resolve(datadir::take_data_dir_arg(&mut args)?)?;
+    let data_dir = locations.data_dir.as_path();
+    let configfile = NamedFile::new(locations.configfile.clone());
+    // the day change hour of the config decides on the logfile of the current workday.
+    // Errors in the config file are reported by run().
+    let workday = configfile
+        .reader()
+        .map_err(TTError::from)
+        .and_then(TTConfig::init)
+        .map(|_| utils::workday(&now.naive_local()))
+        .unwrap_or_else(|_| now.date_naive());
+    timetracker::run(
         &args,
         &now,
-        &NamedFile::new(get_configfile_name()),
-        &NamedFile::new(get_logfile_name(&now.date().naive_local())),
-        &NamedFile::new(get_activities_file_name()),
-    );
+        data_dir,
+        &configfile,
+        &NamedFile::new(get_logfile_name(data_dir, &workday)),
+        &NamedFile::new(get_activities_file_name(data_dir)),
+        &NamedFile::new(get_journal_file_name(data_dir)),
+    )
+}
+
+fn main() {
+    let result = run(env::args().collect());
     let retval = match result {
         Ok(n) => n,
         Err(e) => {
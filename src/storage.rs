@@ -0,0 +1,503 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::result::Result;
+use std::str::FromStr;
+
+use chrono::{Duration, NaiveDate};
+
+use crate::archive::{archive_name, read_archive, write_archive, ArchivedDays};
+use crate::error::TTError;
+use crate::journal::Journal;
+use crate::subcommands::migrate::find_logfiles;
+use crate::utils::{FileLock, FileProxy};
+
+/// the log lines of one day, without line endings
+#[derive(Debug, Clone, PartialEq)]
+pub struct DayLog {
+    pub date: NaiveDate,
+    pub lines: Vec<String>,
+}
+
+impl DayLog {
+    /// the lines like BufRead::lines returns them, for the collector
+    pub fn iter_lines(&self) -> impl Iterator<Item = io::Result<String>> + '_ {
+        self.lines.iter().cloned().map(Ok)
+    }
+}
+
+/// where the log entries are kept. The commands only see the lines of a day, how the days are
+/// stored is up to the implementation.
+pub trait Storage<R: BufRead, W: Write, F: FileProxy<R, W>> {
+    /// the logs of the days from `from` to `to`, both included, in order of the dates.
+    /// Days without entries have no lines.
+    fn load(&self, from: &NaiveDate, to: &NaiveDate) -> Result<Vec<DayLog>, TTError>;
+
+    /// appends an entry to the log of the day and records it in the journal
+    fn append(&self, date: &NaiveDate, line: &str, journal: &Journal<F>) -> Result<(), TTError>;
+
+    /// replaces the log of the day with the given lines at once, under the lock of the journal
+    /// that the caller holds. It is not recorded in the journal, see rewrite_journaled.
+    fn rewrite(&self, date: &NaiveDate, lines: &[String], lock: &FileLock) -> Result<(), TTError>;
+
+    /// replaces the log of the day like rewrite and records the lines of the day before and
+    /// after in the journal, for undo. Nothing is written if the lines stay the same.
+    fn rewrite_journaled(
+        &self,
+        date: &NaiveDate,
+        lines: &[String],
+        journal: &Journal<F>,
+        lock: &FileLock,
+    ) -> Result<(), TTError> {
+        let before = self.load_day(date)?.lines;
+        if before == lines {
+            return Ok(());
+        }
+        self.rewrite(date, lines, lock)?;
+        journal.record_rewrite(&self.path(date), Some(*date), before, lines)
+    }
+
+    /// the file that holds the log of the day, for messages and for the editor
+    fn path(&self, date: &NaiveDate) -> PathBuf;
+
+    /// the first day with entries, None if the log is empty
+    fn first_date(&self) -> Result<Option<NaiveDate>, TTError>;
+
+    /// the log of one day
+    fn load_day(&self, date: &NaiveDate) -> Result<DayLog, TTError> {
+        Ok(self.load(date, date)?.pop().unwrap_or(DayLog {
+            date: *date,
+            lines: vec![],
+        }))
+    }
+}
+
+fn read_lines<R: BufRead>(reader: R) -> Result<Vec<String>, TTError> {
+    Ok(reader.lines().collect::<io::Result<Vec<String>>>()?)
+}
+
+fn replace_lines<R: BufRead, W: Write>(
+    file: &impl FileProxy<R, W>,
+    lines: &[String],
+) -> Result<(), TTError> {
+    let content: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    Ok(file.replace_content(content.as_bytes())?)
+}
+
+/// how the daily files are arranged in the data directory (layout in the config file)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// all files in the data directory, like 2020-06-01
+    Flat,
+    /// a directory per year and month, like 2020/06/2020-06-01
+    Nested,
+}
+
+impl Layout {
+    /// the log file of the day in the directory
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use chrono::NaiveDate;
+    /// use timetracker::storage::Layout;
+    /// let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+    /// assert_eq!(Layout::Flat.logfile_name(Path::new("tt"), &date), PathBuf::from("tt/2020-06-01"));
+    /// assert_eq!(Layout::Nested.logfile_name(Path::new("tt"), &date), PathBuf::from("tt/2020/06/2020-06-01"));
+    /// ```
+    pub fn logfile_name(&self, dir: &Path, date: &NaiveDate) -> PathBuf {
+        let name = date.format("%F").to_string();
+        match self {
+            Layout::Flat => dir.join(name),
+            Layout::Nested => dir
+                .join(date.format("%Y").to_string())
+                .join(date.format("%m").to_string())
+                .join(name),
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(Layout::Flat),
+            "nested" => Ok(Layout::Nested),
+            _ => Err(format!("layout must be flat or nested, but is {}", s)),
+        }
+    }
+}
+
+/// one file per day in a directory, named after the date like 2020-06-01, in the given layout.
+/// Days that have been archived by `tt archive` are read from the archive of their month, and
+/// entries that have been added to the day after it has been archived follow them.
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use chrono::{Local, NaiveDate};
+/// use timetracker::journal::Journal;
+/// use timetracker::storage::{DailyFiles, Layout, Storage};
+/// use timetracker::utils::{FakeFile, FileProxy};
+/// let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+/// let logfile = FakeFile::new(PathBuf::from("/home/user/.tt/2020-06-01"));
+/// let journalfile = FakeFile::new(PathBuf::from("journal"));
+/// let storage = DailyFiles::new(PathBuf::from("/home/user/.tt"), Layout::Flat).with_file(date, &logfile);
+/// storage.append(&date, "08:00 email", &Journal::new(&journalfile, &Local::now(), "add")).unwrap();
+/// assert_eq!(storage.load_day(&date).unwrap().lines, vec!["08:00 email"]);
+/// assert_eq!(storage.path(&date.succ_opt().unwrap()), PathBuf::from("/home/user/.tt/2020-06-02"));
+/// ```
+pub struct DailyFiles<'a, F> {
+    dir: PathBuf,
+    layout: Layout,
+    known: Option<(NaiveDate, &'a F)>,
+}
+
+impl<'a, F> DailyFiles<'a, F> {
+    pub fn new(dir: PathBuf, layout: Layout) -> DailyFiles<'a, F> {
+        DailyFiles {
+            dir,
+            layout,
+            known: None,
+        }
+    }
+
+    /// use this file for the log of the date instead of opening it by its name
+    pub fn with_file(self, date: NaiveDate, file: &'a F) -> DailyFiles<'a, F> {
+        DailyFiles {
+            known: Some((date, file)),
+            ..self
+        }
+    }
+
+    fn with_day_file<R: BufRead, W: Write, T>(
+        &self,
+        date: &NaiveDate,
+        action: impl FnOnce(&F) -> T,
+    ) -> T
+    where
+        F: FileProxy<R, W>,
+    {
+        match self.known {
+            Some((known_date, file)) if known_date == *date => action(file),
+            _ => action(&F::new(self.layout.logfile_name(&self.dir, date))),
+        }
+    }
+}
+
+impl<'a, R: BufRead, W: Write, F: FileProxy<R, W>> Storage<R, W, F> for DailyFiles<'a, F> {
+    fn load(&self, from: &NaiveDate, to: &NaiveDate) -> Result<Vec<DayLog>, TTError> {
+        let mut days = Vec::new();
+        let mut archives: HashMap<PathBuf, ArchivedDays> = HashMap::new();
+        let mut date = *from;
+        while date <= *to {
+            let archive = archive_name(&self.dir, &date);
+            if !archives.contains_key(&archive) {
+                let archived =
+                    read_archive(&F::new(archive.clone())).map_err(|err| err.in_file(&archive))?;
+                archives.insert(archive.clone(), archived);
+            }
+            let mut lines = archives[&archive].get(&date).cloned().unwrap_or_default();
+            lines.extend(self.with_day_file(&date, |file| read_lines(file.reader()?))?);
+            days.push(DayLog { date, lines });
+            date += Duration::days(1);
+        }
+        Ok(days)
+    }
+
+    fn append(&self, date: &NaiveDate, line: &str, journal: &Journal<F>) -> Result<(), TTError> {
+        self.with_day_file(date, |file| journal.append_line(file, line))
+    }
+
+    // an archived day moves back to its daily file
+    fn rewrite(&self, date: &NaiveDate, lines: &[String], _lock: &FileLock) -> Result<(), TTError> {
+        let archive = F::new(archive_name(&self.dir, date));
+        let mut archived = read_archive(&archive)?;
+        if archived.remove(date).is_some() {
+            write_archive(&archive, &archived)?;
+        }
+        self.with_day_file(date, |file| replace_lines(file, lines))
+    }
+
+    fn path(&self, date: &NaiveDate) -> PathBuf {
+        self.with_day_file(date, |file| file.pathname().to_path_buf())
+    }
+
+    // the first day of the oldest archive that has days, of the daily files and of the known file
+    fn first_date(&self) -> Result<Option<NaiveDate>, TTError> {
+        let mut first = Vec::new();
+        let mut archives: Vec<PathBuf> = match fs::read_dir(self.dir.join("archive")) {
+            Ok(entries) => entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "gz"))
+                .collect(),
+            Err(_) => vec![],
+        };
+        archives.sort();
+        for archive in archives {
+            let archived =
+                read_archive(&F::new(archive.clone())).map_err(|err| err.in_file(&archive))?;
+            if let Some(date) = archived.keys().next() {
+                first.push(*date);
+                break;
+            }
+        }
+        if self.dir.is_dir() {
+            first.extend(find_logfiles(&self.dir)?.first().map(|(date, _)| *date));
+        }
+        if let Some((date, file)) = self.known {
+            if !read_lines(file.reader()?)?.is_empty() {
+                first.push(date);
+            }
+        }
+        Ok(first.into_iter().min())
+    }
+}
+
+/// all days in one file that entries are appended to, every line starts with its date like
+/// `2020-06-01 08:00 email`. Lines that do not start with a date are ignored. Rewriting a day,
+/// like `tt merge` does, replaces the whole file at once, but only the lines of the day change.
+/// Line numbers in messages count the lines of the day, not of the file.
+///
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use chrono::{Local, NaiveDate};
+/// use timetracker::journal::Journal;
+/// use timetracker::storage::{SingleFile, Storage};
+/// use timetracker::utils::{FakeFile, FileProxy};
+/// let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+/// let journalfile = FakeFile::new(PathBuf::from("journal"));
+/// let journal = Journal::new(&journalfile, &Local::now(), "add");
+/// let storage = SingleFile::new(FakeFile::new(PathBuf::from("log")));
+/// storage.append(&date, "08:00 email", &journal).unwrap();
+/// storage.append(&date.succ_opt().unwrap(), "09:00 meeting", &journal).unwrap();
+/// let days = storage.load(&date, &date.succ_opt().unwrap()).unwrap();
+/// assert_eq!(days[0].lines, vec!["08:00 email"]);
+/// assert_eq!(days[1].lines, vec!["09:00 meeting"]);
+/// ```
+pub struct SingleFile<F> {
+    file: F,
+}
+
+impl<F> SingleFile<F> {
+    pub fn new(file: F) -> SingleFile<F> {
+        SingleFile { file }
+    }
+}
+
+/// the line in the single file, prefixed with its date
+pub(crate) fn dated_line(date: &NaiveDate, line: &str) -> String {
+    match line.is_empty() {
+        true => date.format("%F").to_string(),
+        false => format!("{} {}", date.format("%F"), line),
+    }
+}
+
+/// splits a line of the single file into its date and the log line
+pub(crate) fn split_dated_line(line: &str) -> Option<(NaiveDate, &str)> {
+    let date = NaiveDate::parse_from_str(line.get(..10)?, "%F").ok()?;
+    match &line[10..] {
+        "" => Some((date, "")),
+        rest => Some((date, rest.strip_prefix(' ')?)),
+    }
+}
+
+impl<R: BufRead, W: Write, F: FileProxy<R, W>> Storage<R, W, F> for SingleFile<F> {
+    fn load(&self, from: &NaiveDate, to: &NaiveDate) -> Result<Vec<DayLog>, TTError> {
+        let mut days: Vec<DayLog> = Vec::new();
+        let mut date = *from;
+        while date <= *to {
+            days.push(DayLog {
+                date,
+                lines: vec![],
+            });
+            date += Duration::days(1);
+        }
+        for line in read_lines(self.file.reader()?)? {
+            if let Some((date, line)) = split_dated_line(&line) {
+                if *from <= date && date <= *to {
+                    days[(date - *from).num_days() as usize]
+                        .lines
+                        .push(line.to_string());
+                }
+            }
+        }
+        Ok(days)
+    }
+
+    fn append(&self, date: &NaiveDate, line: &str, journal: &Journal<F>) -> Result<(), TTError> {
+        journal.append_line(&self.file, &dated_line(date, line))
+    }
+
+    /// the new lines of the day take the place of its first old line, or go to the end. The
+    /// lines of the other days stay as they are, the file is replaced as a whole.
+    fn rewrite(&self, date: &NaiveDate, lines: &[String], _lock: &FileLock) -> Result<(), TTError> {
+        let mut result: Vec<String> = Vec::new();
+        let mut replacement = Some(lines.iter().map(|line| dated_line(date, line)));
+        for line in read_lines(self.file.reader()?)? {
+            match split_dated_line(&line) {
+                Some((line_date, _)) if line_date == *date => {
+                    result.extend(replacement.take().into_iter().flatten())
+                }
+                _ => result.push(line),
+            }
+        }
+        result.extend(replacement.into_iter().flatten());
+        replace_lines(&self.file, &result)
+    }
+
+    fn path(&self, _date: &NaiveDate) -> PathBuf {
+        self.file.pathname().to_path_buf()
+    }
+
+    fn first_date(&self) -> Result<Option<NaiveDate>, TTError> {
+        Ok(read_lines(self.file.reader()?)?
+            .iter()
+            .filter_map(|line| split_dated_line(line).map(|(date, _)| date))
+            .min())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::{read_operations, Change, Rewrite};
+    use crate::utils::FakeFile;
+    use chrono::{Local, TimeZone};
+
+    #[test]
+    fn daily_files_use_the_known_file() {
+        let date = NaiveDate::from_ymd_opt(2020, 6, 2).unwrap();
+        let logfile = FakeFile::with_content(PathBuf::from("logfile"), b"08:00 email\n");
+        let storage =
+            DailyFiles::new(PathBuf::from("/tmp/tt"), Layout::Nested).with_file(date, &logfile);
+        let days = storage.load(&date.pred_opt().unwrap(), &date).unwrap();
+        assert_eq!(days[0].lines, Vec::<String>::new());
+        assert_eq!(days[1].lines, vec!["08:00 email"]);
+        assert_eq!(
+            storage.path(&date.pred_opt().unwrap()),
+            PathBuf::from("/tmp/tt/2020/06/2020-06-01")
+        );
+        assert_eq!(storage.path(&date), PathBuf::from("logfile"));
+
+        let lock = logfile.lock().unwrap();
+        storage
+            .rewrite(
+                &date,
+                &["08:00 email".to_string(), "09:00 break".to_string()],
+                &lock,
+            )
+            .unwrap();
+        assert_eq!(logfile.close().unwrap(), "08:00 email\n09:00 break\n");
+    }
+
+    #[test]
+    fn single_file_by_date() {
+        let logfile = FakeFile::with_content(
+            PathBuf::from("log"),
+            b"# all days in one file\n\
+            2020-06-01 08:00 email\n\
+            2020-06-01\n\
+            2020-06-02 09:00 meeting\n\
+            2020-06-01 10:00 break\n",
+        );
+        let storage = SingleFile::new(logfile);
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let days = storage.load(&date.pred_opt().unwrap(), &date).unwrap();
+        assert_eq!(days.len(), 2);
+        assert!(days[0].lines.is_empty());
+        assert_eq!(days[1].lines, vec!["08:00 email", "", "10:00 break"]);
+        assert_eq!(
+            storage.load_day(&date.succ_opt().unwrap()).unwrap().lines,
+            vec!["09:00 meeting"]
+        );
+        assert_eq!(storage.first_date().unwrap(), Some(date));
+    }
+
+    #[test]
+    fn single_file_rewrite() {
+        let logfile = FakeFile::with_content(
+            PathBuf::from("log"),
+            b"2020-06-01 08:00 email\n\
+            2020-06-02 09:00 meeting\n\
+            2020-06-01 10:00 break\n",
+        );
+        let journalfile = FakeFile::new(PathBuf::from("journal"));
+        let lock = journalfile.lock().unwrap();
+        let storage = SingleFile::new(logfile);
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        storage
+            .rewrite(
+                &date,
+                &["08:30 email".to_string(), "10:00 break".to_string()],
+                &lock,
+            )
+            .unwrap();
+        storage
+            .rewrite(
+                &date.pred_opt().unwrap(),
+                &["23:00 break".to_string()],
+                &lock,
+            )
+            .unwrap();
+        let journal = Journal::new(
+            &journalfile,
+            &Local.with_ymd_and_hms(2020, 6, 2, 9, 0, 0).unwrap(),
+            "add",
+        );
+        storage
+            .append(&date.succ_opt().unwrap(), "11:00 break", &journal)
+            .unwrap();
+        assert_eq!(
+            storage.file.close().unwrap(),
+            "2020-06-01 08:30 email\n\
+            2020-06-01 10:00 break\n\
+            2020-06-02 09:00 meeting\n\
+            2020-05-31 23:00 break\n\
+            2020-06-02 11:00 break\n"
+        );
+    }
+
+    #[test]
+    fn single_file_rewrite_journaled() {
+        let logfile = FakeFile::with_content(
+            PathBuf::from("log"),
+            b"2020-06-01 08:00 email\n\
+            2020-06-02 09:00 meeting\n",
+        );
+        let journalfile = FakeFile::new(PathBuf::from("journal"));
+        let journal = Journal::new(
+            &journalfile,
+            &Local.with_ymd_and_hms(2020, 6, 2, 9, 0, 0).unwrap(),
+            "merge",
+        );
+        let lock = journal.lock().unwrap();
+        let storage = SingleFile::new(logfile);
+        let date = NaiveDate::from_ymd_opt(2020, 6, 2).unwrap();
+        let lines = ["09:00 meeting".to_string(), "10:00 break".to_string()];
+        storage
+            .rewrite_journaled(&date, &lines, &journal, &lock)
+            .unwrap();
+        // the same lines again change nothing
+        storage
+            .rewrite_journaled(&date, &lines, &journal, &lock)
+            .unwrap();
+        let operations = read_operations(journalfile.reader().unwrap()).unwrap();
+        assert_eq!(
+            operations[0].changes,
+            vec![(
+                PathBuf::from("log"),
+                Change::Rewrite(Rewrite {
+                    date: Some(date),
+                    before: vec!["09:00 meeting".to_string()],
+                    after: lines.to_vec(),
+                })
+            )]
+        );
+        assert_eq!(
+            storage.file.close().unwrap(),
+            "2020-06-01 08:00 email\n\
+            2020-06-02 09:00 meeting\n\
+            2020-06-02 10:00 break\n"
+        );
+    }
+}
@@ -1,8 +1,17 @@
+pub mod activities;
 pub mod add;
+pub mod archive;
+pub mod check;
 pub mod edit;
+pub mod export;
+pub mod import;
+pub mod import_ics;
 pub mod interactive;
 pub mod is_active;
 pub mod list;
+pub mod merge;
+pub mod migrate;
 pub mod report;
 pub mod resume;
+pub mod undo;
 pub mod watch_i3;
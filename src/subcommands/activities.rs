@@ -0,0 +1,395 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::result::Result;
+
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use structopt::StructOpt;
+
+use crate::activities::{self, joined, read_activity_list, to_toml, with_archived, FileFormat};
+use crate::configfile::TTConfig;
+use crate::error::{TTError, TTErrorKind};
+use crate::journal::Journal;
+use crate::log_parser::Block;
+use crate::storage::Storage;
+use crate::utils::{self, FileProxy};
+
+#[derive(StructOpt, Debug)]
+pub enum ActivitiesOpt {
+    /// convert the activities file from lines to TOML, which can hold a description, customer,
+    /// billable flag, rate and estimate for every activity. The old file is kept with the
+    /// suffix .old
+    Convert,
+
+    /// archive the activities that have not been logged for some time, they are not shown by
+    /// tt list and the interactive menu anymore. With archive-activities-after in the config,
+    /// tt add does this, too, once a day
+    Prune(PruneOpt),
+
+    /// show archived activities in tt list and the interactive menu again. They are not
+    /// archived again before the days of archive-activities-after have passed
+    Restore {
+        /// the names or shortnames of the activities
+        activities: Vec<String>,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub struct PruneOpt {
+    #[structopt(long)]
+    /// archive the activities without log entries since this day, default is the number of days
+    /// of archive-activities-after in the config
+    pub unused_since: Option<NaiveDate>,
+
+    #[structopt(short = "n", long)]
+    /// only print the activities that would be archived
+    pub dry_run: bool,
+}
+
+// interface for the runner, changes the activities file
+pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    opt: ActivitiesOpt,
+    now: &DateTime<Local>,
+    storage: &dyn Storage<R, W, F>,
+    activitiesfile: &F,
+    journalfile: &F,
+) -> Result<i32, TTError> {
+    let journal = Journal::new(journalfile, now, "activities");
+    let _lock = journal.lock()?;
+    let today = utils::workday(&now.naive_local());
+    match opt {
+        ActivitiesOpt::Convert => convert(activitiesfile, &journal),
+        ActivitiesOpt::Prune(prune_opt) => prune(prune_opt, now, storage, activitiesfile, &journal),
+        ActivitiesOpt::Restore { activities } => {
+            restore(&activities, &today, activitiesfile, &journal)
+        }
+    }
+}
+
+// converts the activities file to TOML
+fn convert<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    activitiesfile: &F,
+    journal: &Journal<F>,
+) -> Result<i32, TTError> {
+    let path = activitiesfile.pathname();
+    let mut old = Vec::new();
+    activitiesfile.reader()?.read_to_end(&mut old)?;
+    let (format, activities) = read_activity_list(&old[..]).map_err(|err| err.in_file(path))?;
+    if format == FileFormat::Toml {
+        println!("{} is already in TOML", path.display());
+        return Ok(0);
+    }
+    let activities = joined(&activities);
+    let mut backup_name = path.file_name().unwrap_or_default().to_os_string();
+    backup_name.push(".old");
+    let backup = F::new(path.with_file_name(backup_name));
+    backup.replace_content(&old)?;
+    let content = format!("# activities of tt\n{}", to_toml(&activities));
+    let lines: Vec<String> = content.lines().map(str::to_string).collect();
+    journal.rewrite_file(activitiesfile, &lines)?;
+    println!(
+        "converted {} activities to TOML, the old file is {}",
+        activities.len(),
+        backup.pathname().display()
+    );
+    Ok(0)
+}
+
+// archives the activities that have not been used since the day
+fn prune<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    opt: PruneOpt,
+    now: &DateTime<Local>,
+    storage: &dyn Storage<R, W, F>,
+    activitiesfile: &F,
+    journal: &Journal<F>,
+) -> Result<i32, TTError> {
+    let today = utils::workday(&now.naive_local());
+    let since = match (opt.unused_since, TTConfig::get().archive_activities_after) {
+        (Some(since), _) => since,
+        (None, Some(days)) => today - Duration::days(days.into()),
+        (None, None) => {
+            return Err(TTError::new(TTErrorKind::UsageError(
+                "give the day with --unused-since or configure archive-activities-after",
+            )))
+        }
+    };
+    let archived = archive_unused(
+        &since,
+        &today,
+        storage,
+        activitiesfile,
+        journal,
+        opt.dry_run,
+    )?;
+    match (archived.is_empty(), opt.dry_run) {
+        (true, _) => println!("all activities have been used since {}", since),
+        (false, true) => println!("would archive: {}", archived.join(", ")),
+        (false, false) => println!("archived: {}", archived.join(", ")),
+    }
+    Ok(0)
+}
+
+/// archives the activities that have not been logged for the days of archive-activities-after,
+/// if it is configured. `tt add` runs it after adding its entry, with the lock of the journal,
+/// so that `tt undo` takes the archiving back together with the entry.
+///
+/// It looks at the log only once a day, the pruned file keeps the day it last did. Nothing is
+/// archived while the log does not reach back to the first of the days, since with a shorter
+/// history every activity would look unused.
+pub fn archive_automatically<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    now: &DateTime<Local>,
+    storage: &dyn Storage<R, W, F>,
+    activitiesfile: &F,
+    prunedfile: &F,
+    journal: &Journal<F>,
+) -> Result<(), TTError> {
+    let days = match TTConfig::get().archive_activities_after {
+        Some(days) => days,
+        None => return Ok(()),
+    };
+    let today = utils::workday(&now.naive_local());
+    let mut pruned = String::new();
+    prunedfile.reader()?.read_to_string(&mut pruned)?;
+    if pruned.trim() == today.format("%F").to_string() {
+        return Ok(());
+    }
+    prunedfile.replace_content(format!("{}\n", today.format("%F")).as_bytes())?;
+    let since = today - Duration::days(days.into());
+    match storage.first_date()? {
+        Some(first) if first <= since => (),
+        _ => return Ok(()),
+    }
+    let archived = archive_unused(&since, &today, storage, activitiesfile, journal, false)?;
+    if !archived.is_empty() {
+        println!(
+            "archived the activities not logged for {} days: {}",
+            days,
+            archived.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// archives the activities that have no log entries from the day since until today, and returns
+/// their names. Entries on a track count, too, and activities restored since the day are kept.
+/// The caller holds the lock of the journal.
+pub fn archive_unused<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    since: &NaiveDate,
+    today: &NaiveDate,
+    storage: &dyn Storage<R, W, F>,
+    activitiesfile: &F,
+    journal: &Journal<F>,
+    dry_run: bool,
+) -> Result<Vec<String>, TTError> {
+    let path = activitiesfile.pathname();
+    let mut text = String::new();
+    activitiesfile.reader()?.read_to_string(&mut text)?;
+    let (_, list) = read_activity_list(text.as_bytes()).map_err(|err| err.in_file(path))?;
+    let mut used = HashSet::new();
+    for day in storage.load(since, today)? {
+        for line in day.iter_lines() {
+            if let Ok(
+                Block::NormalBlock(data)
+                | Block::ReallyBlock(data)
+                | Block::DurationBlock(_, data)
+                | Block::TrackBlock(_, data),
+            ) = Block::from_line(line)
+            {
+                used.insert(data.activity);
+            }
+        }
+    }
+    let unused = activities::unused(&list, &used, since);
+    if !unused.is_empty() && !dry_run {
+        let content =
+            with_archived(&text, &unused, true, today).map_err(|err| err.in_file(path))?;
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        journal.rewrite_file(activitiesfile, &lines)?;
+    }
+    Ok(unused)
+}
+
+// takes the activities out of the archive, the day is kept as the day they were restored
+fn restore<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    names: &[String],
+    today: &NaiveDate,
+    activitiesfile: &F,
+    journal: &Journal<F>,
+) -> Result<i32, TTError> {
+    let path = activitiesfile.pathname();
+    let mut text = String::new();
+    activitiesfile.reader()?.read_to_string(&mut text)?;
+    let activity_map =
+        activities::read_activities(text.as_bytes()).map_err(|err| err.in_file(path))?;
+    let mut restored = Vec::new();
+    for name in names {
+        match activity_map.get(name) {
+            Some(activity) if activity.archived => restored.push(activity.name.to_string()),
+            Some(activity) => println!("{} is not archived", activity.name),
+            None => {
+                return Err(TTError::new(TTErrorKind::UsageError("activity not known"))
+                    .context(format!("restoring activity {:?}", name)))
+            }
+        }
+    }
+    if !restored.is_empty() {
+        let content =
+            with_archived(&text, &restored, false, today).map_err(|err| err.in_file(path))?;
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        journal.rewrite_file(activitiesfile, &lines)?;
+        println!("restored: {}", restored.join(", "));
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::activities::read_activities;
+    use crate::journal::{self, Change, Journal};
+    use crate::storage::SingleFile;
+    use crate::utils::FakeFile;
+
+    #[test]
+    fn convert_lines() {
+        let activitiesfile = FakeFile::new(PathBuf::from("activities"));
+        write!(
+            activitiesfile.writer().unwrap(),
+            "mail email customer=acme\nmeeting\n"
+        )
+        .unwrap();
+        let journalfile = FakeFile::new(PathBuf::from("journal"));
+        let journal = Journal::new(&journalfile, &Local::now(), "activities");
+        let before = read_activities(activitiesfile.reader().unwrap()).unwrap();
+        assert_eq!(convert(&activitiesfile, &journal).unwrap(), 0);
+        let (format, _) = read_activity_list(activitiesfile.reader().unwrap()).unwrap();
+        assert_eq!(format, FileFormat::Toml);
+        assert_eq!(
+            read_activities(activitiesfile.reader().unwrap()).unwrap(),
+            before
+        );
+        // converting again changes nothing
+        assert_eq!(convert(&activitiesfile, &journal).unwrap(), 0);
+        assert_eq!(
+            read_activities(activitiesfile.reader().unwrap()).unwrap(),
+            before
+        );
+        // the conversion is journaled, so that tt undo puts back the lines
+        let operations = journal::read_operations(journalfile.reader().unwrap()).unwrap();
+        assert_eq!(operations.len(), 1);
+        match &operations[0].changes[..] {
+            [(path, Change::Rewrite(rewrite))] => {
+                assert_eq!(path, &PathBuf::from("activities"));
+                assert_eq!(rewrite.before, vec!["mail email customer=acme", "meeting"]);
+            }
+            other => panic!("unexpected changes {:?}", other),
+        }
+    }
+
+    #[test]
+    fn prune_and_restore() {
+        let activitiesfile = FakeFile::new(PathBuf::from("activities"));
+        write!(
+            activitiesfile.writer().unwrap(),
+            "[email]\nshortname = \"mail\"\n[meeting]\n[pager]\n[travel]\n"
+        )
+        .unwrap();
+        let logfile = FakeFile::new(PathBuf::from("log"));
+        let storage = SingleFile::new(logfile);
+        let journalfile = FakeFile::new(PathBuf::from("journal"));
+        let journal = Journal::new(&journalfile, &Local::now(), "add");
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        storage.append(&day(1), "08:00 travel", &journal).unwrap();
+        storage.append(&day(4), "07:00 @pager", &journal).unwrap();
+        storage
+            .append(&day(4), "08:00 email =mail", &journal)
+            .unwrap();
+        storage.append(&day(4), "+0:30 meeting", &journal).unwrap();
+
+        let journal = Journal::new(&journalfile, &Local::now(), "activities");
+        let unused =
+            archive_unused(&day(4), &day(5), &storage, &activitiesfile, &journal, true).unwrap();
+        assert_eq!(unused, vec!["travel"]);
+        let archived = |file: &FakeFile| -> Vec<String> {
+            let (_, list) = read_activity_list(file.reader().unwrap()).unwrap();
+            list.into_iter()
+                .filter(|activity| activity.archived)
+                .map(|activity| activity.name)
+                .collect()
+        };
+        assert!(archived(&activitiesfile).is_empty());
+        archive_unused(&day(5), &day(5), &storage, &activitiesfile, &journal, false).unwrap();
+        assert_eq!(
+            archived(&activitiesfile),
+            vec!["email", "meeting", "pager", "travel"]
+        );
+
+        restore(&["mail".to_string()], &day(5), &activitiesfile, &journal).unwrap();
+        assert_eq!(
+            archived(&activitiesfile),
+            vec!["meeting", "pager", "travel"]
+        );
+        // a restored activity is kept until it has been unused since the day it was restored
+        archive_unused(&day(5), &day(6), &storage, &activitiesfile, &journal, false).unwrap();
+        assert_eq!(
+            archived(&activitiesfile),
+            vec!["meeting", "pager", "travel"]
+        );
+        archive_unused(&day(6), &day(6), &storage, &activitiesfile, &journal, false).unwrap();
+        assert_eq!(
+            archived(&activitiesfile),
+            vec!["email", "meeting", "pager", "travel"]
+        );
+        assert!(restore(&["unknown".to_string()], &day(6), &activitiesfile, &journal).is_err());
+    }
+
+    #[test]
+    fn archived_automatically_once_a_day() {
+        TTConfig::init("archive-activities-after = 3\n".as_bytes()).unwrap();
+        let activitiesfile = FakeFile::new(PathBuf::from("activities"));
+        write!(activitiesfile.writer().unwrap(), "email\nmeeting\npager\n").unwrap();
+        let storage = SingleFile::new(FakeFile::new(PathBuf::from("log")));
+        let prunedfile = FakeFile::new(PathBuf::from("pruned"));
+        let journalfile = FakeFile::new(PathBuf::from("journal"));
+        let journal = Journal::new(&journalfile, &Local::now(), "add");
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+        let at = |d| {
+            Local
+                .from_local_datetime(&day(d).and_hms_opt(9, 0, 0).unwrap())
+                .unwrap()
+        };
+        storage.append(&day(4), "08:00 email", &journal).unwrap();
+        storage.append(&day(6), "08:00 email", &journal).unwrap();
+        let archive = |d| {
+            archive_automatically(&at(d), &storage, &activitiesfile, &prunedfile, &journal)
+                .unwrap();
+            let (_, list) = read_activity_list(activitiesfile.reader().unwrap()).unwrap();
+            list.into_iter()
+                .filter(|activity| activity.archived)
+                .map(|activity| activity.name)
+                .collect::<Vec<String>>()
+        };
+
+        // the log does not reach back three days yet
+        assert!(archive(5).is_empty());
+        let mut pruned = String::new();
+        prunedfile
+            .reader()
+            .unwrap()
+            .read_to_string(&mut pruned)
+            .unwrap();
+        assert_eq!(pruned, "2024-03-05\n");
+        // the log has been looked at today already
+        prunedfile.replace_content(b"2024-03-07\n").unwrap();
+        assert!(archive(7).is_empty());
+        assert_eq!(archive(8), vec!["meeting", "pager"]);
+
+        restore(&["meeting".to_string()], &day(8), &activitiesfile, &journal).unwrap();
+        assert_eq!(archive(10), vec!["email", "pager"]);
+        assert_eq!(archive(12), vec!["email", "meeting", "pager"]);
+    }
+}
@@ -1,17 +1,22 @@
-use std::collections::HashMap;
 use std::io::{BufRead, Write};
 use std::result::Result;
 use std::string::String;
 
 use chrono::{self, DateTime, Duration, Local, NaiveTime};
-use itertools::Itertools;
 use structopt::StructOpt;
 
-use crate::collector::collect_blocks;
+use crate::activities::{
+    self, activity_lines, read_activity_list, Activity, ActivityMap, FileFormat,
+};
+use crate::collector::collect_workday;
+use crate::configfile::TTConfig;
 use crate::error::{TTError, TTErrorKind};
-use crate::log_parser::{is_break, is_distributable, is_start};
+use crate::journal::Journal;
+use crate::log_parser::{is_break, is_distributable, is_start, split_tags};
 use crate::log_parser::{Block, BlockData};
-use crate::subcommands::report::{report, SummaryFormat};
+use crate::storage::Storage;
+use crate::subcommands::activities::archive_automatically;
+use crate::subcommands::report::{print_diagnostics, report, SummaryFormat};
 use crate::utils;
 use crate::utils::FileProxy;
 
@@ -22,20 +27,34 @@ pub(crate) struct AddOpt {
     pub really: bool,
 
     #[structopt(short, long="time", parse(try_from_str = utils::parse_time))]
-    /// use this time instead of the current time; format: HH:MM
+    /// use this time instead of the current time; format: HH:MM or HH:MM:SS
     pub timestamp: Option<NaiveTime>,
 
     #[structopt(short, long, default_value)]
     /// activity started so many minutes before
     pub ago: i64,
 
+    #[structopt(short, long, allow_hyphen_values = true, parse(try_from_str = parse_adjustment))]
+    /// add this much time to the activity, or take it away with a minus, without a time on the
+    /// timeline; format: HH:MM, +HH:MM or -HH:MM
+    pub duration: Option<Duration>,
+
+    #[structopt(long)]
+    /// start this secondary track, which runs next to the activities; end it with "break"
+    pub track: Option<String>,
+
     #[structopt(name = "ACTIVITY")]
     /// which activity (start with "+" if not in activities file)
     pub activity: Option<String>,
 
     #[structopt(name = "tags")]
-    /// any additional tags; start first tag with "=" to give a shortname
+    /// any additional tags; start first tag with "=" to give a shortname,
+    /// use key=value for metadata like customer=acme
     pub tags: Vec<String>,
+
+    #[structopt(name = "note", last = true)]
+    /// a note on what you did, after "--"
+    pub note: Vec<String>,
 }
 
 // interface for the runner, adds what is in the opt to the logfile
@@ -43,35 +62,120 @@ pub(crate) struct AddOpt {
 pub(crate) fn run<'a, R: BufRead, W: Write, F: FileProxy<R, W>>(
     opt: AddOpt,
     now: &DateTime<Local>,
-    default_logfile: &F,
+    storage: &dyn Storage<R, W, F>,
     activitiesfile: &F,
+    journalfile: &F,
+    prunedfile: &F,
 ) -> Result<i32, TTError> {
-    let timestamp = now.time();
+    let timestamp = utils::without_seconds(&now.time());
     let block = block_from_opt(opt, &timestamp)?;
-    let activity_map = read_activities(activitiesfile.reader()?)?;
+    let journal = Journal::new(journalfile, now, "add");
+    let _lock = journal.lock()?;
+    let activity_map = activities::read_activities(activitiesfile.reader()?)
+        .map_err(|err| err.in_file(activitiesfile.pathname()))?;
     add(
         block,
         Some(&activity_map),
         activitiesfile,
-        default_logfile,
+        storage,
         &timestamp,
         now,
+        &journal,
     )?;
+    archive_automatically(now, storage, activitiesfile, prunedfile, &journal)?;
     Ok(0)
 }
 
 // converts the options to a Block that represents a log entry
 fn block_from_opt(opt: AddOpt, logtime: &NaiveTime) -> Result<Block, TTError> {
     let distribute = &opt.activity.as_ref().map(|a| is_distributable(a));
+    let note = match opt.note.is_empty() {
+        true => None,
+        false => Some(opt.note.join(" ")),
+    };
     match opt {
         //  (opt.really, opt.activity, opt.timestamp, opt.tags)
+        AddOpt {
+            duration: Some(_),
+            really: true,
+            ..
+        }
+        | AddOpt {
+            duration: Some(_),
+            track: Some(_),
+            ..
+        } => Err(TTError::new(TTErrorKind::UsageError(
+            "a duration can neither be corrected with really nor be on a track",
+        ))),
+        AddOpt {
+            duration: Some(duration),
+            activity: Some(activity),
+            tags,
+            ..
+        } => {
+            let (tags, metadata) = split_tags(tags.iter().map(String::as_str));
+            Ok(Block::DurationBlock(
+                duration,
+                BlockData {
+                    start: NaiveTime::MIN,
+                    activity,
+                    tags,
+                    metadata,
+                    note,
+                    distribute: distribute.unwrap(),
+                    utc_offset: None,
+                    start_seconds: false,
+                },
+            ))
+        }
+        AddOpt {
+            track: Some(_),
+            really: true,
+            ..
+        } => Err(TTError::new(TTErrorKind::UsageError(
+            "a track cannot be corrected with really, edit the log file instead",
+        ))),
+        AddOpt {
+            track: Some(track),
+            really: false,
+            activity,
+            timestamp,
+            tags,
+            ago: before,
+            duration: _,
+            note: _,
+        } => {
+            // the first word after the track name can only end the track
+            let mut words: Vec<String> = activity.into_iter().chain(tags).collect();
+            let activity = match words.first().is_some_and(|word| is_break(word)) {
+                true => words.remove(0),
+                false => track.clone(),
+            };
+            let (tags, metadata) = split_tags(words.iter().map(String::as_str));
+            Ok(Block::TrackBlock(
+                track,
+                BlockData {
+                    start: timestamp.unwrap_or(*logtime) - Duration::minutes(before),
+                    activity,
+                    tags,
+                    metadata,
+                    note,
+                    distribute: false,
+                    utc_offset: None,
+                    start_seconds: false,
+                },
+            ))
+        }
         AddOpt {
             really: true,
             activity: None,
             timestamp: Some(real_timestamp),
             tags,
             ago: before,
-        } if tags.is_empty() => Ok(Block::TimeCorrection(
+            track: _,
+            duration: _,
+            note: _,
+        } if tags.is_empty() && note.is_none() => Ok(Block::TimeCorrection(
             real_timestamp - Duration::minutes(before),
         )),
         AddOpt {
@@ -80,7 +184,10 @@ fn block_from_opt(opt: AddOpt, logtime: &NaiveTime) -> Result<Block, TTError> {
             timestamp: None,
             tags,
             ago: before,
-        } if before > 0 && tags.is_empty() => {
+            track: _,
+            duration: _,
+            note: _,
+        } if before > 0 && tags.is_empty() && note.is_none() => {
             Ok(Block::TimeCorrection(*logtime - Duration::minutes(before)))
         }
         AddOpt {
@@ -89,18 +196,31 @@ fn block_from_opt(opt: AddOpt, logtime: &NaiveTime) -> Result<Block, TTError> {
             timestamp: None,
             tags,
             ago: 0,
-        } => Ok(Block::ReallyBlock(BlockData {
-            start: *logtime,
-            activity: real_activity,
-            tags,
-            distribute: distribute.unwrap(),
-        })),
+            track: _,
+            duration: _,
+            note: _,
+        } => {
+            let (tags, metadata) = split_tags(tags.iter().map(String::as_str));
+            Ok(Block::ReallyBlock(BlockData {
+                start: *logtime,
+                activity: real_activity,
+                tags,
+                metadata,
+                note,
+                distribute: distribute.unwrap(),
+                utc_offset: None,
+                start_seconds: false,
+            }))
+        }
         AddOpt {
             really: true,
             activity: _,
             timestamp: _,
             tags: _,
             ago: _,
+            track: _,
+            duration: _,
+            note: _,
         } => Err(TTError::new(TTErrorKind::UsageError(
             "really either needs an activity or a manual timestamp (which includes using --before)",
         ))),
@@ -110,30 +230,53 @@ fn block_from_opt(opt: AddOpt, logtime: &NaiveTime) -> Result<Block, TTError> {
             timestamp: Some(real_timestamp),
             tags,
             ago: before,
-        } => Ok(Block::NormalBlock(BlockData {
-            start: real_timestamp - Duration::minutes(before),
-            activity,
-            tags,
-            distribute: distribute.unwrap(),
-        })),
+            track: _,
+            duration: _,
+            note: _,
+        } => {
+            let (tags, metadata) = split_tags(tags.iter().map(String::as_str));
+            Ok(Block::NormalBlock(BlockData {
+                start: real_timestamp - Duration::minutes(before),
+                activity,
+                tags,
+                metadata,
+                note,
+                distribute: distribute.unwrap(),
+                utc_offset: None,
+                start_seconds: false,
+            }))
+        }
         AddOpt {
             really: false,
             activity: Some(activity),
             timestamp: None,
             tags,
             ago: before,
-        } => Ok(Block::NormalBlock(BlockData {
-            start: *logtime - Duration::minutes(before),
-            activity,
-            tags,
-            distribute: distribute.unwrap(),
-        })),
+            track: _,
+            duration: _,
+            note: _,
+        } => {
+            let (tags, metadata) = split_tags(tags.iter().map(String::as_str));
+            Ok(Block::NormalBlock(BlockData {
+                start: *logtime - Duration::minutes(before),
+                activity,
+                tags,
+                metadata,
+                note,
+                distribute: distribute.unwrap(),
+                utc_offset: None,
+                start_seconds: false,
+            }))
+        }
         AddOpt {
             really: false,
             activity: None,
             timestamp: _,
             tags: _,
             ago: _,
+            track: _,
+            duration: _,
+            note: _,
         } => Err(TTError::new(TTErrorKind::UsageError(
             "please provide an activity.",
         ))),
@@ -152,42 +295,12 @@ fn shortname_from_tag(s: &String) -> Option<String> {
     }
 }
 
-pub type ActivityMap = HashMap<String, (String, Vec<String>)>;
-
-pub fn read_activities<R: BufRead>(configfile: R) -> Result<ActivityMap, TTError> {
-    let mut activity_map: ActivityMap = HashMap::new();
-    for line in configfile.lines() {
-        let some_line = line?;
-        let mut words = some_line.split_whitespace();
-        let shortname = words.next();
-        let activity = words.next();
-        let tags: Vec<String> = words.map(|s| s.to_string()).collect();
-        match (shortname, activity) {
-            (None, _) => {
-                return Err(TTError::new(TTErrorKind::ActivityConfigError(
-                    "Config activity lines wrong, correct format is <left> <right> or <activity>",
-                )))
-            }
-            (Some(shortname), None) => {
-                activity_map.insert(shortname.to_string(), (shortname.to_string(), vec![]));
-                ()
-            }
-            (Some(shortname), Some(activity)) => {
-                activity_map.insert(shortname.to_string(), (activity.to_string(), tags));
-                let shortname_for_backlink = if activity_map.contains_key(activity) {
-                    vec![]
-                } else {
-                    vec![shortname.to_string()]
-                };
-                activity_map.insert(
-                    activity.to_string(),
-                    (activity.to_string(), shortname_for_backlink),
-                );
-                ()
-            }
-        }
+// the duration of --duration, like the manual durations in the log, but the plus is optional
+fn parse_adjustment(s: &str) -> Result<Duration, String> {
+    match s.starts_with(&['+', '-'][..]) {
+        true => utils::parse_signed_duration(s),
+        false => utils::parse_signed_duration(&format!("+{}", s)),
     }
-    Ok(activity_map)
 }
 
 // validates the activity from user input in a block and resolves shortnames etc.
@@ -198,16 +311,26 @@ pub fn read_activities<R: BufRead>(configfile: R) -> Result<ActivityMap, TTError
 // - creates new entry in activitymap if a tag provides a shortname (starting with '=')
 // - writes a bit of information to stdout if applicable
 // The function mutates the data in the passed block in-place
-pub fn validate_activity<'a, R: BufRead, W: Write>(
+pub fn validate_activity<'a, R: BufRead, W: Write, F: FileProxy<R, W>>(
     block: &'a mut Block,
     activity_map: &ActivityMap,
-    activitiesfile: &impl FileProxy<R, W>,
+    activitiesfile: &F,
+    journal: &Journal<F>,
 ) -> Result<(), TTError> {
-    if let Block::ReallyBlock(ref mut data) | Block::NormalBlock(ref mut data) = block {
+    if let Block::ReallyBlock(ref mut data)
+    | Block::NormalBlock(ref mut data)
+    | Block::DurationBlock(_, ref mut data) = block
+    {
         let is_force_add = data.activity.starts_with("+");
         // let activity = if is_force_add  { &data.activity[1..] } else { &data.activity };
         if is_force_add {
             data.activity = utils::resolve_prefix_for_number(data.activity[1..].as_ref());
+            if data.activity.starts_with('@') {
+                // it would be read back as a track
+                return Err(TTError::new(TTErrorKind::UsageError(
+                    "activity names cannot start with @, that starts a track",
+                )));
+            }
             data.distribute = is_distributable(data.activity.as_ref());
 
             // now check whether we want to add a new shortcut to the activitiesfile
@@ -216,19 +339,44 @@ pub fn validate_activity<'a, R: BufRead, W: Write>(
                 && !is_start(data.activity.as_ref())
             {
                 if let Some(shortname) = data.tags.iter().find_map(shortname_from_tag) {
-                    // there is a tag in form =..., so user wants to define a shortcut
-                    let is_already_defined =
-                        if let Some((found, _found_tags)) = activity_map.get(&shortname) {
-                            *found == data.activity
-                        } else {
-                            false
+                    // there is a tag in form =..., so user wants to define a shortcut.
+                    // Another tt might have added it since the activity_map has been read.
+                    let (format, current) = read_activity_list(activitiesfile.reader()?)
+                        .map_err(|err| err.in_file(activitiesfile.pathname()))?;
+                    let is_already_defined = activities::activity_map(&current)
+                        .get(&shortname)
+                        .is_some_and(|found| found.name == data.activity);
+                    let is_in_table = format == FileFormat::Toml
+                        && current.iter().any(|known| known.name == data.activity);
+                    if is_in_table {
+                        // a second table with the same name would make the file invalid
+                        let mut text = String::new();
+                        activitiesfile.reader()?.read_to_string(&mut text)?;
+                        let content = activities::with_shortname(&text, &data.activity, &shortname)
+                            .map_err(|err| err.in_file(activitiesfile.pathname()))?;
+                        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+                        journal.rewrite_file(activitiesfile, &lines)?;
+                        println!(
+                            "Added to activitiesfile: ={} for {}",
+                            shortname, data.activity
+                        );
+                    } else if !is_already_defined {
+                        let activity = Activity {
+                            shortname: Some(shortname),
+                            tags: data
+                                .tags
+                                .iter()
+                                .filter(|t| !is_shortname(t))
+                                .cloned()
+                                .chain(data.metadata.iter().map(|(k, v)| format!("{}={}", k, v)))
+                                .collect(),
+                            ..Activity::new(&data.activity)
                         };
-                    if !is_already_defined {
-                        let other_tags = data.tags.iter().filter(|t| !is_shortname(t)).join(" ");
-                        let sep = if other_tags.is_empty() { "" } else { " " };
-                        let line = format!("{} {}{}{}", shortname, data.activity, sep, other_tags);
-                        writeln!(activitiesfile.writer()?, "{}", line)?;
-                        println!("Added to activitiesfile: {}", line);
+                        let lines = activity_lines(&activity, format);
+                        for line in lines.iter() {
+                            journal.append_line(activitiesfile, line)?;
+                        }
+                        println!("Added to activitiesfile: {}", lines.join("\n"));
                     }
                 }
             }
@@ -237,12 +385,37 @@ pub fn validate_activity<'a, R: BufRead, W: Write>(
             let activity: String = utils::resolve_prefix_for_number(data.activity.as_ref());
 
             // Is this a shortcut in the activitiesfile? then use the data from the activitiesfile.
-            if let Some((found_activity, found_tags)) = activity_map.get(&activity) {
-                data.activity = found_activity.to_string();
-                if activity != *found_activity {
-                    data.tags.insert(0, format!("={}", activity))
+            if let Some(found) = activity_map.get(&activity) {
+                if found.archived {
+                    println!(
+                        "{} is archived, tt activities restore {} lists it again",
+                        found.name, activity
+                    );
+                }
+                data.activity = found.name.to_string();
+                // the log has the preferred shortname, and the alias that was typed only if it
+                // is asked for
+                if activity != found.name {
+                    let is_alias = Some(&activity) != found.shortname.as_ref();
+                    let recorded: Vec<String> = found
+                        .shortname
+                        .iter()
+                        .map(|shortname| format!("={}", shortname))
+                        .chain(
+                            (is_alias && TTConfig::get().record_alias)
+                                .then(|| format!("alias:{}", activity)),
+                        )
+                        .collect();
+                    data.tags.splice(0..0, recorded);
+                }
+                let found_tags: Vec<String> = found.metadata_tags().collect();
+                let (found_tags, found_metadata) =
+                    split_tags(found_tags.iter().map(String::as_str));
+                data.tags.extend(found_tags);
+                for (key, value) in found_metadata {
+                    // metadata given for the entry overrides the one of the activities file
+                    data.metadata.entry(key).or_insert(value);
                 }
-                data.tags.extend_from_slice(found_tags.as_slice());
             } else {
                 // since for all shortnames we also add the activity as key into the activity_map,
                 // not finding the activity in the map means it is not known at all.
@@ -259,26 +432,42 @@ pub fn validate_activity<'a, R: BufRead, W: Write>(
 
 // handle subcommand "add"
 // - validates the activity if activity_map is not None
-// - writes the block to the log file
+// - writes the block to the log of the current workday
 // - if the activity is not already in the actitiviesfile and a tag with starting with = is provided,
 //   add the activity to the activitiesfile
 // - writes to stdout what it has added
-pub fn add<R: BufRead, W: Write>(
+// - records the lines it has written in the journal
+// - the caller holds the lock of the journal, from before it has read what the block is based on
+pub fn add<R: BufRead, W: Write, F: FileProxy<R, W>>(
     mut block: Block,
     activity_map: Option<&ActivityMap>,
-    activitiesfile: &impl FileProxy<R, W>,
-    logfile: &impl FileProxy<R, W>,
+    activitiesfile: &F,
+    storage: &dyn Storage<R, W, F>,
     timestamp: &NaiveTime,
     now: &DateTime<Local>,
+    journal: &Journal<F>,
 ) -> Result<(), TTError> {
     if let Some(activity_map) = activity_map {
-        validate_activity(&mut block, activity_map, activitiesfile)?;
+        validate_activity(&mut block, activity_map, activitiesfile, journal)?;
+    }
+    if let (Block::NormalBlock(data) | Block::TrackBlock(_, data), true) =
+        (&mut block, TTConfig::get().record_utc_offset)
+    {
+        data.utc_offset.get_or_insert(*now.offset());
     }
     let msg = block.to_string(timestamp);
     println!("{}", msg);
-    let mut writer = logfile.writer()?;
-    writeln!(writer, "{}", msg)?;
-    let collected = collect_blocks(logfile.reader()?.lines(), Some(&now.time()))?;
+    let workday = utils::workday(&now.naive_local());
+    storage.append(&workday, &msg, journal)?;
+    let previous_day = storage.load_day(&(workday - Duration::days(1)))?;
+    let day = storage.load_day(&workday)?;
+    let (collected, diagnostics) = collect_workday(
+        &workday,
+        previous_day.iter_lines(),
+        day.iter_lines(),
+        Some(&now.time()),
+    );
+    print_diagnostics(&storage.path(&workday), diagnostics);
     report(&collected, &SummaryFormat::Short, &None);
     Ok(())
 }
@@ -291,6 +480,12 @@ mod tests {
 
     use super::utils::FakeFile;
     use super::*;
+    use crate::storage::{DailyFiles, Layout};
+
+    fn storage(logfile: &FakeFile) -> DailyFiles<FakeFile> {
+        DailyFiles::new(PathBuf::from("/tmp"), Layout::Flat)
+            .with_file(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(), logfile)
+    }
 
     #[test]
     fn write_log_normal() {
@@ -302,12 +497,17 @@ mod tests {
             activity: Some("+test-log".to_string()),
             tags: vec!["=some-tag".to_string()],
             ago: 120,
+            track: None,
+            duration: None,
+            note: vec![],
         };
         let result = run(
             opt,
             &Local.ymd(2020, 1, 1).and_hms(8, 0, 0),
-            &logfile,
+            &storage(&logfile),
             &activitiesfile,
+            &FakeFile::new(PathBuf::from("journal")),
+            &FakeFile::new(PathBuf::from("pruned")),
         );
         assert!(result.is_ok());
         assert_eq!(logfile.close().unwrap(), "08:00 06:00 test-log =some-tag\n");
@@ -324,17 +524,23 @@ mod tests {
             activity: Some("test-log".to_string()),
             tags: Vec::new(),
             ago: 0,
+            track: None,
+            duration: None,
+            note: vec![],
         };
         let result = run(
             opt,
             &Local.ymd(2020, 1, 1).and_hms(8, 0, 0),
-            &logfile,
+            &storage(&logfile),
             &activitiesfile,
+            &FakeFile::new(PathBuf::from("journal")),
+            &FakeFile::new(PathBuf::from("pruned")),
         );
         match result {
             Err(TTError {
                 kind: TTErrorKind::UsageError(_),
                 context: _,
+                ..
             }) => (),
             _ => panic!("Expected a LogError::UsageError"),
         }
@@ -353,12 +559,17 @@ mod tests {
             activity: Some("some-tag".to_string()),
             tags: vec!["bla".to_string(), "blo".to_string()],
             ago: 0,
+            track: None,
+            duration: None,
+            note: vec![],
         };
         let result = run(
             opt,
             &Local.ymd(2020, 1, 1).and_hms(8, 0, 0),
-            &logfile,
+            &storage(&logfile),
             &activitiesfile,
+            &FakeFile::new(PathBuf::from("journal")),
+            &FakeFile::new(PathBuf::from("pruned")),
         );
         assert!(result.is_ok());
         assert_eq!(
@@ -379,12 +590,17 @@ mod tests {
             activity: Some("some-tag".to_string()),
             tags: vec![],
             ago: 0,
+            track: None,
+            duration: None,
+            note: vec![],
         };
         let result = run(
             opt,
             &Local.ymd(2020, 1, 1).and_hms(8, 0, 0),
-            &logfile,
+            &storage(&logfile),
             &activitiesfile,
+            &FakeFile::new(PathBuf::from("journal")),
+            &FakeFile::new(PathBuf::from("pruned")),
         );
         assert!(result.is_ok());
         assert_eq!(
@@ -409,12 +625,17 @@ mod tests {
                 "blo".to_string(),
             ],
             ago: 0,
+            track: None,
+            duration: None,
+            note: vec![],
         };
         let result = run(
             opt,
             &Local.ymd(2020, 1, 1).and_hms(8, 0, 0),
-            &logfile,
+            &storage(&logfile),
             &activitiesfile,
+            &FakeFile::new(PathBuf::from("journal")),
+            &FakeFile::new(PathBuf::from("pruned")),
         );
         assert!(result.is_ok());
         assert_eq!(
@@ -0,0 +1,128 @@
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::result::Result;
+
+use chrono::{DateTime, Local, NaiveDate};
+use itertools::Itertools;
+use structopt::StructOpt;
+
+use crate::archive::{archive_name, read_archive, write_archive};
+use crate::error::{TTError, TTErrorKind};
+use crate::subcommands::migrate::{find_logfiles, remove_empty_dirs};
+use crate::utils::{self, FileProxy, NamedFile};
+
+#[derive(StructOpt, Debug)]
+pub struct ArchiveOpt {
+    #[structopt(long)]
+    /// archive the log files of the days before this date
+    pub before: NaiveDate,
+}
+
+// interface for the runner, archives the log files while holding the lock
+pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    opt: ArchiveOpt,
+    now: &DateTime<Local>,
+    data_dir: &Path,
+    journalfile: &F,
+) -> Result<i32, TTError> {
+    if opt.before > utils::workday(&now.naive_local()) {
+        return Err(TTError::new(TTErrorKind::UsageError(
+            "only the days before today can be archived",
+        )));
+    }
+    let _lock = journalfile.lock()?;
+    for (archive, days) in archive(data_dir, &opt.before)? {
+        println!("{}: archived {} days", archive.display(), days);
+    }
+    Ok(0)
+}
+
+/// moves the daily log files of the days before the date into the archives of their months,
+/// together with the days that are already there. Returns the archives and how many days have
+/// been added to them.
+pub fn archive(data_dir: &Path, before: &NaiveDate) -> Result<Vec<(PathBuf, usize)>, TTError> {
+    let logfiles = find_logfiles(data_dir)?
+        .into_iter()
+        .filter(|(date, _)| date < before);
+    let mut archived = Vec::new();
+    for (name, month) in &logfiles.group_by(|(date, _)| archive_name(data_dir, date)) {
+        let month: Vec<(NaiveDate, PathBuf)> = month.collect();
+        let archive = NamedFile::new(name.clone());
+        let mut days = read_archive(&archive).map_err(|err| err.in_file(&name))?;
+        for (date, path) in month.iter() {
+            let lines = NamedFile::new(path.clone())
+                .reader()?
+                .lines()
+                .collect::<Result<Vec<String>, _>>()?;
+            days.entry(*date).or_default().extend(lines);
+        }
+        write_archive(&archive, &days)?;
+        for (_, path) in month.iter() {
+            fs::remove_file(path)?;
+            remove_empty_dirs(data_dir, path);
+        }
+        archived.push((name, month.len()));
+    }
+    Ok(archived)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{DailyFiles, Layout, Storage};
+    use std::env;
+
+    #[test]
+    fn archived_days_are_read() {
+        let dir = env::temp_dir().join(format!("tt-archive-{}", std::process::id()));
+        fs::create_dir_all(dir.join("2020/05")).unwrap();
+        fs::write(dir.join("2020/05/2020-05-29"), "09:00 meeting\n").unwrap();
+        fs::write(dir.join("2020-05-31"), "10:00 email\n10:30 break\n").unwrap();
+        fs::write(dir.join("2020-06-01"), "08:00 email\n").unwrap();
+
+        let archived = archive(&dir, &NaiveDate::from_ymd_opt(2020, 6, 1).unwrap()).unwrap();
+        assert_eq!(archived, vec![(dir.join("archive/2020-05.gz"), 2)]);
+        assert!(!dir.join("2020-05-31").exists());
+        assert!(!dir.join("2020").exists());
+        assert!(dir.join("2020-06-01").exists());
+
+        // entries added to an archived day follow the archived ones
+        fs::write(dir.join("2020-05-31"), "11:00 email\n").unwrap();
+        let storage: DailyFiles<NamedFile> = DailyFiles::new(dir.clone(), Layout::Flat);
+        assert_eq!(
+            storage.first_date().unwrap(),
+            NaiveDate::from_ymd_opt(2020, 5, 29)
+        );
+        let days = storage
+            .load(
+                &NaiveDate::from_ymd_opt(2020, 5, 29).unwrap(),
+                &NaiveDate::from_ymd_opt(2020, 6, 1).unwrap(),
+            )
+            .unwrap();
+        assert_eq!(days[0].lines, vec!["09:00 meeting"]);
+        assert!(days[1].lines.is_empty());
+        assert_eq!(
+            days[2].lines,
+            vec!["10:00 email", "10:30 break", "11:00 email"]
+        );
+        assert_eq!(days[3].lines, vec!["08:00 email"]);
+
+        let lock = NamedFile::new(dir.join("journal")).lock().unwrap();
+        storage
+            .rewrite(
+                &NaiveDate::from_ymd_opt(2020, 5, 31).unwrap(),
+                &["10:00 email".to_string(), "11:30 break".to_string()],
+                &lock,
+            )
+            .unwrap();
+        assert_eq!(
+            storage
+                .load_day(&NaiveDate::from_ymd_opt(2020, 5, 31).unwrap())
+                .unwrap()
+                .lines,
+            vec!["10:00 email", "11:30 break"]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
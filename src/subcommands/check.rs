@@ -0,0 +1,533 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::{BufRead, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::result::Result;
+
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use itertools::Itertools;
+use structopt::StructOpt;
+
+use crate::activities::{self, read_activities, FileFormat, CONFLICT_MARKER};
+use crate::collector::{carried_over, BlockCollector};
+use crate::error::{Diagnostic, Severity, TTError, TTErrorKind};
+use crate::log_parser::{is_break, is_distributable, is_start, words_with_spans, Block};
+use crate::storage::Storage;
+use crate::utils::{self, FileProxy};
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct CheckOpt {
+    #[structopt(long)]
+    /// first day to check, default is the last day
+    pub from: Option<NaiveDate>,
+
+    #[structopt(long)]
+    /// last day to check, default is today
+    pub to: Option<NaiveDate>,
+}
+
+// interface for the runner, checks the activities file and the log files of the date range.
+// Returns 1 if any problem has been found.
+pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    opt: CheckOpt,
+    now: &DateTime<Local>,
+    storage: &dyn Storage<R, W, F>,
+    activitiesfile: &F,
+) -> Result<i32, TTError> {
+    let today = utils::workday(&now.naive_local());
+    let to = opt.to.unwrap_or(today);
+    let from = opt.from.unwrap_or(to);
+    if from > to {
+        return Err(TTError::new(TTErrorKind::UsageError(
+            "the first day to check is after the last day",
+        )));
+    }
+    let mut text = String::new();
+    activitiesfile.reader()?.read_to_string(&mut text)?;
+    let (known_activities, diagnostics) = match FileFormat::of(&text) {
+        FileFormat::Lines => check_activities(text.lines().map(|line| Ok(line.to_string()))),
+        // the problems of TOML are errors when reading it
+        FileFormat::Toml => {
+            let conflicts = text
+                .lines()
+                .enumerate()
+                .filter_map(|(index, line)| Some(merge_conflict(line)?.at_line(index + 1)));
+            match read_activities(text.as_bytes()) {
+                Ok(activities) => (activities.into_keys().collect(), conflicts.collect()),
+                Err(err) => (
+                    HashSet::new(),
+                    std::iter::once(Diagnostic::error(err))
+                        .chain(conflicts)
+                        .collect(),
+                ),
+            }
+        }
+    };
+    let mut counter = ProblemCounter::default();
+    counter.print(activitiesfile.pathname(), diagnostics);
+    let days = storage.load(&(from - Duration::days(1)), &to)?;
+    for (previous_day, day) in days.iter().tuple_windows() {
+        let diagnostics = check_log(
+            &day.date,
+            previous_day.iter_lines(),
+            day.iter_lines(),
+            &known_activities,
+            day.date < today,
+        );
+        counter.print(&storage.path(&day.date), diagnostics);
+    }
+    match (counter.errors, counter.warnings) {
+        (0, 0) => {
+            println!("no problems found");
+            Ok(0)
+        }
+        (errors, warnings) => {
+            println!("found {} errors and {} warnings", errors, warnings);
+            Ok(1)
+        }
+    }
+}
+
+#[derive(Default)]
+struct ProblemCounter {
+    errors: usize,
+    warnings: usize,
+}
+
+impl ProblemCounter {
+    fn print(&mut self, path: &Path, diagnostics: Vec<Diagnostic>) {
+        for diagnostic in diagnostics {
+            match diagnostic.severity {
+                Severity::Error => self.errors += 1,
+                Severity::Warning => self.warnings += 1,
+            }
+            println!("{}\n", diagnostic.in_file(path));
+        }
+    }
+}
+
+/// checks the lines of the activities file. Returns all activities and shortnames that are
+/// defined, and the problems found: empty lines, shortnames that are defined more than once and
+/// conflicts that `tt merge` has marked.
+///
+/// ```
+/// use timetracker::subcommands::check::check_activities;
+/// use timetracker::error::Severity;
+/// let lines = vec![
+///     Ok("mail email".to_string()),
+///     Ok("".to_string()),
+///     Ok("mail meeting".to_string()),
+/// ];
+/// let (known, diagnostics) = check_activities(lines.into_iter());
+/// assert!(known.contains("mail") && known.contains("email"));
+/// assert_eq!(diagnostics.len(), 2);
+/// assert_eq!(diagnostics[0].line_number, Some(2));
+/// assert_eq!(diagnostics[1].severity, Severity::Error);
+/// assert_eq!(diagnostics[1].line_number, Some(3));
+/// ```
+pub fn check_activities<T: Iterator<Item = io::Result<String>>>(
+    lines: T,
+) -> (HashSet<String>, Vec<Diagnostic>) {
+    let mut shortnames: HashMap<String, String> = HashMap::new();
+    let mut activities: HashSet<String> = HashSet::new();
+    let mut diagnostics = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                diagnostics.push(Diagnostic::error(err.into()).at_line(index + 1));
+                continue;
+            }
+        };
+        if let Some(diagnostic) = merge_conflict(&line) {
+            diagnostics.push(diagnostic.at_line(index + 1));
+            continue;
+        }
+        if activities::is_comment(&line) {
+            continue;
+        }
+        let error = |msg: &'static str, span: Range<usize>, hint: &'static str| {
+            TTError::new(TTErrorKind::ActivityConfigError(msg))
+                .with_span(&line, span)
+                .with_hint(hint)
+        };
+        let mut words = words_with_spans(&line);
+        let found = match (words.next(), words.next()) {
+            (None, _) => Err(Diagnostic::error(error(
+                "empty line, the format is <shortname> <activity> [tags] or <activity>",
+                0..line.len(),
+                "remove empty lines from the activities file",
+            ))),
+            (Some((span, activity)), None) => match shortnames.get(activity) {
+                Some(other) if other != activity => Err(Diagnostic::error(error(
+                    "this activity is also the shortname of another activity",
+                    span,
+                    "rename the activity or the shortname",
+                ))),
+                _ => Ok((None, activity)),
+            },
+            (Some((span, shortname)), Some((activity_span, activity))) => {
+                match (shortnames.get(shortname), shortnames.get(activity)) {
+                    (Some(other), _) if other == activity => Err(Diagnostic::warning(error(
+                        "the shortname is defined twice",
+                        span,
+                        "remove one of the lines",
+                    ))),
+                    (Some(_), _) => Err(Diagnostic::error(error(
+                        "the shortname is already used for another activity",
+                        span,
+                        "the later line wins, use another shortname",
+                    ))),
+                    (None, _) if shortname != activity && activities.contains(shortname) => {
+                        Err(Diagnostic::error(error(
+                            "the shortname is the name of another activity",
+                            span,
+                            "use another shortname",
+                        )))
+                    }
+                    (_, Some(other)) if other != activity => Err(Diagnostic::error(error(
+                        "this activity is also the shortname of another activity",
+                        activity_span,
+                        "rename the activity or the shortname",
+                    ))),
+                    _ => Ok((Some(shortname), activity)),
+                }
+            }
+        };
+        match found {
+            Ok((shortname, activity)) => {
+                if let Some(shortname) = shortname {
+                    shortnames.insert(shortname.to_string(), activity.to_string());
+                }
+                activities.insert(activity.to_string());
+            }
+            Err(diagnostic) => diagnostics.push(diagnostic.at_line(index + 1)),
+        }
+    }
+    activities.extend(shortnames.into_keys());
+    (activities, diagnostics)
+}
+
+// the error for a line of the activities file that `tt merge` has added for a conflicting
+// definition of the other machine
+fn merge_conflict(line: &str) -> Option<Diagnostic> {
+    if !line.starts_with(CONFLICT_MARKER) {
+        return None;
+    }
+    Some(Diagnostic::error(
+        TTError::new(TTErrorKind::ActivityConfigError(
+            "merge conflict, the other machine defines this activity differently",
+        ))
+        .with_span(line, 0..line.len())
+        .with_hint("change the definition here if the other one is right, then remove this line"),
+    ))
+}
+
+/// checks the log of a workday, using the log of the previous day for the activity that
+/// continues at the day change. Besides the problems that BlockCollector finds, it warns about
+/// activities that are not known, `start` entries that have not been corrected with `really`
+/// and, for a day that is over, a log that does not end with a break.
+///
+/// ```
+/// use std::collections::HashSet;
+/// use chrono::NaiveDate;
+/// use timetracker::subcommands::check::check_log;
+/// let known: HashSet<String> = vec!["email".to_string()].into_iter().collect();
+/// let lines = vec![
+///     Ok("8:00 email".to_string()),
+///     Ok("9:00 start".to_string()),
+///     Ok("10:00 meeting".to_string()),
+/// ];
+/// let diagnostics = check_log(
+///     &NaiveDate::from_ymd_opt(2020, 6, 16).unwrap(),
+///     std::iter::empty(),
+///     lines.into_iter(),
+///     &known,
+///     true,
+/// );
+/// let lines: Vec<_> = diagnostics.iter().map(|d| d.line_number.unwrap()).collect();
+/// assert_eq!(lines, vec![2, 3, 3]);
+/// ```
+pub fn check_log<T, U>(
+    workday: &NaiveDate,
+    previous_day: T,
+    line_iterator: U,
+    known_activities: &HashSet<String>,
+    day_is_over: bool,
+) -> Vec<Diagnostic>
+where
+    T: Iterator<Item = io::Result<String>>,
+    U: Iterator<Item = io::Result<String>>,
+{
+    let mut collector = BlockCollector::for_workday(workday);
+    for block in carried_over(previous_day) {
+        collector.add(block);
+    }
+    let mut diagnostics = Vec::new();
+    // line number and text of the last entry, and whether it is a `start` to be corrected
+    let mut last_entry: Option<(usize, String, bool)> = None;
+    for (index, line) in line_iterator.enumerate() {
+        let line_number = index + 1;
+        let text = line.as_ref().ok().cloned();
+        collector.add_line(line_number, line);
+        let (text, block) = match text {
+            Some(text) => match Block::from_line(Ok(text.clone())) {
+                Ok(block) => (text, block),
+                Err(_) => continue,
+            },
+            None => continue,
+        };
+        let data = match &block {
+            Block::NormalBlock(data) | Block::ReallyBlock(data) | Block::DurationBlock(_, data) => {
+                data
+            }
+            _ => continue,
+        };
+        if !known_activities.contains(&data.activity)
+            && !is_distributable(&data.activity)
+            && !is_break(&data.activity)
+            && !is_start(&data.activity)
+        {
+            diagnostics.push(
+                Diagnostic::warning(
+                    TTError::new(TTErrorKind::LogError(
+                        "the activity is not in the activities file",
+                    ))
+                    .with_span(&text, word_span(&text, &data.activity))
+                    .with_hint("add it with a shortname, e.g. `tt +ACTIVITY =SHORTNAME`"),
+                )
+                .at_line(line_number),
+            );
+        }
+        if let Block::NormalBlock(data) = &block {
+            if let Some((start_line, start_text, true)) = last_entry.take() {
+                diagnostics.push(unresolved_start(start_line, &start_text));
+            }
+            last_entry = Some((line_number, text, is_start(&data.activity)));
+        } else if let (Block::ReallyBlock(data), Some((_, _, ref mut is_open_start))) =
+            (&block, &mut last_entry)
+        {
+            *is_open_start = is_start(&data.activity);
+        }
+    }
+    diagnostics.extend(collector.take_diagnostics());
+    if day_is_over {
+        if let Some((line_number, text, open_start)) = last_entry {
+            if open_start {
+                diagnostics.push(unresolved_start(line_number, &text));
+            } else if collector
+                .last()
+                .is_some_and(|data| !is_break(&data.activity))
+            {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        TTError::new(TTErrorKind::LogError("the day does not end with a break"))
+                            .with_span(&text, 0..text.len())
+                            .with_hint("the activity continues on the next day until a break"),
+                    )
+                    .at_line(line_number),
+                );
+            }
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| {
+        (
+            diagnostic.line_number,
+            diagnostic.severity == Severity::Warning,
+        )
+    });
+    diagnostics
+}
+
+fn unresolved_start(line_number: usize, text: &str) -> Diagnostic {
+    Diagnostic::warning(
+        TTError::new(TTErrorKind::LogError(
+            "the activity of this `start` has never been given",
+        ))
+        .with_span(text, word_span(text, "start"))
+        .with_hint("correct it with `tt add --really ACTIVITY` or `HH:MM really ACTIVITY`"),
+    )
+    .at_line(line_number)
+}
+
+// the position of the word in the line, or the whole line if it is not found
+fn word_span(text: &str, word: &str) -> Range<usize> {
+    words_with_spans(text)
+        .find(|(_, found)| found.trim_start_matches('_') == word.trim_start_matches('_'))
+        .map_or(0..text.len(), |(span, _)| span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::setup_line_reader;
+
+    fn known() -> HashSet<String> {
+        vec!["email", "meeting"]
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    fn workday() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2020, 6, 16).unwrap()
+    }
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<(Severity, Option<usize>, String)> {
+        diagnostics
+            .iter()
+            .map(|d| match d.error.kind {
+                TTErrorKind::LogError(msg) | TTErrorKind::ActivityConfigError(msg) => {
+                    (d.severity, d.line_number, msg.to_string())
+                }
+                _ => (d.severity, d.line_number, d.error.to_string()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn clean_log() {
+        let diagnostics = check_log(
+            &workday(),
+            setup_line_reader(vec![]),
+            setup_line_reader(vec![
+                "8:00 email",
+                "9:00 start",
+                "9:30 really meeting",
+                "12:00 break",
+            ]),
+            &known(),
+            true,
+        );
+        assert!(diagnostics.is_empty(), "{:?}", messages(&diagnostics));
+    }
+
+    #[test]
+    fn log_problems() {
+        let diagnostics = check_log(
+            &workday(),
+            setup_line_reader(vec![]),
+            setup_line_reader(vec![
+                "8:00 really email",
+                "8:15 email",
+                "7:00 meeting",
+                "9:00 unknown",
+                "10:00 _start",
+                "11:00 email",
+            ]),
+            &known(),
+            true,
+        );
+        assert_eq!(
+            messages(&diagnostics),
+            vec![
+                (
+                    Severity::Error,
+                    Some(1),
+                    "there is no activity before to correct".to_string()
+                ),
+                (
+                    Severity::Error,
+                    Some(3),
+                    "the entry is earlier than the one before".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    Some(4),
+                    "the activity is not in the activities file".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    Some(5),
+                    "the activity of this `start` has never been given".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    Some(6),
+                    "the day does not end with a break".to_string()
+                ),
+            ]
+        );
+        assert_eq!(diagnostics[2].error.details.span, Some(5..12));
+    }
+
+    #[test]
+    fn open_start_of_today() {
+        let diagnostics = check_log(
+            &workday(),
+            setup_line_reader(vec![]),
+            setup_line_reader(vec!["8:00 email", "9:00 start"]),
+            &known(),
+            false,
+        );
+        assert!(diagnostics.is_empty(), "{:?}", messages(&diagnostics));
+    }
+
+    #[test]
+    fn correction_of_previous_day() {
+        let diagnostics = check_log(
+            &workday(),
+            setup_line_reader(vec!["23:00 email"]),
+            setup_line_reader(vec!["0:30 really meeting", "1:00 break"]),
+            &known(),
+            true,
+        );
+        assert!(diagnostics.is_empty(), "{:?}", messages(&diagnostics));
+    }
+
+    #[test]
+    fn activities_problems() {
+        let (known, diagnostics) = check_activities(setup_line_reader(vec![
+            "mail email",
+            "standup meeting =daily",
+            "",
+            "mail email",
+            "standup retro",
+            "email other",
+            "misc",
+            "standup",
+            "# merge conflict with laptop: misc miscellaneous",
+        ]));
+        assert_eq!(
+            messages(&diagnostics),
+            vec![
+                (
+                    Severity::Error,
+                    Some(3),
+                    "empty line, the format is <shortname> <activity> [tags] or <activity>"
+                        .to_string()
+                ),
+                (
+                    Severity::Warning,
+                    Some(4),
+                    "the shortname is defined twice".to_string()
+                ),
+                (
+                    Severity::Error,
+                    Some(5),
+                    "the shortname is already used for another activity".to_string()
+                ),
+                (
+                    Severity::Error,
+                    Some(6),
+                    "the shortname is the name of another activity".to_string()
+                ),
+                (
+                    Severity::Error,
+                    Some(8),
+                    "this activity is also the shortname of another activity".to_string()
+                ),
+                (
+                    Severity::Error,
+                    Some(9),
+                    "merge conflict, the other machine defines this activity differently"
+                        .to_string()
+                ),
+            ]
+        );
+        let mut known: Vec<_> = known.into_iter().collect();
+        known.sort();
+        assert_eq!(known, vec!["email", "mail", "meeting", "misc", "standup"]);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::error::{TTError, TTErrorKind};
-use crate::get_logfile_name;
-use crate::utils::FileProxy;
-use chrono::{DateTime, Local, NaiveDate};
+use crate::storage::Storage;
+use crate::utils::{self, FileProxy};
+use chrono::{DateTime, Duration, Local, NaiveDate};
 use std::io::{BufRead, Write};
 use std::path::Path;
 use std::{env, process};
@@ -27,20 +27,21 @@ pub struct EditOpt {
 pub(crate) fn run<'a, R: BufRead, W: Write, F: FileProxy<R, W>>(
     edit_opt: EditOpt,
     now: &DateTime<Local>,
-    default_logfile: &F,
+    storage: &dyn Storage<R, W, F>,
     activitiesfile: &F,
 ) -> Result<i32, TTError> {
+    let workday = utils::workday(&now.naive_local());
     let pathname = match (edit_opt.activities, edit_opt.date, edit_opt.yesterday) {
         (true, None, false) => Ok(activitiesfile.pathname().to_path_buf()),
         (true, _, _) => Err(TTError::new(TTErrorKind::UsageError(
             "edit --activity does not allow dates",
         ))),
-        (false, Some(date), false) => Ok(get_logfile_name(&date)),
+        (false, Some(date), false) => Ok(storage.path(&date)),
         (false, Some(_data), true) => Err(TTError::new(TTErrorKind::UsageError(
             "You can either specify a --date or --yesterday, but you specified both.",
         ))),
-        (false, None, false) => Ok(default_logfile.pathname().to_path_buf()),
-        (false, None, true) => Ok(get_logfile_name(&now.date().pred().naive_local())),
+        (false, None, false) => Ok(storage.path(&workday)),
+        (false, None, true) => Ok(storage.path(&(workday - Duration::days(1)))),
     };
     pathname.and_then(|p| edit(&p))?;
     Ok(0)
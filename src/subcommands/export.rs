@@ -0,0 +1,65 @@
+use std::io::{BufRead, Write};
+use std::result::Result;
+
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use itertools::Itertools;
+use structopt::StructOpt;
+
+use crate::collector::collect_workday;
+use crate::error::{TTError, TTErrorKind};
+use crate::export::{self, write_records, Format};
+use crate::storage::Storage;
+use crate::subcommands::report::print_diagnostics;
+use crate::utils::{self, FileProxy};
+
+#[derive(StructOpt, Debug)]
+pub struct ExportOpt {
+    #[structopt(long)]
+    /// first day to export, default is the last day
+    pub from: Option<NaiveDate>,
+
+    #[structopt(long)]
+    /// last day to export, default is today
+    pub to: Option<NaiveDate>,
+
+    #[structopt(short, long, default_value = "csv")]
+    /// the format of the export: csv, json, timewarrior, watson, timeclock or ics
+    pub format: Format,
+}
+
+// interface for the runner, prints the intervals of the days
+pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    opt: ExportOpt,
+    now: &DateTime<Local>,
+    storage: &dyn Storage<R, W, F>,
+) -> Result<i32, TTError> {
+    let today = utils::workday(&now.naive_local());
+    let to = opt.to.unwrap_or(today);
+    let from = opt.from.unwrap_or(to);
+    if from > to {
+        return Err(TTError::new(TTErrorKind::UsageError(
+            "the first day to export is after the last day",
+        )));
+    }
+    let mut records = Vec::new();
+    let days = storage.load(&(from - Duration::days(1)), &to)?;
+    for (previous_day, day) in days.iter().tuple_windows() {
+        // the current activity lasts until now
+        let add_ending_at = match day.date == today {
+            true => Some(now.naive_local().time()),
+            false => None,
+        };
+        let (collected, diagnostics) = collect_workday(
+            &day.date,
+            previous_day.iter_lines(),
+            day.iter_lines(),
+            add_ending_at.as_ref(),
+        );
+        print_diagnostics(&storage.path(&day.date), diagnostics);
+        if let Some(collected) = collected {
+            records.extend(export::records(&day.date, &collected.summary));
+        }
+    }
+    print!("{}", write_records(opt.format, &records)?);
+    Ok(0)
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::result::Result;
+
+use chrono::{DateTime, Local, NaiveDate};
+use structopt::StructOpt;
+
+use crate::error::{TTError, TTErrorKind};
+use crate::export::{log_lines, read_records, Format, Record};
+use crate::journal::Journal;
+use crate::storage::Storage;
+use crate::subcommands::merge::{merge_day, MergedDay};
+use crate::utils::FileProxy;
+
+#[derive(StructOpt, Debug)]
+pub struct ImportOpt {
+    #[structopt(parse(from_os_str))]
+    /// the file to import, as written by `tt export` or another time tracker
+    pub file: PathBuf,
+
+    #[structopt(short, long)]
+    /// the format of the file: csv, json, timewarrior, watson or timeclock. Default is the
+    /// extension of the file: .csv, .json, .data (timewarrior) or .timeclock
+    pub format: Option<Format>,
+
+    #[structopt(long)]
+    /// import also if entries overlap with other activities in the log, with conflict markers
+    pub merge: bool,
+}
+
+// interface for the runner, adds the intervals of the file to the log.
+// Returns 1 if there are overlaps.
+pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    opt: ImportOpt,
+    now: &DateTime<Local>,
+    storage: &dyn Storage<R, W, F>,
+    journalfile: &F,
+) -> Result<i32, TTError> {
+    let extension = opt.file.extension().and_then(|e| e.to_str());
+    let format = match opt
+        .format
+        .or_else(|| extension.and_then(Format::from_extension))
+    {
+        Some(format) => format,
+        None => {
+            return Err(TTError::new(TTErrorKind::UsageError(
+                "cannot tell the format of the file, use --format",
+            )))
+        }
+    };
+    let text =
+        fs::read_to_string(&opt.file).map_err(|err| TTError::from(err).in_file(&opt.file))?;
+    let records = read_records(format, &text).map_err(|err| err.in_file(&opt.file))?;
+    let mut days: BTreeMap<NaiveDate, Vec<Record>> = BTreeMap::new();
+    for record in records {
+        days.entry(record.date).or_default().push(record);
+    }
+
+    let journal = Journal::new(journalfile, now, "import");
+    let lock = journal.lock()?;
+    let source = opt.file.display().to_string();
+    let mut changes = Vec::new();
+    let mut overlaps = Vec::new();
+    for (date, records) in days {
+        let day = storage.load_day(&date)?;
+        match merge_day(&day.lines, &log_lines(&records), &source) {
+            MergedDay::Unchanged => (),
+            MergedDay::Merged(lines) => changes.push((date, lines)),
+            MergedDay::Conflict(lines) => {
+                overlaps.push(date);
+                changes.push((date, lines));
+            }
+        }
+    }
+    if !overlaps.is_empty() && !opt.merge {
+        for date in overlaps.iter() {
+            eprintln!(
+                "{}: the imported entries overlap with other activities",
+                storage.path(date).display()
+            );
+        }
+        println!("nothing imported, use --merge to import with conflict markers");
+        return Ok(1);
+    }
+    for (date, lines) in changes.iter() {
+        storage.rewrite_journaled(date, lines, &journal, &lock)?;
+        match overlaps.contains(date) {
+            true => println!("conflict in {}", storage.path(date).display()),
+            false => println!("{}: imported", date),
+        }
+    }
+    match overlaps.len() {
+        0 => Ok(0),
+        conflicts => {
+            println!(
+                "found {} conflicts, `tt check` shows them until they are resolved",
+                conflicts
+            );
+            Ok(1)
+        }
+    }
+}
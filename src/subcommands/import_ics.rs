@@ -0,0 +1,464 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::result::Result;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime};
+use structopt::StructOpt;
+
+use crate::collector::BlockCollector;
+use crate::configfile::{ImportIcsConfig, TTConfig};
+use crate::error::{TTError, TTErrorKind};
+use crate::export::ics::{read_events, Event};
+use crate::journal::Journal;
+use crate::log_parser::{is_break, Block, BlockData};
+use crate::storage::Storage;
+use crate::subcommands::merge::interleave;
+use crate::utils::{self, FileProxy};
+
+/// the metadata key of the meetings in the log, its value is the id of the event
+pub const ICS_KEY: &str = "ics";
+
+#[derive(StructOpt, Debug)]
+pub struct ImportIcsOpt {
+    #[structopt(parse(from_os_str))]
+    /// the calendar, an iCalendar file like the export of a calendar app
+    pub file: PathBuf,
+
+    #[structopt(long)]
+    /// first day to add meetings to, default is today
+    pub from: Option<NaiveDate>,
+
+    #[structopt(long)]
+    /// last day to add meetings to, default is today
+    pub to: Option<NaiveDate>,
+
+    #[structopt(short = "n", long)]
+    /// only print the entries that would be added
+    pub dry_run: bool,
+}
+
+/// the log of a day with the meetings added
+#[derive(Debug, PartialEq)]
+pub struct AddedMeetings {
+    /// all lines of the day
+    pub lines: Vec<String>,
+    /// the new lines
+    pub added: Vec<String>,
+    /// why meetings have not been added
+    pub skipped: Vec<String>,
+    /// the number of meetings that have been added
+    pub started: usize,
+    /// the number of meetings that have been added before they were over and got their end now
+    pub ended: usize,
+}
+
+// interface for the runner, adds the meetings of the calendar to the log
+pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    opt: ImportIcsOpt,
+    now: &DateTime<Local>,
+    storage: &dyn Storage<R, W, F>,
+    journalfile: &F,
+) -> Result<i32, TTError> {
+    let journal = Journal::new(journalfile, now, "import-ics");
+    let now = now.naive_local();
+    let today = utils::workday(&now);
+    let to = opt.to.unwrap_or(today);
+    let from = opt.from.unwrap_or(to);
+    if from > to {
+        return Err(TTError::new(TTErrorKind::UsageError(
+            "the first day to import is after the last day",
+        )));
+    }
+    let text =
+        fs::read_to_string(&opt.file).map_err(|err| TTError::from(err).in_file(&opt.file))?;
+    // meetings that have not started yet are not in the log
+    let until = (to + Duration::days(1))
+        .and_time(utils::day_change())
+        .min(now);
+    let (events, warnings) = read_events(&text, &from.and_time(utils::day_change()), &until)
+        .map_err(|err| err.in_file(&opt.file))?;
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+    let mut days: BTreeMap<NaiveDate, Vec<Event>> = BTreeMap::new();
+    for event in events {
+        days.entry(utils::workday(&event.start))
+            .or_default()
+            .push(event);
+    }
+
+    let config = TTConfig::get();
+    let lock = journal.lock()?;
+    for (date, events) in days {
+        let day = storage.load_day(&date)?;
+        let meetings = add_meetings(&day.lines, &events, &config.import_ics, &now);
+        for notice in meetings.skipped.iter() {
+            println!("{}: {}", date, notice);
+        }
+        if opt.dry_run {
+            for line in meetings.added.iter() {
+                println!("{}: {}", date, line);
+            }
+        } else if !meetings.added.is_empty() {
+            storage.rewrite_journaled(&date, &meetings.lines, &journal, &lock)?;
+            match (meetings.started, meetings.ended) {
+                (started, 0) => println!("{}: added {} meetings", date, started),
+                (0, ended) => println!("{}: added the end of {} meetings", date, ended),
+                (started, ended) => println!(
+                    "{}: added {} meetings and the end of {} meetings",
+                    date, started, ended
+                ),
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// the activity of a meeting: the first rule that matches its summary, or the default activity.
+/// Whitespace in the activity becomes a dash.
+pub fn meeting_activity(config: &ImportIcsConfig, summary: &str) -> Option<String> {
+    let activity = config
+        .rules
+        .iter()
+        .find_map(|rule| {
+            let captures = rule.pattern.captures(summary)?;
+            let mut activity = String::new();
+            captures.expand(&rule.activity, &mut activity);
+            Some(activity)
+        })
+        .or_else(|| config.default_activity.clone())?;
+    let words: Vec<&str> = activity.split_whitespace().collect();
+    match words.is_empty() {
+        true => None,
+        false => Some(words.join("-")),
+    }
+}
+
+// whether the line starts the meeting: the same time and the id of the event in ICS_KEY
+fn is_meeting_start(line: &str, meeting: &BlockData) -> bool {
+    match Block::from_line(Ok(line.to_string())) {
+        Ok(Block::NormalBlock(data)) => {
+            data.start == meeting.start
+                && data.metadata.contains_key(ICS_KEY)
+                && data.metadata.get(ICS_KEY) == meeting.metadata.get(ICS_KEY)
+        }
+        _ => false,
+    }
+}
+
+// the time of the line, if it has one
+fn line_time(line: &str) -> Option<Duration> {
+    let word = line.split_whitespace().next()?;
+    let (time, _) = utils::parse_time_with_offset(word).ok()?;
+    Some(utils::workday_offset(&time))
+}
+
+// the activity that is going on at the time in the log
+fn activity_at(lines: &[String], at: &Duration) -> Option<BlockData> {
+    let mut collector = BlockCollector::new();
+    let mut time = Duration::zero();
+    for (index, line) in lines.iter().enumerate() {
+        time = line_time(line).unwrap_or(time);
+        if time > *at {
+            break;
+        }
+        collector.add_line(index + 1, Ok(line.to_string()));
+    }
+    collector.last().cloned()
+}
+
+/// adds the meetings to the log of a day: the meeting starts with its activity, the id of the
+/// event as metadata `ics=<id>` and the summary as note. When it is over, the activity before it is resumed or the break goes on.
+/// Meetings are skipped if the log already has entries during them, or if the meeting's activity
+/// is already going on when it starts. A meeting that has been added before it was over only
+/// gets its end.
+pub fn add_meetings(
+    lines: &[String],
+    events: &[Event],
+    config: &ImportIcsConfig,
+    now: &NaiveDateTime,
+) -> AddedMeetings {
+    let mut lines = lines.to_vec();
+    let mut added: Vec<String> = Vec::new();
+    let mut skipped = Vec::new();
+    // the ends of the meetings that have been added
+    let mut ends: Vec<String> = Vec::new();
+    let (mut started, mut ended_now) = (0, 0);
+    for event in events {
+        let (start, end) = (event.start.time(), event.end.time());
+        let activity = match meeting_activity(config, &event.summary) {
+            Some(activity) => activity,
+            None => {
+                skipped.push(format!(
+                    "no activity for \"{}\" at {}",
+                    event.summary,
+                    utils::format_time(&start)
+                ));
+                continue;
+            }
+        };
+        let note = event
+            .summary
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let line = format!(
+            "{} {} {}={} -- {}",
+            utils::format_time(&start),
+            activity,
+            ICS_KEY,
+            event.id(),
+            note
+        );
+        let data = match Block::from_line(Ok(line)) {
+            Ok(Block::NormalBlock(data)) => data,
+            _ => {
+                skipped.push(format!(
+                    "\"{}\" is not an activity for \"{}\"",
+                    activity, event.summary
+                ));
+                continue;
+            }
+        };
+        let (start_key, end_key) = (utils::workday_offset(&start), utils::workday_offset(&end));
+        let during = lines
+            .iter()
+            .filter_map(|line| line_time(line))
+            .any(|time| start_key < time && time < end_key);
+        // the start of the meeting from an import before it was over
+        let own = lines.iter().position(|line| is_meeting_start(line, &data));
+        let before = match own {
+            Some(position) => {
+                let mut others = lines.clone();
+                others.remove(position);
+                activity_at(&others, &start_key)
+            }
+            None => activity_at(&lines, &start_key),
+        };
+        let ended = event.end <= *now
+            && end_key > start_key
+            && !lines.iter().any(|line| line_time(line) == Some(end_key));
+        let in_log = match own {
+            Some(_) => during || !ended,
+            None => {
+                during || before.as_ref().map(|before| &before.activity) == Some(&data.activity)
+            }
+        };
+        if in_log {
+            skipped.push(format!(
+                "\"{}\" at {} is already in the log",
+                event.summary,
+                utils::format_time(&start)
+            ));
+            continue;
+        }
+        let mut new = Vec::new();
+        if own.is_none() {
+            // the end of a meeting right before this one is the start of this one
+            if let Some(position) = lines
+                .iter()
+                .position(|line| ends.contains(line) && line_time(line) == Some(start_key))
+            {
+                let line = lines.remove(position);
+                added.retain(|added| *added != line);
+                ends.retain(|end| *end != line);
+            }
+            new.push(Block::NormalBlock(data).to_string(&start));
+            started += 1;
+        } else {
+            ended_now += 1;
+        }
+        if ended {
+            let resumed = before.filter(|before| !is_break(&before.activity));
+            new.push(match resumed {
+                Some(before) => {
+                    let tags = before
+                        .tags
+                        .iter()
+                        .filter(|tag| !tag.starts_with("resume:"))
+                        .cloned()
+                        .collect();
+                    let data = BlockData {
+                        start: end,
+                        tags,
+                        note: None,
+                        utc_offset: None,
+                        start_seconds: false,
+                        ..before
+                    };
+                    Block::NormalBlock(data).to_string(&end)
+                }
+                None => format!("{} break", utils::format_time(&end)),
+            });
+            ends.extend(new.last().cloned());
+        }
+        lines = interleave(&lines, &new);
+        added.extend(new);
+    }
+    AddedMeetings {
+        lines,
+        added,
+        skipped,
+        started,
+        ended: ended_now,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    fn event(summary: &str, start: (u32, u32), end: (u32, u32)) -> Event {
+        let day = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        Event {
+            uid: format!("{}@example.com", summary.to_lowercase().replace(' ', "-")),
+            summary: summary.to_string(),
+            start: day.and_hms_opt(start.0, start.1, 0).unwrap(),
+            end: day.and_hms_opt(end.0, end.1, 0).unwrap(),
+        }
+    }
+
+    fn config() -> ImportIcsConfig {
+        let config = "[import-ics]\n\
+            rules = [{ pattern = \"^Daily\", activity = \"_daily\" },\n\
+                     { pattern = \"(JIRA-\\\\d+)\", activity = \"$1 review\" }]\n\
+            default-activity = \"meeting\"\n";
+        TTConfig::init(config.as_bytes()).unwrap();
+        TTConfig::get().import_ics.clone()
+    }
+
+    #[test]
+    fn activities_of_the_rules() {
+        let config = config();
+        let activity = |summary| meeting_activity(&config, summary);
+        assert_eq!(activity("Daily standup"), Some("_daily".to_string()));
+        assert_eq!(
+            activity("Go through JIRA-12"),
+            Some("JIRA-12-review".to_string())
+        );
+        assert_eq!(activity("Lunch with Anna"), Some("meeting".to_string()));
+        let without_default = ImportIcsConfig {
+            default_activity: None,
+            ..config
+        };
+        assert_eq!(meeting_activity(&without_default, "Lunch"), None);
+    }
+
+    #[test]
+    fn resumed_after_the_meeting() {
+        let log = lines(&["08:00 email =mail resume:1", "12:00 break"]);
+        let events = [
+            event("Daily standup", (9, 0), (9, 15)),
+            event("JIRA-7 planning", (9, 15), (10, 0)),
+            event("Lunch", (12, 30), (13, 0)),
+        ];
+        let now = NaiveDate::from_ymd_opt(2020, 6, 1)
+            .unwrap()
+            .and_hms_opt(17, 0, 0)
+            .unwrap();
+        let meetings = add_meetings(&log, &events, &config(), &now);
+        assert_eq!(
+            meetings.lines,
+            lines(&[
+                "08:00 email =mail resume:1",
+                "09:00 _daily ics=2007a54b -- Daily standup",
+                "09:15 JIRA-7-review ics=96fba388 -- JIRA-7 planning",
+                "10:00 email =mail",
+                "12:00 break",
+                "12:30 meeting ics=64473a1e -- Lunch",
+                "13:00 break",
+            ])
+        );
+        assert_eq!(meetings.added.len(), 5);
+        assert_eq!((meetings.started, meetings.ended), (3, 0));
+        assert!(meetings.skipped.is_empty());
+    }
+
+    #[test]
+    fn skipped_meetings() {
+        let log = lines(&[
+            "08:00 email",
+            "09:00 meeting -- Lunch",
+            "09:30 coding",
+            "10:00 break",
+        ]);
+        let events = [
+            // already added
+            event("Lunch", (9, 0), (9, 30)),
+            // the log has entries during it
+            event("Long meeting", (8, 30), (9, 45)),
+            // not over yet, the end is added later
+            event("Late", (16, 30), (18, 0)),
+        ];
+        let now = NaiveDate::from_ymd_opt(2020, 6, 1)
+            .unwrap()
+            .and_hms_opt(17, 0, 0)
+            .unwrap();
+        let meetings = add_meetings(&log, &events, &config(), &now);
+        assert_eq!(
+            meetings.added,
+            lines(&["16:30 meeting ics=e5300ffc -- Late"])
+        );
+        assert_eq!(meetings.skipped.len(), 2);
+        assert_eq!(
+            meetings.lines.last().unwrap(),
+            "16:30 meeting ics=e5300ffc -- Late"
+        );
+
+        // still not over
+        let again = add_meetings(&meetings.lines, &events, &config(), &now);
+        assert!(again.added.is_empty());
+        assert_eq!(again.skipped.len(), 3);
+    }
+
+    #[test]
+    fn ended_on_the_next_run() {
+        let log = lines(&["08:00 email =mail", "09:00 meeting ics=e5300ffc -- Late"]);
+        let events = [event("Late", (9, 0), (10, 0))];
+        let during = NaiveDate::from_ymd_opt(2020, 6, 1)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap();
+        assert!(add_meetings(&log, &events, &config(), &during)
+            .added
+            .is_empty());
+
+        let now = NaiveDate::from_ymd_opt(2020, 6, 1)
+            .unwrap()
+            .and_hms_opt(19, 0, 0)
+            .unwrap();
+        let meetings = add_meetings(&log, &events, &config(), &now);
+        assert_eq!(meetings.added, lines(&["10:00 email =mail"]));
+        assert_eq!((meetings.started, meetings.ended), (0, 1));
+        assert_eq!(
+            meetings.lines,
+            lines(&[
+                "08:00 email =mail",
+                "09:00 meeting ics=e5300ffc -- Late",
+                "10:00 email =mail"
+            ])
+        );
+        // the end is only added once
+        let again = add_meetings(&meetings.lines, &events, &config(), &now);
+        assert!(again.added.is_empty());
+
+        let log = lines(&["10:00 break", "16:30 meeting ics=e5300ffc -- Late"]);
+        let events = [event("Late", (16, 30), (18, 0))];
+        let meetings = add_meetings(&log, &events, &config(), &now);
+        assert_eq!(meetings.added, lines(&["18:00 break"]));
+
+        // the meeting is found by its id, also with another note
+        let log = lines(&[
+            "10:00 break",
+            "16:30 meeting ics=e5300ffc -- Late, with Anna",
+        ]);
+        let meetings = add_meetings(&log, &events, &config(), &now);
+        assert_eq!(meetings.added, lines(&["18:00 break"]));
+    }
+}
@@ -1,18 +1,24 @@
+use crate::activities::{self, ActivityMap};
 use crate::error::{TTError, TTErrorKind};
-use crate::log_parser::{is_distributable, is_start, Block, BlockData};
-use crate::utils::FileProxy;
+use crate::journal::Journal;
+use crate::log_parser::{
+    is_distributable, is_start, split_note, split_tags, Block, BlockData, Metadata,
+};
+use crate::storage::Storage;
+use crate::utils::{self, FileLock, FileProxy};
 use crate::{collector, subcommands};
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local};
 use itertools::Itertools;
 use std::io::{BufRead, Write};
 use text_io::read;
 
 pub(crate) fn run<'a, R: BufRead, W: Write, F: FileProxy<R, W>>(
     now: &DateTime<Local>,
-    default_logfile: &F,
+    storage: &dyn Storage<R, W, F>,
     activitiesfile: &F,
+    journalfile: &F,
 ) -> Result<i32, TTError> {
-    interactive(now, default_logfile, activitiesfile)?;
+    interactive(now, storage, activitiesfile, journalfile)?;
     Ok(0)
 }
 
@@ -26,26 +32,48 @@ pub(crate) fn run<'a, R: BufRead, W: Write, F: FileProxy<R, W>>(
 // return -> provide summary with numbers and query again.
 fn interactive<R: BufRead, W: Write, F: FileProxy<R, W>>(
     now: &DateTime<Local>,
-    default_logfile: &F,
+    storage: &dyn Storage<R, W, F>,
     activitiesfile: &F,
+    journalfile: &F,
 ) -> Result<(), TTError> {
-    let timestamp = now.time();
-    let activity_map = subcommands::add::read_activities(activitiesfile.reader()?)
-        .expect("Cannot read config file");
-    let collected =
-        match collector::collect_blocks(default_logfile.reader()?.lines(), Some(&timestamp)) {
-            Ok(Some(collected)) => Some(collected),
-            Ok(None) => None,
-            Err(err) => return Err(err),
-        };
+    let journal = Journal::new(journalfile, now, "interactive");
+    let timestamp = utils::without_seconds(&now.time());
+    let activity_map =
+        activities::read_activities(activitiesfile.reader()?).expect("Cannot read config file");
+    let workday = utils::workday(&now.naive_local());
+    let previous_day = storage.load_day(&(workday - Duration::days(1)))?;
+    let day = storage.load_day(&workday)?;
+    // the reply is read without the lock, so that other commands don't wait for it. What is
+    // added depends on the day as it is shown, so it must not have changed in the meantime.
+    let lock_unchanged = || -> Result<(FileLock, ActivityMap), TTError> {
+        let lock = journal.lock()?;
+        if storage.load_day(&(workday - Duration::days(1)))? != previous_day
+            || storage.load_day(&workday)? != day
+        {
+            return Err(TTError::new(TTErrorKind::UsageError(
+                "the log has been changed in the meantime, aborting.",
+            ))
+            .context("in interactive mode".to_string()));
+        }
+        let activity_map = activities::read_activities(activitiesfile.reader()?)
+            .map_err(|err| err.in_file(activitiesfile.pathname()))?;
+        Ok((lock, activity_map))
+    };
+    let (collected, diagnostics) = collector::collect_workday(
+        &workday,
+        previous_day.iter_lines(),
+        day.iter_lines(),
+        Some(&timestamp),
+    );
+    subcommands::report::print_diagnostics(&storage.path(&workday), diagnostics);
     let really = collected
         .as_ref()
         .map_or(false, |c| is_start(&c.final_activity));
     println!("{:?}", activity_map);
     let activities_sorted: Vec<_> = activity_map
         .iter()
-        .filter(|(k, (v, _tags))| *k != v)
-        .sorted()
+        .filter(|(k, activity)| activity.shortname.as_ref() == Some(*k) && !activity.archived)
+        .sorted_by_key(|(k, _activity)| *k)
         .enumerate()
         .collect();
     if let Some(real_collected) = &collected {
@@ -70,19 +98,25 @@ fn interactive<R: BufRead, W: Write, F: FileProxy<R, W>>(
             .and_then(|i| Ok(activities_sorted.get(i)))
         {
             Ok(Some((_i, (k, _v)))) => {
+                let (_lock, activity_map) = lock_unchanged()?;
                 let data = BlockData {
                     start: timestamp,
                     activity: k.to_string(),
                     tags: vec![],
+                    metadata: Metadata::new(),
+                    note: None,
                     distribute: false,
+                    utc_offset: None,
+                    start_seconds: false,
                 };
                 subcommands::add::add(
                     Block::from_data(data, really),
                     Some(&activity_map),
                     activitiesfile,
-                    default_logfile,
+                    storage,
                     &timestamp,
                     now,
+                    &journal,
                 )
             }
             _ => Err(TTError::new(TTErrorKind::UsageError(
@@ -91,11 +125,14 @@ fn interactive<R: BufRead, W: Write, F: FileProxy<R, W>>(
             .context(format!("in interactive mode, interpreting '{}", cmd))),
         },
         'r' => {
-            let resume_stack =
-                subcommands::resume::find_resume_activities(default_logfile.reader()?);
-            for (i, (activity, tags, _offset)) in resume_stack.iter().enumerate() {
-                if !is_start(activity) {
-                    println!("{:2>} {} {}", i, activity, tags.join(" "));
+            // the problems in the log have already been shown above
+            let (resume_stack, _diagnostics) = subcommands::resume::find_resume_activities(
+                previous_day.iter_lines(),
+                day.iter_lines(),
+            );
+            for (i, (data, _offset)) in resume_stack.iter().enumerate() {
+                if !is_start(&data.activity) {
+                    println!("{:2>} {} {}", i, data.activity, data.tags.join(" "));
                 }
             }
             print!("Resume which? ");
@@ -103,33 +140,43 @@ fn interactive<R: BufRead, W: Write, F: FileProxy<R, W>>(
             let n = cmd
                 .parse::<usize>()
                 .expect("I did not understand. Aborting.");
+            let _lock = lock_unchanged()?;
             subcommands::resume::resume(
                 activitiesfile,
-                default_logfile,
+                storage,
                 now,
                 Some(timestamp),
                 n,
                 really,
+                &journal,
             )
         }
-        'e' => subcommands::edit::edit(default_logfile.pathname()),
+        'e' => subcommands::edit::edit(&storage.path(&workday)),
         'a' => subcommands::edit::edit(activitiesfile.pathname()),
         '+' | '_' => {
-            let words: Vec<String> = cmd.split(" ").map(str::to_string).collect();
-            let (activity, tags) = (&words[0], &words[1..]);
+            let (entry, note) = split_note(&cmd);
+            let words: Vec<&str> = entry.split_whitespace().collect();
+            let activity = words[0];
+            let (tags, metadata) = split_tags(words[1..].iter().copied());
+            let (_lock, activity_map) = lock_unchanged()?;
             let data = BlockData {
                 start: timestamp,
                 activity: activity.to_string(),
-                tags: tags.to_vec(),
+                tags,
+                metadata,
+                note: note.map(str::to_string),
                 distribute: is_distributable(activity),
+                utc_offset: None,
+                start_seconds: false,
             };
             subcommands::add::add(
                 Block::from_data(data, really),
                 Some(&activity_map),
                 activitiesfile,
-                default_logfile,
+                storage,
                 &timestamp,
                 now,
+                &journal,
             )
         }
         _ => match collected {
@@ -146,19 +193,25 @@ fn interactive<R: BufRead, W: Write, F: FileProxy<R, W>>(
                 let reply: String = read!("{}\n");
                 match reply.parse::<usize>().and_then(|i| Ok(report_lines.get(i))) {
                     Ok(Some((_line, activity))) => {
+                        let (_lock, activity_map) = lock_unchanged()?;
                         let block = Block::NormalBlock(BlockData {
                             start: timestamp,
                             activity: format!("+{}", activity),
                             tags: vec![],
+                            metadata: Metadata::new(),
+                            note: None,
                             distribute: false,
+                            utc_offset: None,
+                            start_seconds: false,
                         });
                         subcommands::add::add(
                             block,
                             Some(&activity_map),
                             activitiesfile,
-                            default_logfile,
+                            storage,
                             &timestamp,
                             now,
+                            &journal,
                         )
                     }
                     _ => Err(TTError::new(TTErrorKind::UsageError(
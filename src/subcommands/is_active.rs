@@ -1,7 +1,8 @@
-use crate::collector::collect_blocks;
+use crate::collector::collect_workday;
 use crate::error::TTError;
-use crate::utils::FileProxy;
-use chrono::{DateTime, Local, NaiveTime};
+use crate::storage::Storage;
+use crate::utils::{self, FileProxy};
+use chrono::{DateTime, Duration, Local, NaiveDateTime};
 use std::io;
 use std::io::{BufRead, Write};
 
@@ -11,19 +12,32 @@ use std::io::{BufRead, Write};
 // public interface is the function add()
 pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
     now: &DateTime<Local>,
-    default_logfile: &F,
+    storage: &dyn Storage<R, W, F>,
     _activitiesfile: &F,
 ) -> Result<i32, TTError> {
-    match is_active(&now.naive_local().time(), default_logfile.reader()?.lines())? {
+    let workday = utils::workday(&now.naive_local());
+    let days = storage.load(&(workday - Duration::days(1)), &workday)?;
+    match is_active(
+        &now.naive_local(),
+        days[0].iter_lines(),
+        days[1].iter_lines(),
+    )? {
         false => Ok(1),
         true => Ok(0),
     }
 }
 pub fn is_active<T: Iterator<Item = io::Result<String>>>(
-    add_endit_at: &NaiveTime,
+    now: &NaiveDateTime,
+    previous_logfile: T,
     logfile: T,
 ) -> Result<bool, TTError> {
-    let collected = collect_blocks(logfile, Some(add_endit_at))?;
+    // problems in the log are reported elsewhere, this is for scripts
+    let (collected, _diagnostics) = collect_workday(
+        &utils::workday(now),
+        previous_logfile,
+        logfile,
+        Some(&now.time()),
+    );
     match collected {
         None => Ok(false),
         Some(collected) if collected.final_activity == "break" => Ok(false),
@@ -35,22 +49,77 @@ pub fn is_active<T: Iterator<Item = io::Result<String>>>(
 mod tests {
     use super::*;
     use crate::utils::setup_line_reader;
+    use chrono::NaiveDate;
 
     #[test]
     fn end_with_break() {
         let lines = setup_line_reader(vec!["8:30 start", "10:00 break"]);
-        assert!(!is_active(&NaiveTime::from_hms(11, 0, 0), lines).unwrap());
+        assert!(!is_active(
+            &NaiveDate::from_ymd_opt(2020, 6, 16)
+                .unwrap()
+                .and_hms_opt(11, 0, 0)
+                .unwrap(),
+            setup_line_reader(vec![]),
+            lines
+        )
+        .unwrap());
     }
 
     #[test]
     fn end_not_with_break() {
         let lines = setup_line_reader(vec!["8:30 start"]);
-        assert!(is_active(&NaiveTime::from_hms(11, 0, 0), lines).unwrap());
+        assert!(is_active(
+            &NaiveDate::from_ymd_opt(2020, 6, 16)
+                .unwrap()
+                .and_hms_opt(11, 0, 0)
+                .unwrap(),
+            setup_line_reader(vec![]),
+            lines
+        )
+        .unwrap());
     }
 
     #[test]
     fn empty() {
         let lines = setup_line_reader(vec![]);
-        assert!(!is_active(&NaiveTime::from_hms(11, 0, 0), lines).unwrap());
+        assert!(!is_active(
+            &NaiveDate::from_ymd_opt(2020, 6, 16)
+                .unwrap()
+                .and_hms_opt(11, 0, 0)
+                .unwrap(),
+            setup_line_reader(vec![]),
+            lines
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn open_since_previous_day() {
+        let previous_lines = setup_line_reader(vec!["8:30 start", "22:00 oncall"]);
+        let lines = setup_line_reader(vec![]);
+        assert!(is_active(
+            &NaiveDate::from_ymd_opt(2020, 6, 16)
+                .unwrap()
+                .and_hms_opt(1, 0, 0)
+                .unwrap(),
+            previous_lines,
+            lines
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn ended_after_midnight() {
+        let previous_lines = setup_line_reader(vec!["22:00 oncall"]);
+        let lines = setup_line_reader(vec!["0:30 break"]);
+        assert!(!is_active(
+            &NaiveDate::from_ymd_opt(2020, 6, 16)
+                .unwrap()
+                .and_hms_opt(1, 0, 0)
+                .unwrap(),
+            previous_lines,
+            lines
+        )
+        .unwrap());
     }
 }
@@ -1,19 +1,30 @@
+use crate::activities::{activity_lines, read_activity_list, FileFormat};
 use crate::error::TTError;
 use crate::utils::FileProxy;
-use chrono::{DateTime, Local};
 use std::io::{BufRead, Write};
 
 pub fn run<'a, R: BufRead, W: Write, F: FileProxy<R, W>>(
-    _now: &DateTime<Local>,
-    _default_logfile: &F,
     activitiesfile: &F,
 ) -> Result<i32, TTError> {
-    list_activities(activitiesfile.reader()?);
+    list_activities(activitiesfile.reader()?)
+        .map_err(|err| err.in_file(activitiesfile.pathname()))?;
     Ok(0)
 }
 
-pub fn list_activities(activitiesfile: impl BufRead) {
-    for line in activitiesfile.lines() {
-        println!("{}", line.unwrap());
+// the activities like lines of the activities file, with their description as note after the
+// preferred shortname. Archived activities are not listed.
+pub fn list_activities(activitiesfile: impl BufRead) -> Result<(), TTError> {
+    let (_, activities) = read_activity_list(activitiesfile)?;
+    for activity in activities.into_iter().filter(|activity| !activity.archived) {
+        for (index, line) in activity_lines(&activity, FileFormat::Lines)
+            .iter()
+            .enumerate()
+        {
+            match (index, &activity.description) {
+                (0, Some(description)) => println!("{} -- {}", line, description),
+                _ => println!("{}", line),
+            }
+        }
     }
+    Ok(())
 }
@@ -0,0 +1,522 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::result::Result;
+
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use structopt::StructOpt;
+
+use crate::activities::{activity_lines, activity_map, read_activity_list, CONFLICT_MARKER};
+use crate::activities::{Activity, FileFormat};
+use crate::archive::read_archive;
+use crate::error::TTError;
+use crate::journal::Journal;
+use crate::log_parser::{is_break, Block, BlockData};
+use crate::storage::{split_dated_line, Storage};
+use crate::subcommands::migrate::find_logfiles;
+use crate::utils::{self, FileProxy, NamedFile};
+
+#[derive(StructOpt, Debug)]
+pub struct MergeOpt {
+    #[structopt(parse(from_os_str))]
+    /// the data directory of the other machine, like a copy of its ~/.tt
+    pub other: PathBuf,
+}
+
+/// the result of merging the log of a day
+#[derive(Debug, PartialEq)]
+pub enum MergedDay {
+    /// the other side has nothing new
+    Unchanged,
+    /// the lines of both sides by time, without duplicates and without the breaks that end an
+    /// activity while the other side still works on it
+    Merged(Vec<String>),
+    /// both sides with conflict markers around them, for `tt check` to find
+    Conflict(Vec<String>),
+}
+
+// interface for the runner, merges the other data directory into this one.
+// Returns 1 if there are conflicts.
+pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    opt: MergeOpt,
+    now: &DateTime<Local>,
+    storage: &dyn Storage<R, W, F>,
+    activitiesfile: &F,
+    journalfile: &F,
+) -> Result<i32, TTError> {
+    let journal = Journal::new(journalfile, now, "merge");
+    let lock = journal.lock()?;
+    let other_name = opt.other.display().to_string();
+    let mut conflicts = 0;
+
+    let other_file = opt.other.join("activities");
+    let (_, other_activities) = read_activity_list(NamedFile::new(other_file.clone()).reader()?)
+        .map_err(|err| err.in_file(&other_file))?;
+    let (format, activities) = read_activity_list(activitiesfile.reader()?)
+        .map_err(|err| err.in_file(activitiesfile.pathname()))?;
+    let (mut added, mut differing) = merge_activities(&activities, &other_activities);
+    if format == FileFormat::Toml {
+        // another definition of a known activity, like another shortname, is a conflict in TOML
+        let (again, new): (Vec<Activity>, Vec<Activity>) = added
+            .into_iter()
+            .partition(|activity| activities.iter().any(|known| known.name == activity.name));
+        differing.extend(
+            again
+                .into_iter()
+                .filter(|activity| *activity != Activity::new(&activity.name)),
+        );
+        added = new;
+    }
+    for activity in added.iter() {
+        for line in activity_lines(activity, format) {
+            journal.append_line(activitiesfile, &line)?;
+        }
+    }
+    // the other definition is kept as a marked comment, the one here stays in use
+    for activity in differing.iter() {
+        let line = conflict_line(activity, format, &other_name);
+        journal.append_line(activitiesfile, &line)?;
+        println!(
+            "conflict in {}: {}",
+            activitiesfile.pathname().display(),
+            line
+        );
+        conflicts += 1;
+    }
+
+    for (date, other_lines) in read_store(&opt.other)? {
+        let day = storage.load_day(&date)?;
+        match merge_day(&day.lines, &other_lines, &other_name) {
+            MergedDay::Unchanged => (),
+            MergedDay::Merged(lines) => {
+                storage.rewrite_journaled(&date, &lines, &journal, &lock)?;
+                println!("{}: merged", date);
+            }
+            MergedDay::Conflict(lines) => {
+                storage.rewrite_journaled(&date, &lines, &journal, &lock)?;
+                println!("conflict in {}", storage.path(&date).display());
+                conflicts += 1;
+            }
+        }
+    }
+    if conflicts > 0 {
+        println!(
+            "found {} conflicts, `tt check` shows them until they are resolved",
+            conflicts
+        );
+    }
+    match conflicts {
+        0 => Ok(0),
+        _ => Ok(1),
+    }
+}
+
+/// the line for the activities file with the definition of the other machine that conflicts
+/// with one here, see CONFLICT_MARKER
+/// ```
+/// use timetracker::activities::{read_activities, Activity, FileFormat};
+/// use timetracker::subcommands::merge::conflict_line;
+/// let jf = Activity {
+///     shortname: Some("jf".to_string()),
+///     ..Activity::new("jour-fixe")
+/// };
+/// let line = conflict_line(&jf, FileFormat::Lines, "laptop");
+/// assert_eq!(line, "# merge conflict with laptop: jf jour-fixe");
+/// let activities = read_activities(format!("jf jira\n{}\n", line).as_bytes()).unwrap();
+/// assert_eq!(activities["jf"].name, "jira");
+/// ```
+pub fn conflict_line(activity: &Activity, format: FileFormat, other_name: &str) -> String {
+    format!(
+        "{} with {}: {}",
+        CONFLICT_MARKER,
+        other_name,
+        activity_lines(activity, format).join(" ")
+    )
+}
+
+/// the logs of all days in a data directory: daily files in either layout, archives and the
+/// single file
+pub fn read_store(dir: &Path) -> Result<BTreeMap<NaiveDate, Vec<String>>, TTError> {
+    let mut days: BTreeMap<NaiveDate, Vec<String>> = BTreeMap::new();
+    if dir.join("archive").is_dir() {
+        for entry in fs::read_dir(dir.join("archive"))? {
+            let path = entry?.path();
+            for (date, lines) in
+                read_archive(&NamedFile::new(path.clone())).map_err(|err| err.in_file(&path))?
+            {
+                days.entry(date).or_default().extend(lines);
+            }
+        }
+    }
+    for (date, path) in find_logfiles(dir)? {
+        for line in NamedFile::new(path).reader()?.lines() {
+            days.entry(date).or_default().push(line?);
+        }
+    }
+    for line in NamedFile::new(dir.join("log")).reader()?.lines() {
+        if let Some((date, line)) = split_dated_line(&line?) {
+            days.entry(date).or_default().push(line.to_string());
+        }
+    }
+    Ok(days)
+}
+
+/// the activities of the other activities file that are new here, and the ones that are
+/// defined differently: the same activity with other attributes, or a shortname that is used
+/// for another activity here
+/// ```
+/// use timetracker::activities::read_activity_list;
+/// use timetracker::subcommands::merge::merge_activities;
+/// let (_, ours) = read_activity_list("mail email\njour jour-fixe\n".as_bytes()).unwrap();
+/// let (_, theirs) = read_activity_list("mail email\nmtg meeting\njour jf\n".as_bytes()).unwrap();
+/// let (added, differing) = merge_activities(&ours, &theirs);
+/// assert_eq!(added[0].name, "meeting");
+/// assert_eq!(differing[0].name, "jf");
+/// ```
+pub fn merge_activities(ours: &[Activity], theirs: &[Activity]) -> (Vec<Activity>, Vec<Activity>) {
+    let known = activity_map(ours);
+    let mut added: Vec<Activity> = Vec::new();
+    let mut differing = Vec::new();
+    for activity in theirs {
+        if ours.contains(activity) || added.contains(activity) {
+            continue;
+        }
+        let same_key = |key: &String| known.get(key);
+        let conflicts = activity
+            .shortname
+            .iter()
+            .filter_map(same_key)
+            .any(|other| other.name != activity.name)
+            || same_key(&activity.name).is_some_and(|other| {
+                other.shortname == activity.shortname || other.name != activity.name
+            });
+        match conflicts {
+            true => differing.push(activity.clone()),
+            false => added.push(activity.clone()),
+        }
+    }
+    (added, differing)
+}
+
+// the place of each line on the timeline of the day: its log time, lines without one follow the
+// line before them
+fn timeline_keys(lines: &[String]) -> Vec<Duration> {
+    let mut key = Duration::zero();
+    lines
+        .iter()
+        .map(|line| {
+            let time = line
+                .split_whitespace()
+                .next()
+                .and_then(|word| utils::parse_time_with_offset(word).ok());
+            if let Some((time, _)) = time {
+                key = utils::workday_offset(&time);
+            }
+            key
+        })
+        .collect()
+}
+
+// the entry of a line in a form to compare it: the order of the tags and whether the start
+// is written with seconds do not matter. None for lines that are no entry, like comments.
+fn entry(line: &str) -> Option<Block> {
+    let normalise = |mut data: BlockData| {
+        data.tags.sort();
+        data.start_seconds = false;
+        data
+    };
+    match Block::from_line(Ok(line.to_string())).ok()? {
+        Block::NormalBlock(data) => Some(Block::NormalBlock(normalise(data))),
+        Block::ReallyBlock(data) => Some(Block::ReallyBlock(normalise(data))),
+        Block::TrackBlock(track, data) => Some(Block::TrackBlock(track, normalise(data))),
+        Block::DurationBlock(duration, data) => {
+            Some(Block::DurationBlock(duration, normalise(data)))
+        }
+        Block::TimeCorrection(time) => Some(Block::TimeCorrection(time)),
+        Block::CommentBlock(_) => None,
+    }
+}
+
+// whether two lines are the same entry, with the same tags, metadata and note, like
+// `8:00 email tag =mail` and `08:00 email =mail tag`. Other lines, like comments, have to be
+// equal.
+pub(crate) fn same_entry(a: &str, b: &str) -> bool {
+    a == b || entry(a).is_some_and(|entry_a| entry(b) == Some(entry_a))
+}
+
+// whether two lines are entries for the same time and activity (or track), which may differ
+// in their tags, metadata or note
+fn same_start(a: &str, b: &str) -> bool {
+    let start = |line: &str| match entry(line)? {
+        Block::NormalBlock(data) => Some((None, false, data.start, data.activity)),
+        Block::ReallyBlock(data) => Some((None, true, data.start, data.activity)),
+        Block::TrackBlock(track, data) => Some((Some(track), false, data.start, data.activity)),
+        _ => None,
+    };
+    start(a).is_some_and(|start_a| start(b) == Some(start_a))
+}
+
+// whether the line is a break while the other side works on an activity. An activity that
+// the other side has not ended yet does not count, that side might just not have logged more.
+fn ends_within(line: &str, other: &[(Duration, Duration, String)]) -> bool {
+    match Block::from_line(Ok(line.to_string())) {
+        Ok(Block::NormalBlock(data)) if is_break(&data.activity) => {
+            let time = utils::workday_offset(&data.start);
+            other
+                .iter()
+                .any(|(start, end, _)| *start <= time && time < *end && *end < Duration::days(1))
+        }
+        _ => false,
+    }
+}
+
+// the activities of the day with their start and end, without breaks
+fn intervals(lines: &[String]) -> Vec<(Duration, Duration, String)> {
+    let mut intervals: Vec<(Duration, Duration, String)> = Vec::new();
+    for line in lines {
+        match Block::from_line(Ok(line.to_string())) {
+            Ok(Block::NormalBlock(data)) => {
+                let start = utils::workday_offset(&data.start);
+                if let Some(last) = intervals.last_mut() {
+                    last.1 = start;
+                }
+                intervals.push((start, Duration::days(1), data.activity));
+            }
+            Ok(Block::ReallyBlock(data)) => {
+                if let Some(last) = intervals.last_mut() {
+                    last.2 = data.activity;
+                }
+            }
+            _ => (),
+        }
+    }
+    intervals.retain(|(_, _, activity)| !is_break(activity));
+    intervals
+}
+
+/// merges the log lines of a day from the other machine into ours: both keep their order and
+/// are interleaved by time, entries that are on both sides (the same time, activity, tags,
+/// metadata and note) are only kept once. If a side has an activity at a time when the other
+/// side has a different one, or the same entry with other tags, metadata or note, the day gets
+/// both sides with conflict markers around them instead. If both sides have the same activity at a time, the
+/// merged day has it as long as either side: a break of one side is left out while the other
+/// side still works on the activity.
+/// ```
+/// use timetracker::subcommands::merge::{merge_day, MergedDay};
+/// let lines = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+/// let ours = lines(&["08:00 email", "10:00 break"]);
+/// let theirs = lines(&["13:00 meeting", "14:00 break", "08:00 email"]);
+/// assert_eq!(
+///     merge_day(&ours, &theirs, "laptop"),
+///     MergedDay::Merged(lines(&["08:00 email", "10:00 break", "13:00 meeting", "14:00 break"]))
+/// );
+/// ```
+pub fn merge_day(ours: &[String], theirs: &[String], other_name: &str) -> MergedDay {
+    // the lines that are only on the other side, every line of ours matches one of theirs
+    let mut unmatched: Vec<&String> = ours.iter().collect();
+    let new: Vec<String> = theirs
+        .iter()
+        .filter(
+            |line| match unmatched.iter().position(|our| same_entry(our, line)) {
+                Some(pos) => {
+                    unmatched.remove(pos);
+                    false
+                }
+                None => true,
+            },
+        )
+        .cloned()
+        .collect();
+    if new.iter().all(|line| line.trim().is_empty()) {
+        return MergedDay::Unchanged;
+    }
+    // an entry that the other side has written differently, like with another note
+    let differs = new
+        .iter()
+        .any(|line| unmatched.iter().any(|our| same_start(our, line)));
+
+    let their_intervals = intervals(theirs);
+    let our_intervals = intervals(ours);
+    let overlaps = our_intervals.iter().any(|(start, end, activity)| {
+        their_intervals
+            .iter()
+            .any(|(s, e, a)| start < e && s < end && activity != a)
+    });
+    if overlaps || differs {
+        let mut lines = vec!["<<<<<<< this machine".to_string()];
+        lines.extend(ours.iter().cloned());
+        lines.push("=======".to_string());
+        lines.extend(theirs.iter().cloned());
+        lines.push(format!(">>>>>>> {}", other_name));
+        return MergedDay::Conflict(lines);
+    }
+
+    let ours: Vec<String> = ours
+        .iter()
+        .filter(|line| !ends_within(line, &their_intervals))
+        .cloned()
+        .collect();
+    let new: Vec<String> = new
+        .into_iter()
+        .filter(|line| !ends_within(line, &our_intervals))
+        .collect();
+    MergedDay::Merged(interleave(&ours, &new))
+}
+
+/// the lines of both sides in the order of time, lines with the same time as one of ours follow
+/// it. Both sides keep their order.
+pub(crate) fn interleave(ours: &[String], new: &[String]) -> Vec<String> {
+    let our_keys = timeline_keys(ours);
+    let new_keys = timeline_keys(new);
+    let mut merged = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < ours.len() || j < new.len() {
+        if j >= new.len() || (i < ours.len() && our_keys[i] <= new_keys[j]) {
+            merged.push(ours[i].to_string());
+            i += 1;
+        } else {
+            merged.push(new[j].to_string());
+            j += 1;
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collector::collect_workday;
+    use crate::subcommands::check::check_log;
+    use std::collections::HashSet;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn unchanged() {
+        let ours = lines(&["08:00 email", "# a comment", "10:00 break"]);
+        assert_eq!(
+            merge_day(&ours, &lines(&["08:00 email"]), "laptop"),
+            MergedDay::Unchanged
+        );
+        assert_eq!(merge_day(&ours, &ours, "laptop"), MergedDay::Unchanged);
+    }
+
+    #[test]
+    fn interleaved_with_comments() {
+        let ours = lines(&["08:00 email", "10:00 break", "16:00 email", "17:00 break"]);
+        let theirs = lines(&[
+            "12:00 meeting",
+            "# with the team",
+            "13:00 break",
+            "+0:30 travel",
+        ]);
+        assert_eq!(
+            merge_day(&ours, &theirs, "laptop"),
+            MergedDay::Merged(lines(&[
+                "08:00 email",
+                "10:00 break",
+                "12:00 meeting",
+                "# with the team",
+                "13:00 break",
+                "+0:30 travel",
+                "16:00 email",
+                "17:00 break",
+            ]))
+        );
+    }
+
+    #[test]
+    fn overlapping_activities_conflict() {
+        let ours = lines(&["08:00 email", "12:00 break"]);
+        let theirs = lines(&["10:00 meeting", "11:00 break"]);
+        let merged = match merge_day(&ours, &theirs, "laptop") {
+            MergedDay::Conflict(merged) => merged,
+            other => panic!("expected a conflict, got {:?}", other),
+        };
+        assert_eq!(merged[0], "<<<<<<< this machine");
+        assert_eq!(merged[3], "=======");
+        assert_eq!(merged[6], ">>>>>>> laptop");
+
+        let known: HashSet<String> = ["email", "meeting"].iter().map(|a| a.to_string()).collect();
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let diagnostics = check_log(
+            &date,
+            vec![].into_iter(),
+            merged.iter().cloned().map(Ok),
+            &known,
+            true,
+        );
+        let markers = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.to_string().contains("merge conflict"))
+            .count();
+        assert_eq!(markers, 3);
+        // reports still show what can be parsed
+        let (collected, _) = collect_workday(
+            &date,
+            vec![].into_iter(),
+            merged.iter().cloned().map(Ok),
+            None,
+        );
+        assert!(collected.is_some());
+    }
+
+    #[test]
+    fn same_activity_on_both_sides() {
+        let ours = lines(&["08:00 email", "10:00 break"]);
+        let theirs = lines(&["09:00 email", "11:00 break"]);
+        let merged = match merge_day(&ours, &theirs, "laptop") {
+            MergedDay::Merged(merged) => merged,
+            other => panic!("expected a merge, got {:?}", other),
+        };
+        assert_eq!(
+            merged,
+            lines(&["08:00 email", "09:00 email", "11:00 break"])
+        );
+        // email from 8 to 11 on either side
+        let (collected, _) = collect_workday(
+            &NaiveDate::from_ymd_opt(2020, 6, 1).unwrap(),
+            vec![].into_iter(),
+            merged.iter().cloned().map(Ok),
+            None,
+        );
+        let summary = collected.unwrap().summary;
+        assert_eq!(summary.activities["email"].0.num_minutes(), 3 * 60);
+        assert!(!summary.activities.contains_key("break"));
+    }
+
+    #[test]
+    fn same_entries_written_differently() {
+        let ours = lines(&["8:00 email =mail", "10:00 break"]);
+        let theirs = lines(&["08:00 email =mail", "10:00 break"]);
+        assert_eq!(merge_day(&ours, &theirs, "laptop"), MergedDay::Unchanged);
+        let ours = lines(&[
+            "8:00 email =mail customer=acme urgent -- inbox",
+            "10:00 break",
+        ]);
+        let theirs = lines(&[
+            "08:00:00 email urgent customer=acme =mail -- inbox",
+            "10:00 break",
+        ]);
+        assert_eq!(merge_day(&ours, &theirs, "laptop"), MergedDay::Unchanged);
+    }
+
+    #[test]
+    fn differing_entries_conflict() {
+        for theirs in &[
+            "08:00 email customer=other",
+            "08:00 email customer=acme urgent",
+            "08:00 email customer=acme -- inbox",
+        ] {
+            let ours = lines(&["08:00 email customer=acme", "10:00 break"]);
+            let theirs = lines(&[theirs, "10:00 break"]);
+            match merge_day(&ours, &theirs, "laptop") {
+                MergedDay::Conflict(merged) => assert_eq!(merged[4], theirs[0]),
+                other => panic!("expected a conflict, got {:?}", other),
+            }
+        }
+    }
+}
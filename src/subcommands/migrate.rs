@@ -0,0 +1,141 @@
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::result::Result;
+
+use chrono::NaiveDate;
+use structopt::StructOpt;
+
+use crate::error::TTError;
+use crate::storage::Layout;
+use crate::utils::FileProxy;
+
+#[derive(StructOpt, Debug)]
+pub struct MigrateOpt {
+    #[structopt(long)]
+    /// move the daily log files to this layout: flat or nested
+    pub layout: Layout,
+}
+
+// interface for the runner, moves the log files and tells what to configure
+pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    opt: MigrateOpt,
+    data_dir: &Path,
+    journalfile: &F,
+) -> Result<i32, TTError> {
+    let lock = journalfile.lock()?;
+    let (moved, kept) = migrate(data_dir, opt.layout)?;
+    drop(lock);
+    for path in kept.iter() {
+        eprintln!(
+            "{} not moved, there is already a log file for its day",
+            path.display()
+        );
+    }
+    println!("moved {} log files", moved.len());
+    let layout = match opt.layout {
+        Layout::Flat => "flat",
+        Layout::Nested => "nested",
+    };
+    println!("set layout = \"{}\" in the config file", layout);
+    match kept.is_empty() {
+        true => Ok(0),
+        false => Ok(1),
+    }
+}
+
+/// the daily log files in the directory, in either layout
+pub fn find_logfiles(dir: &Path) -> Result<Vec<(NaiveDate, PathBuf)>, TTError> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        match NaiveDate::parse_from_str(name, "%F") {
+            Ok(date) if path.is_file() => found.push((date, path)),
+            Err(_) if name.len() == 4 && path.is_dir() => {
+                for month in fs::read_dir(&path)? {
+                    let month = month?.path();
+                    if month.is_dir() {
+                        for file in fs::read_dir(&month)? {
+                            let file = file?.path();
+                            let date = file
+                                .file_name()
+                                .and_then(|n| n.to_str())
+                                .and_then(|n| NaiveDate::parse_from_str(n, "%F").ok());
+                            if let (Some(date), true) = (date, file.is_file()) {
+                                found.push((date, file));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// removes the month and year directories of the nested layout that held the log file, if they
+/// are empty now
+pub(crate) fn remove_empty_dirs(dir: &Path, logfile: &Path) {
+    if let Some(month) = logfile.parent().filter(|month| *month != dir) {
+        if fs::remove_dir(month).is_ok() {
+            let _ = month.parent().map(fs::remove_dir);
+        }
+    }
+}
+
+/// moves the daily log files in the directory to their place in the layout. A file stays where it
+/// is if there is already a file in its new place. Returns the moved and the kept files.
+pub fn migrate(dir: &Path, layout: Layout) -> Result<(Vec<PathBuf>, Vec<PathBuf>), TTError> {
+    let mut moved = Vec::new();
+    let mut kept = Vec::new();
+    for (date, path) in find_logfiles(dir)? {
+        let target = layout.logfile_name(dir, &date);
+        if target == path {
+            continue;
+        }
+        if target.exists() {
+            kept.push(path);
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&path, &target)?;
+        remove_empty_dirs(dir, &path);
+        moved.push(target);
+    }
+    Ok((moved, kept))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn flat_to_nested_and_back() {
+        let dir = env::temp_dir().join(format!("tt-migrate-{}", std::process::id()));
+        fs::create_dir_all(dir.join("2020/05")).unwrap();
+        fs::write(dir.join("2020-06-01"), "08:00 email\n").unwrap();
+        fs::write(dir.join("2020/05/2020-05-29"), "09:00 meeting\n").unwrap();
+        fs::write(dir.join("activities"), "mail email\n").unwrap();
+
+        let (moved, kept) = migrate(&dir, Layout::Nested).unwrap();
+        assert_eq!(moved, vec![dir.join("2020/06/2020-06-01")]);
+        assert!(kept.is_empty());
+        fs::write(dir.join("2020-06-01"), "10:00 break\n").unwrap();
+        let (moved, kept) = migrate(&dir, Layout::Flat).unwrap();
+        assert_eq!(moved, vec![dir.join("2020-05-29")]);
+        assert_eq!(kept, vec![dir.join("2020/06/2020-06-01")]);
+        assert!(!dir.join("2020/05").exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("2020-06-01")).unwrap(),
+            "10:00 break\n"
+        );
+        assert!(dir.join("activities").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
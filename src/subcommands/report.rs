@@ -3,8 +3,9 @@ use chrono::{DateTime, Duration, Local};
 use chrono::{Datelike, NaiveDate};
 use core::str::FromStr;
 use itertools::Itertools;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::io::{BufRead, Write};
+use std::path::Path;
 
 use std::fmt;
 use std::result::Result;
@@ -13,15 +14,20 @@ use structopt::StructOpt;
 
 // use crate::SummaryFormat::{Short, Long};
 
-use crate::collector::{collect_blocks, ActivityHashMap, CollectResult, Summary};
-use crate::error::TTError;
-use crate::get_logfile_name;
-use crate::log_parser;
-use crate::subcommands::add::{read_activities, ActivityMap};
+use crate::activities::{read_activities, ActivityMap};
+use crate::collector::{collect_workday, ActivityHashMap, CollectResult, Summary};
+use crate::error::{Diagnostic, TTError, TTErrorKind};
+use crate::log_parser::{self, Metadata};
+use crate::storage::Storage;
 use crate::utils;
 use crate::utils::{format_duration, format_time, FileProxy};
 use std::collections::hash_map::RandomState;
 
+pub mod json;
+pub mod projects;
+
+use self::projects::Level;
+
 #[derive(StructOpt, Debug)]
 pub(crate) struct ReportOpt {
     #[structopt(short, long)]
@@ -47,6 +53,30 @@ pub(crate) struct ReportOpt {
     #[structopt(short, long)]
     /// treat internal activities (the ones starting with "_") like normal activities
     pub all: bool,
+
+    #[structopt(short, long)]
+    /// sum up the activities by the value of this metadata key, e.g. customer
+    pub group_by: Option<String>,
+
+    #[structopt(long, parse(try_from_str = parse_metadata_filter))]
+    /// only report the time spent with this metadata; format: key=value, can be repeated to
+    /// report the time of entries with all of them
+    pub filter: Vec<(String, String)>,
+
+    #[structopt(short, long, default_value = "text")]
+    /// print the report as text or as json, see the README for the json schema
+    pub output: Output,
+
+    #[structopt(short, long)]
+    /// show the activities in the tree of their projects, and distribute the internal activities
+    /// at this level: 1 for the top-level projects, 2 for their sub-projects etc. or activities
+    pub projects: Option<Level>,
+}
+
+fn parse_metadata_filter(s: &str) -> Result<(String, String), String> {
+    log_parser::as_metadata(s)
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("filter must have the form key=value, but is {:?}", s))
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -61,6 +91,25 @@ pub enum SummaryFormat {
     Worktime, // worktime today in minutes
 }
 
+/// how the report is printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    Text,
+    Json,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Output::Text),
+            "json" => Ok(Output::Json),
+            _ => Err(format!("output must be text or json, but is {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParseSummaryFormatError {
     _priv: (),
@@ -94,7 +143,7 @@ impl std::fmt::Display for ParseSummaryFormatError {
 pub(crate) fn run<'a, R: BufRead, W: Write, F: FileProxy<R, W>>(
     report_opt: ReportOpt,
     now: &DateTime<Local>,
-    _default_logfile: &F,
+    storage: &dyn Storage<R, W, F>,
     activitiesfile: &F,
 ) -> Result<i32, TTError> {
     let format = match report_opt.format {
@@ -103,23 +152,117 @@ pub(crate) fn run<'a, R: BufRead, W: Write, F: FileProxy<R, W>>(
         _ => &SummaryFormat::Long,
     };
     report_opt.format.clone().unwrap_or(SummaryFormat::Long);
+    let groups_shown = matches!(format, SummaryFormat::Long | SummaryFormat::Table)
+        || report_opt.output == Output::Json;
+    if report_opt.group_by.is_some() && !groups_shown {
+        return Err(TTError::new(TTErrorKind::UsageError(
+            "--group-by only works with the long and the table format, or with --output json",
+        )));
+    }
+    let activity_map = match (format, report_opt.projects) {
+        (SummaryFormat::Table, _) | (_, Some(_)) => Some(
+            read_activities(activitiesfile.reader()?)
+                .map_err(|err| err.in_file(activitiesfile.pathname()))?,
+        ),
+        // only for the preferred shortname of the current activity, they work without it
+        (SummaryFormat::Status, _) | (SummaryFormat::Activity, _) => activitiesfile
+            .reader()
+            .ok()
+            .and_then(|reader| read_activities(reader).ok()),
+        _ => None,
+    };
     let mut summaries = Vec::new();
-    for (date, add_ending_at) in report_dates(&report_opt, *now) {
-        let logfile_reader = F::new(get_logfile_name(&date)).reader();
-        let collected = collect_blocks(logfile_reader?.lines(), add_ending_at.as_ref())?;
-        if format != &SummaryFormat::Table {
+    let mut json_days = Vec::new();
+    let dates: Vec<_> = report_dates(&report_opt, *now).collect();
+    // the days to report on and the day before the first one, whose open activity continues
+    let days = match (dates.first(), dates.last()) {
+        (Some((first, _)), Some((last, _))) => storage.load(&(*first - Duration::days(1)), last)?,
+        _ => vec![],
+    };
+    for ((date, add_ending_at), (previous_day, day)) in
+        dates.into_iter().zip(days.iter().tuple_windows())
+    {
+        let (mut collected, diagnostics) = collect_workday(
+            &date,
+            previous_day.iter_lines(),
+            day.iter_lines(),
+            add_ending_at.as_ref(),
+        );
+        print_diagnostics(&storage.path(&date), diagnostics);
+        if let Some(ref mut collected) = collected {
+            if !report_opt.filter.is_empty() {
+                collected.summary = filter_summary(&collected.summary, &report_opt.filter);
+            }
+            if let Some(activity_map) = &activity_map {
+                use_preferred_shortname(collected, activity_map);
+            }
+        }
+        if report_opt.output == Output::Json {
+            json_days.push(json::day(
+                &date,
+                &collected,
+                report_opt.cutoff,
+                report_opt.all,
+                &report_opt.group_by,
+            ));
+        } else if format != &SummaryFormat::Table {
             if report_opt.week {
                 println!("{}:\n", date);
             }
-            report(&collected, &format, &report_opt.cutoff);
+            match (format, report_opt.projects, &collected, &activity_map) {
+                (SummaryFormat::Long, Some(level), Some(collected), Some(activity_map)) => {
+                    projects_report(
+                        &collected.summary,
+                        report_opt.cutoff,
+                        report_opt.all,
+                        activity_map,
+                        level,
+                    )
+                }
+                _ => report(&collected, &format, &report_opt.cutoff),
+            }
+            if let (SummaryFormat::Long, Some(key), Some(collected)) =
+                (format, &report_opt.group_by, &collected)
+            {
+                println!("by {}:", key);
+                let distributed = handle_cutoff_and_distribute(
+                    &collected.summary,
+                    report_opt.cutoff,
+                    report_opt.all,
+                );
+                for (group, duration) in group_durations(&collected.summary, &distributed, key) {
+                    println!("- {:16} {}", group, utils::format_duration(&duration));
+                }
+            }
         }
         if let Some(collected) = collected {
             summaries.push((date, collected.summary));
         }
     }
-    if format == &SummaryFormat::Table {
-        let activity_map = read_activities(activitiesfile.reader()?)?;
-        report_table(&summaries, report_opt.cutoff, report_opt.all, &activity_map);
+    let activity_map = activity_map.filter(|_| format == &SummaryFormat::Table);
+    match (report_opt.output, activity_map) {
+        (Output::Json, activity_map) => {
+            let table = activity_map.map(|activity_map| {
+                json::table(
+                    &summaries,
+                    report_opt.cutoff,
+                    report_opt.all,
+                    &activity_map,
+                    &report_opt.group_by,
+                )
+            });
+            let report = json::Report::new(json_days, table);
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        (Output::Text, Some(activity_map)) => report_table(
+            &summaries,
+            report_opt.cutoff,
+            report_opt.all,
+            &activity_map,
+            &report_opt.group_by,
+            report_opt.projects,
+        ),
+        (Output::Text, None) => (),
     }
     Ok(0)
 }
@@ -128,7 +271,7 @@ fn report_dates(
     opt: &ReportOpt,
     now: DateTime<Local>,
 ) -> Box<dyn Iterator<Item = (NaiveDate, Option<NaiveTime>)>> {
-    let date = now.naive_local().date();
+    let date = utils::workday(&now.naive_local());
     let mut base_date = date;
     if let Some(explicit_date) = opt.date {
         base_date = explicit_date;
@@ -167,7 +310,15 @@ fn report_sort_key(
     )
 }
 
-pub(crate) fn short_report(summary: &Summary) -> String {
+// problems found in a log file, they go to stderr to keep the report usable for scripts
+pub(crate) fn print_diagnostics(logfile: &Path, diagnostics: Vec<Diagnostic>) {
+    for diagnostic in diagnostics {
+        eprintln!("{}\n", diagnostic.in_file(logfile));
+    }
+}
+
+// the first line of the long report: start, end, breaks and the work time of the day
+fn day_report_line(summary: &Summary) -> String {
     format!(
         "start: {}, end: {}, breaks: {}, work time: {}, distribute: {}",
         utils::format_time(&summary.start),
@@ -176,7 +327,31 @@ pub(crate) fn short_report(summary: &Summary) -> String {
         utils::format_duration(&summary.work_time),
         utils::format_duration(&summary.distribute),
     )
-    .to_string()
+}
+
+pub(crate) fn short_report(summary: &Summary) -> String {
+    let mut report = day_report_line(summary);
+    if !summary.adjustments.is_empty() {
+        let manual = summary
+            .adjustments
+            .values()
+            .fold(Duration::zero(), |lhs, rhs| lhs + *rhs);
+        report = format!(
+            "{}, manual: {}",
+            report,
+            utils::format_signed_duration(&manual)
+        );
+    }
+    if summary.tracks.is_empty() {
+        return report;
+    }
+    let tracks = summary
+        .tracks
+        .iter()
+        .sorted_by_key(|(name, _)| name.to_string())
+        .map(|(name, (duration, _))| format!("@{} {}", name, utils::format_duration(duration)))
+        .join(", ");
+    format!("{}, tracks: {}", report, tracks)
 }
 
 fn handle_cutoff_and_distribute(
@@ -213,6 +388,128 @@ fn handle_cutoff_and_distribute(
         .collect()
 }
 
+// restricts the activities to the time spent with all of the given metadata. The internal
+// activities are scaled down to the share of the matching time in the time of the other
+// activities, so that the matching activities get the same distributed time as in the whole
+// summary.
+fn filter_summary(summary: &Summary, filters: &[(String, String)]) -> Summary {
+    let matches = |metadata: &Metadata| {
+        filters
+            .iter()
+            .all(|(key, value)| metadata.get(key) == Some(value))
+    };
+    let mut filtered = summary.clone();
+    filtered.metadata_combinations = summary
+        .metadata_combinations
+        .iter()
+        .map(|(name, combinations)| {
+            let matching = combinations
+                .iter()
+                .filter(|(metadata, _)| matches(metadata))
+                .map(|(metadata, duration)| (metadata.clone(), *duration))
+                .collect();
+            (name.clone(), matching)
+        })
+        .collect();
+    let mut total = Duration::zero();
+    let mut matching_total = Duration::zero();
+    filtered.activities = summary
+        .activities
+        .iter()
+        .filter_map(|(name, (duration, duration_map))| {
+            if log_parser::is_break(name) || log_parser::is_distributable(name) {
+                return Some((name.clone(), (*duration, duration_map.clone())));
+            }
+            total += *duration;
+            let matching: Duration = filtered
+                .metadata_combinations
+                .get(name)?
+                .values()
+                .fold(Duration::zero(), |sum, duration| sum + *duration);
+            if matching.is_zero() {
+                return None;
+            }
+            matching_total += matching;
+            let tag_durations = duration_map
+                .iter()
+                .map(|(tag, tag_duration)| (tag.clone(), matching.min(*tag_duration)))
+                .collect();
+            Some((name.clone(), (matching, tag_durations)))
+        })
+        .collect();
+    let scale = |duration: &Duration| match total.is_zero() {
+        true => Duration::zero(),
+        false => Duration::seconds(
+            (duration.num_seconds() as f64 * matching_total.num_seconds() as f64
+                / total.num_seconds() as f64)
+                .round() as i64,
+        ),
+    };
+    for (name, (duration, duration_map)) in filtered.activities.iter_mut() {
+        if log_parser::is_distributable(name) && !log_parser::is_break(name) {
+            *duration = scale(duration);
+            for tag_duration in duration_map.values_mut() {
+                *tag_duration = scale(tag_duration);
+            }
+        }
+    }
+    filtered.distribute = scale(&summary.distribute);
+    filtered.work_time = matching_total + filtered.distribute;
+    // the durations by key and value are those of the matching time only
+    filtered.metadata = filtered
+        .metadata_combinations
+        .iter()
+        .filter(|(_, combinations)| !combinations.is_empty())
+        .map(|(name, combinations)| {
+            let mut durations: BTreeMap<(String, String), Duration> = BTreeMap::new();
+            for (metadata, duration) in combinations.iter() {
+                for (key, value) in metadata.iter() {
+                    *durations
+                        .entry((key.clone(), value.clone()))
+                        .or_insert_with(Duration::zero) += *duration;
+                }
+            }
+            (name.clone(), durations)
+        })
+        .collect();
+    filtered
+}
+
+// sums up the distributed durations of the activities by the value of the metadata key.
+// Time without this key is reported as "key=-".
+fn group_durations(
+    summary: &Summary,
+    distributed: &HashMap<&String, (Duration, &BTreeMap<String, Duration>)>,
+    key: &str,
+) -> BTreeMap<String, Duration> {
+    let mut groups: BTreeMap<String, Duration> = BTreeMap::new();
+    let mut add_share = |group: String, total: &Duration, part: &Duration, whole: &Duration| {
+        if whole.num_seconds() > 0 && part.num_seconds() > 0 {
+            let share = Duration::seconds(
+                (total.num_seconds() as f64 * part.num_seconds() as f64
+                    / whole.num_seconds() as f64) as i64,
+            );
+            let sum = groups.entry(group).or_insert_with(Duration::zero);
+            *sum += share;
+        }
+    };
+    for (name, (total, _tags)) in distributed.iter() {
+        let raw = summary
+            .activities
+            .get(*name)
+            .map_or_else(Duration::zero, |(duration, _)| *duration);
+        let mut unassigned = raw;
+        if let Some(metadata) = summary.metadata.get(*name) {
+            for ((_key, value), duration) in metadata.iter().filter(|((k, _), _)| k == key) {
+                add_share(format!("{}={}", key, value), total, duration, &raw);
+                unassigned -= *duration;
+            }
+        }
+        add_share(format!("{}=-", key), total, &unassigned, &raw);
+    }
+    groups
+}
+
 // return the long status report as an iterator of lines; it consumes summary.
 pub(crate) fn long_report_lines(
     summary: &Summary,
@@ -227,47 +524,80 @@ pub(crate) fn long_report_lines(
     activities
         .iter()
         .map(|(name, (duration, duration_map))| {
-            (
-                if log_parser::is_distributable(name)
-                    || log_parser::is_break(name)
-                    || duration < &cutoff
-                {
-                    format!("- {:16}({})", name, utils::format_duration(&duration)).to_string()
-                } else {
-                    let share = Duration::seconds(
-                        ((summary.distribute.num_seconds() as f64 + cutoff_total)
-                            * (duration.num_seconds() as f64
-                                / (summary.work_time.num_seconds() as f64
-                                    - summary.distribute.num_seconds() as f64
-                                    - cutoff_total))) as i64,
-                    );
-                    let tag_report = duration_map
-                        .iter()
-                        .filter(|(t, _duration)| !t.starts_with("resume:"))
-                        .map(|(tag, tag_duration)| {
-                            format!(
-                                "{}{}",
-                                tag,
-                                if tag.starts_with("=") || *tag_duration == *duration {
-                                    "".to_string()
-                                } else {
-                                    format!("({})", utils::format_duration(tag_duration))
-                                }
-                            )
-                        })
-                        .join(", ");
-                    format!(
-                        "- {:16} {} + {} = {}  {}",
-                        name,
-                        utils::format_duration(&duration),
-                        utils::format_duration(&share),
-                        utils::format_duration(&(*duration + share)),
-                        tag_report
-                    )
-                    .to_string()
-                },
-                name.to_string(),
+            let line = if log_parser::is_distributable(name)
+                || log_parser::is_break(name)
+                || duration < &cutoff
+            {
+                format!("- {:16}({})", name, utils::format_duration(&duration)).to_string()
+            } else {
+                let share = Duration::seconds(
+                    ((summary.distribute.num_seconds() as f64 + cutoff_total)
+                        * (duration.num_seconds() as f64
+                            / (summary.work_time.num_seconds() as f64
+                                - summary.distribute.num_seconds() as f64
+                                - cutoff_total))) as i64,
+                );
+                let tag_report = duration_map
+                    .iter()
+                    .filter(|(t, _duration)| !t.starts_with("resume:"))
+                    .map(|(tag, tag_duration)| {
+                        format!(
+                            "{}{}",
+                            tag,
+                            if tag.starts_with("=") || *tag_duration == *duration {
+                                "".to_string()
+                            } else {
+                                format!("({})", utils::format_duration(tag_duration))
+                            }
+                        )
+                    })
+                    .join(", ");
+                format!(
+                    "- {:16} {} + {} = {}  {}",
+                    name,
+                    utils::format_duration(&duration),
+                    utils::format_duration(&share),
+                    utils::format_duration(&(*duration + share)),
+                    tag_report
+                )
+                .to_string()
+            };
+            // manual durations are part of the duration, but they should be recognizable
+            let line = match summary.adjustments.get(*name) {
+                Some(adjustment) => format!(
+                    "{}  [manual {}]",
+                    line.trim_end(),
+                    utils::format_signed_duration(adjustment)
+                ),
+                None => line,
+            };
+            (line, name.to_string())
+        })
+        .collect()
+}
+
+// the lines for the secondary tracks in the long report, sorted by name
+pub(crate) fn track_report_lines(summary: &Summary) -> Vec<String> {
+    summary
+        .tracks
+        .iter()
+        .sorted_by_key(|(name, _)| name.to_string())
+        .map(|(name, (duration, duration_map))| {
+            let tags = duration_map
+                .iter()
+                .map(|(tag, tag_duration)| match tag_duration == duration {
+                    true => tag.to_string(),
+                    false => format!("{}({})", tag, utils::format_duration(tag_duration)),
+                })
+                .join(", ");
+            format!(
+                "- {:16} {}  {}",
+                name,
+                utils::format_duration(duration),
+                tags
             )
+            .trim_end()
+            .to_string()
         })
         .collect()
 }
@@ -336,10 +666,18 @@ pub fn report(
                 }
                 ()
             }
-            SummaryFormat::Long | SummaryFormat::Short => {
-                println!("{}", short_report(&summary));
-                if let SummaryFormat::Long = format {
-                    for (line, _activity) in long_report_lines(&summary, cutoff) {
+            SummaryFormat::Short => println!("{}", short_report(summary)),
+            SummaryFormat::Long => {
+                println!("{}", day_report_line(summary));
+                for (line, activity) in long_report_lines(&summary, cutoff) {
+                    println!("{}", line);
+                    for note in summary.notes.get(&activity).into_iter().flatten() {
+                        println!("    {}", note);
+                    }
+                }
+                if !summary.tracks.is_empty() {
+                    println!("tracks (not part of the work time):");
+                    for line in track_report_lines(summary) {
                         println!("{}", line);
                     }
                 }
@@ -369,6 +707,8 @@ pub fn report(
 struct DailyActivity {
     durations: HashMap<NaiveDate, Duration>,
     total: Duration,
+    // the part of the total that was logged as manual duration
+    manual: Duration,
 }
 
 impl Default for DailyActivity {
@@ -376,15 +716,121 @@ impl Default for DailyActivity {
         DailyActivity {
             durations: HashMap::default(),
             total: Duration::zero(),
+            manual: Duration::zero(),
         }
     }
 }
 
+// the distributed durations of the activities per day, or of the groups with group_by
+fn table_rows(
+    summaries: &[(NaiveDate, Summary)],
+    cutoff: Option<Duration>,
+    all: bool,
+    group_by: &Option<String>,
+) -> HashMap<String, DailyActivity> {
+    let mut activities: HashMap<String, DailyActivity, RandomState> = HashMap::new(); // &str, DailyActivity>::new();
+    for (date, summary) in summaries {
+        let filtered_activities = handle_cutoff_and_distribute(&summary, cutoff, all);
+        // the manual durations are only known per activity, not per group
+        let rows: Vec<(String, Duration, Duration)> = match group_by {
+            None => filtered_activities
+                .iter()
+                .map(|(name, (duration, ..))| {
+                    let manual = summary.adjustments.get(*name).cloned();
+                    (
+                        name.to_string(),
+                        *duration,
+                        manual.unwrap_or_else(Duration::zero),
+                    )
+                })
+                .collect(),
+            Some(key) => group_durations(summary, &filtered_activities, key)
+                .into_iter()
+                .map(|(name, duration)| (name, duration, Duration::zero()))
+                .collect(),
+        };
+        for (name, duration, manual) in rows {
+            let mut daily = activities.entry(name).or_default();
+            daily.total = daily.total + duration;
+            daily.manual += manual;
+            daily.durations.insert(*date, duration);
+        }
+    }
+    activities
+}
+
+// the log can have another alias of the current activity than the preferred shortname
+fn use_preferred_shortname(collected: &mut CollectResult, activity_map: &ActivityMap) {
+    let preferred = activity_map
+        .get(&collected.final_activity)
+        .and_then(|activity| activity.shortname.as_ref());
+    if let (Some(_), Some(preferred)) = (&collected.final_shortname, preferred) {
+        collected.final_shortname = Some(format!("={}", preferred));
+    }
+}
+
+// the long report with the activities in the tree of their projects
+fn projects_report(
+    summary: &Summary,
+    cutoff: Option<Duration>,
+    all: bool,
+    activity_map: &ActivityMap,
+    level: Level,
+) {
+    println!("{}", day_report_line(summary));
+    let tree = projects::tree(summary, cutoff, all, activity_map, level);
+    for (line, path) in projects::report_lines(&tree).iter().zip(tree.keys()) {
+        println!("{}", line);
+        let activity = &path[path.len() - 1];
+        for note in summary.notes.get(activity).into_iter().flatten() {
+            println!("{}    {}", "  ".repeat(path.len() - 1), note);
+        }
+    }
+    if !summary.tracks.is_empty() {
+        println!("tracks (not part of the work time):");
+        for line in track_report_lines(summary) {
+            println!("{}", line);
+        }
+    }
+}
+
+// the rows of the project tree per day, their names are indented by the depth in the tree
+fn project_table_rows(
+    summaries: &[(NaiveDate, Summary)],
+    cutoff: Option<Duration>,
+    all: bool,
+    activity_map: &ActivityMap,
+    level: Level,
+) -> HashMap<String, DailyActivity> {
+    let mut rows: HashMap<String, DailyActivity> = HashMap::new();
+    for (date, summary) in summaries {
+        for (path, node) in projects::tree(summary, cutoff, all, activity_map, level) {
+            let name = format!("{}{}", " ".repeat(path.len() - 1), path.join("/"));
+            let daily = rows.entry(name).or_default();
+            daily.total += node.total();
+            daily.durations.insert(*date, node.total());
+        }
+    }
+    rows
+}
+
+// the rows of the project tree are sorted by their path, after the indentation
+fn tree_key(name: &str) -> Vec<&str> {
+    name.trim_start().split('/').collect()
+}
+
+// the activity at the end of a row of the project tree, or the activity of a row
+fn activity_name(name: &str) -> &str {
+    tree_key(name).pop().unwrap_or(name)
+}
+
 fn report_table(
     summaries: &Vec<(NaiveDate, Summary)>,
     cutoff: Option<Duration>,
     all: bool,
     activity_map: &ActivityMap,
+    group_by: &Option<String>,
+    projects: Option<Level>,
 ) {
     // Table-Format:
     // XXXX Mo Di Mi Do Fr Sa So Sum
@@ -400,24 +846,32 @@ fn report_table(
     // First part is easy directly with the summaries.
     // After that we need a list of tickets with the sums for each day and totals
     // let max_length = summaries.map(|s| s.)
-    let mut activities: HashMap<&str, DailyActivity, RandomState> = HashMap::new(); // &str, DailyActivity>::new();
-    for (date, summary) in summaries {
-        let filtered_activities = handle_cutoff_and_distribute(&summary, cutoff, all);
-        for (name, (duration, ..)) in filtered_activities {
-            let mut daily = activities.entry(&name).or_default();
-            daily.total = daily.total + duration;
-            daily.durations.insert(*date, duration);
-        }
-    }
+    let activities = match projects {
+        None => table_rows(summaries, cutoff, all, group_by),
+        Some(level) => project_table_rows(summaries, cutoff, all, activity_map, level),
+    };
     // let normalized_activities = handle_cutoff_and_distribute(summary)
-    let activity_names: Vec<&&str> = activities.keys().sorted().collect();
-    let activity_length = activity_names.iter().map(|s| s.len()).max().unwrap();
+    let activity_names: Vec<&String> = activities
+        .keys()
+        .sorted_by_key(|name| tree_key(name))
+        .collect();
+    let track_names: BTreeSet<String> = summaries
+        .iter()
+        .flat_map(|(_date, summary)| summary.tracks.keys())
+        .map(|name| format!("@{}", name))
+        .collect();
+    let activity_length = activity_names
+        .iter()
+        .map(|s| s.len())
+        .chain(track_names.iter().map(|s| s.len()))
+        .max()
+        .unwrap();
     let shortname_length = activity_names
         .iter()
         .map(|s| {
             activity_map
-                .get(**s)
-                .and_then(|(_activity, tags)| tags.first())
+                .get(activity_name(s))
+                .and_then(|activity| activity.shortname.as_ref())
                 .map(|s| s.len())
                 .unwrap_or_default()
         })
@@ -490,7 +944,9 @@ fn report_table(
     // activities
     for name in activity_names {
         let activity = activities.get(name).unwrap();
-        let shortname = activity_map.get(*name).map(|(_, v)| v.first()).flatten();
+        let shortname = activity_map
+            .get(activity_name(name))
+            .and_then(|activity| activity.shortname.as_ref());
         let maybe_with_shortname = shortname.map(|s| {
             format!(
                 "{}{} ={}",
@@ -500,7 +956,7 @@ fn report_table(
             )
         });
         let full_name = match maybe_with_shortname {
-            None => *name,
+            None => name.as_str(),
             Some(ref with_shortname) => with_shortname,
         };
         write_durations(
@@ -511,7 +967,197 @@ fn report_table(
                     .get(date)
                     .map_or_else(|| "-".to_string(), |d| format_duration(d))
             })),
-            &format_duration(&activity.total),
+            &match activity.manual.is_zero() {
+                true => format_duration(&activity.total),
+                false => format!(
+                    "{:>5}  [manual {}]",
+                    format_duration(&activity.total),
+                    utils::format_signed_duration(&activity.manual)
+                ),
+            },
         );
     }
+
+    // secondary tracks, they are not part of the worktime
+    if !track_names.is_empty() {
+        println!();
+    }
+    for track in track_names.iter() {
+        let name = &track[1..];
+        let total = summaries
+            .iter()
+            .filter_map(|(_date, summary)| summary.tracks.get(name))
+            .fold(Duration::zero(), |lhs, (rhs, _)| lhs + *rhs);
+        write_durations(
+            track,
+            Box::new(summaries.iter().map(move |(_date, summary)| {
+                summary
+                    .tracks
+                    .get(name)
+                    .map_or_else(|| "-".to_string(), |(d, _)| format_duration(d))
+            })),
+            &format_duration(&total),
+        );
+    }
+
+    // notes per activity and day
+    let mut notes: BTreeMap<&str, Vec<(&NaiveDate, String)>> = BTreeMap::new();
+    for (date, summary) in summaries {
+        for (name, day_notes) in summary.notes.iter() {
+            notes
+                .entry(name.as_str())
+                .or_default()
+                .push((date, day_notes.join("; ")));
+        }
+    }
+    if !notes.is_empty() {
+        println!();
+        for (name, day_notes) in notes {
+            for (date, note) in day_notes {
+                println!("{:width$} {} {}", name, date, note, width = activity_length);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::setup_line_reader;
+
+    fn summary() -> Summary {
+        let lines = setup_line_reader(vec![
+            "8:00 JIRA-1 customer=acme",
+            "9:00 JIRA-2 customer=other",
+            "10:00 JIRA-1",
+            "11:00 _meeting",
+            "12:00 break",
+        ]);
+        crate::collector::collect_blocks(lines, None)
+            .unwrap()
+            .unwrap()
+            .summary
+    }
+
+    #[test]
+    fn filter_by_metadata() {
+        let filter = |key: &str, value: &str| (key.to_string(), value.to_string());
+        let filtered = filter_summary(&summary(), &[filter("customer", "acme")]);
+        assert_eq!(
+            filtered.activities.get("JIRA-1").unwrap().0.num_minutes(),
+            60
+        );
+        assert!(filtered.activities.get("JIRA-2").is_none());
+        // the acme hour is a third of the other activities, so it gets a third of the meeting
+        assert_eq!(filtered.activities["_meeting"].0.num_minutes(), 20);
+        assert_eq!(filtered.work_time.num_minutes(), 80);
+        let distributed = handle_cutoff_and_distribute(&filtered, None, false);
+        assert_eq!(distributed.len(), 1);
+        assert_eq!(distributed[&"JIRA-1".to_string()].0.num_minutes(), 80);
+    }
+
+    #[test]
+    fn filter_by_several_keys() {
+        let lines = setup_line_reader(vec![
+            "8:00 JIRA-1 customer=acme",
+            "8:30 JIRA-1 billable=no",
+            "9:00 JIRA-2 customer=acme billable=no",
+            "9:15 break",
+        ]);
+        let summary = crate::collector::collect_blocks(lines, None)
+            .unwrap()
+            .unwrap()
+            .summary;
+        let filter = |key: &str, value: &str| (key.to_string(), value.to_string());
+        let filters = [filter("customer", "acme"), filter("billable", "no")];
+        let filtered = filter_summary(&summary, &filters);
+        // JIRA-1 has both, but never at the same time
+        assert!(filtered.activities.get("JIRA-1").is_none());
+        assert_eq!(filtered.activities["JIRA-2"].0.num_minutes(), 15);
+        assert_eq!(filtered.work_time.num_minutes(), 15);
+        let filtered = filter_summary(&summary, &filters[..1]);
+        assert_eq!(filtered.activities["JIRA-1"].0.num_minutes(), 30);
+        assert_eq!(filtered.metadata["JIRA-1"].len(), 1);
+    }
+
+    #[test]
+    fn group_by_metadata() {
+        let summary = summary();
+        let distributed = handle_cutoff_and_distribute(&summary, None, false);
+        let groups = group_durations(&summary, &distributed, "customer");
+        assert_eq!(groups.len(), 3);
+        // each hour of JIRA-x gets 20 minutes of the distributed meeting
+        assert_eq!(groups["customer=acme"].num_minutes(), 80);
+        assert_eq!(groups["customer=other"].num_minutes(), 80);
+        assert_eq!(groups["customer=-"].num_minutes(), 80);
+    }
+
+    #[test]
+    fn project_rows_per_day() {
+        let activities = "JIRA-1 JIRA-1 project=acme/portal\nJIRA-2 JIRA-2 project=other\n";
+        let activity_map = read_activities(activities.as_bytes()).unwrap();
+        let monday = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let summaries = vec![(monday, summary()), (monday.succ_opt().unwrap(), summary())];
+        let rows = project_table_rows(&summaries, None, false, &activity_map, Level::Depth(1));
+        assert_eq!(rows.len(), 5);
+        // the meeting is distributed to the top level: 40 of its minutes to acme, 20 to other
+        assert_eq!(rows["acme"].total.num_minutes(), 320);
+        assert_eq!(rows["acme"].durations[&monday].num_minutes(), 160);
+        assert_eq!(rows[" acme/portal"].durations[&monday].num_minutes(), 120);
+        assert_eq!(rows["  acme/portal/JIRA-1"].total.num_minutes(), 240);
+        assert_eq!(rows["other"].total.num_minutes(), 160);
+        let top_level = rows["acme"].total + rows["other"].total;
+        assert_eq!(
+            top_level,
+            summaries[0].1.work_time + summaries[1].1.work_time
+        );
+        assert_eq!(activity_name("  acme/portal/JIRA-1"), "JIRA-1");
+    }
+
+    #[test]
+    fn short_report_with_tracks() {
+        let lines = setup_line_reader(vec![
+            "8:00 JIRA-1",
+            "9:00 @oncall",
+            "10:30 @oncall break",
+            "11:00 break",
+        ]);
+        let summary = crate::collector::collect_blocks(lines, None)
+            .unwrap()
+            .unwrap()
+            .summary;
+        assert_eq!(
+            short_report(&summary),
+            "start: 08:00, end: 11:00, breaks: 0:00, work time: 3:00, distribute: 0:00, \
+             tracks: @oncall 1:30"
+        );
+        assert!(!day_report_line(&summary).contains("oncall"));
+    }
+
+    #[test]
+    fn short_report_with_manual_durations() {
+        let lines = setup_line_reader(vec![
+            "8:00 JIRA-1",
+            "+1:30 JIRA-7",
+            "-0:20 JIRA-1",
+            "9:00 break",
+        ]);
+        let summary = crate::collector::collect_blocks(lines, None)
+            .unwrap()
+            .unwrap()
+            .summary;
+        assert!(short_report(&summary).ends_with(", manual: +1:10"));
+    }
+
+    #[test]
+    fn report_with_negative_total() {
+        let lines = setup_line_reader(vec!["8:00 JIRA-1", "9:00 break", "-1:20 JIRA-1"]);
+        let summary = crate::collector::collect_blocks(lines, None)
+            .unwrap()
+            .unwrap()
+            .summary;
+        // more time taken off than logged: the total is negative, it does not wrap around
+        assert!(short_report(&summary).contains("work time: -0:20"));
+        assert!(long_report_lines(&summary, &None)[0].0.contains("(-0:20)"));
+    }
 }
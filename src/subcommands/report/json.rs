@@ -0,0 +1,410 @@
+//! The report as JSON, for scripts and status bars: `tt report --output json`.
+//!
+//! Durations are whole seconds, times are `HH:MM:SS` and dates `YYYY-MM-DD`. The schema has a
+//! version; it only changes compatibly (new fields) as long as the version stays the same.
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "days": [{
+//!     "date": "2020-06-01",
+//!     "current": {"activity": "JIRA-1", "shortname": "j1", "since": "16:00:00"},
+//!     "summary": {
+//!       "start": "08:00:00", "end": "17:00:00",
+//!       "breaks": 3600, "work_time": 28800, "distribute": 3600,
+//!       "activities": [{
+//!         "name": "JIRA-1", "duration": 25200, "share": 3600, "total": 28800,
+//!         "break": false, "distributed": false, "manual": null,
+//!         "tags": [{"name": "review", "duration": 3600, "total": 4114}],
+//!         "metadata": {"customer=acme": 25200},
+//!         "notes": ["fixed the login"]
+//!       }],
+//!       "tracks": [{"name": "car", "duration": 1800, "tags": []}],
+//!       "groups": {"customer=acme": 28800}
+//!     }
+//!   }],
+//!   "table": {
+//!     "dates": ["2020-06-01"],
+//!     "work_time": 28800,
+//!     "rows": [{"name": "JIRA-1", "shortname": "j1",
+//!               "durations": {"2020-06-01": 28800}, "total": 28800, "manual": null}]
+//!   }
+//! }
+//! ```
+//!
+//! - `current` and `summary` are null for a day without activities, `shortname` is null for an
+//!   activity without one.
+//! - `share` is the time of internal activities (and of the ones below the cutoff) that is
+//!   distributed to the activity, `total` is its duration plus its share. Breaks and distributed
+//!   activities have a `total` of 0, so the totals add up to the work time. The tags get the same
+//!   share as their activity.
+//! - `manual` is the part of the duration that was logged as manual duration, in the rows of the
+//!   table the part of the total. It is null for the groups of `--group-by`.
+//! - `groups` is only there with `--group-by`, `table` only for the table format (the default for
+//!   `--week`). With `--group-by` the rows of the table are the groups.
+
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDate, NaiveTime};
+use itertools::Itertools;
+use serde::Serialize;
+
+use super::{group_durations, handle_cutoff_and_distribute, report_sort_key, table_rows};
+use crate::activities::ActivityMap;
+use crate::collector::{CollectResult, Summary};
+use crate::log_parser;
+
+/// the version of the schema
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub version: u32,
+    pub days: Vec<Day>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<Table>,
+}
+
+impl Report {
+    pub fn new(days: Vec<Day>, table: Option<Table>) -> Report {
+        Report {
+            version: VERSION,
+            days,
+            table,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Day {
+    pub date: String,
+    pub current: Option<Current>,
+    pub summary: Option<DaySummary>,
+}
+
+/// the activity at the end of the day, or now
+#[derive(Serialize, Debug)]
+pub struct Current {
+    pub activity: String,
+    pub shortname: Option<String>,
+    pub since: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DaySummary {
+    pub start: String,
+    pub end: String,
+    pub breaks: i64,
+    pub work_time: i64,
+    pub distribute: i64,
+    pub activities: Vec<Activity>,
+    pub tracks: Vec<Track>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<BTreeMap<String, i64>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Activity {
+    pub name: String,
+    pub duration: i64,
+    pub share: i64,
+    pub total: i64,
+    #[serde(rename = "break")]
+    pub is_break: bool,
+    pub distributed: bool,
+    pub manual: Option<i64>,
+    pub tags: Vec<Tag>,
+    pub metadata: BTreeMap<String, i64>,
+    pub notes: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Tag {
+    pub name: String,
+    pub duration: i64,
+    pub total: i64,
+}
+
+/// the time on a secondary track, it is not part of the work time
+#[derive(Serialize, Debug)]
+pub struct Track {
+    pub name: String,
+    pub duration: i64,
+    pub tags: Vec<Tag>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Table {
+    pub dates: Vec<String>,
+    pub work_time: i64,
+    pub rows: Vec<Row>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Row {
+    pub name: String,
+    pub shortname: Option<String>,
+    pub durations: BTreeMap<String, i64>,
+    pub total: i64,
+    pub manual: Option<i64>,
+}
+
+fn time(t: &NaiveTime) -> String {
+    t.format("%H:%M:%S").to_string()
+}
+
+// the part of total that belongs to part of whole
+fn proportion(total: &Duration, part: &Duration, whole: &Duration) -> i64 {
+    match whole.num_seconds() {
+        0 => 0,
+        whole => (total.num_seconds() as f64 * part.num_seconds() as f64 / whole as f64) as i64,
+    }
+}
+
+/// the day with the durations of the report, the cutoff and the distribution applied
+pub(crate) fn day(
+    date: &NaiveDate,
+    collected: &Option<CollectResult>,
+    cutoff: Option<Duration>,
+    all: bool,
+    group_by: &Option<String>,
+) -> Day {
+    let collected = match collected {
+        Some(collected) => collected,
+        None => {
+            return Day {
+                date: date.to_string(),
+                current: None,
+                summary: None,
+            }
+        }
+    };
+    Day {
+        date: date.to_string(),
+        current: Some(Current {
+            activity: collected.final_activity.clone(),
+            shortname: collected
+                .final_shortname
+                .as_ref()
+                .map(|shortname| shortname.trim_start_matches('=').to_string()),
+            since: time(&collected.final_start),
+        }),
+        summary: Some(summary(&collected.summary, cutoff, all, group_by)),
+    }
+}
+
+fn summary(
+    summary: &Summary,
+    cutoff: Option<Duration>,
+    all: bool,
+    group_by: &Option<String>,
+) -> DaySummary {
+    let distributed = handle_cutoff_and_distribute(summary, cutoff, all);
+    let activities = summary
+        .activities
+        .iter()
+        .sorted_by_key(report_sort_key)
+        .map(|(name, (duration, tags))| {
+            let total = distributed
+                .get(name)
+                .map_or_else(Duration::zero, |(total, _)| *total);
+            let is_break = log_parser::is_break(name);
+            Activity {
+                name: name.clone(),
+                duration: duration.num_seconds(),
+                share: match distributed.contains_key(name) {
+                    true => (total - *duration).num_seconds(),
+                    false => 0,
+                },
+                total: total.num_seconds(),
+                is_break,
+                distributed: !is_break && !distributed.contains_key(name),
+                manual: summary
+                    .adjustments
+                    .get(name)
+                    .map(|adjustment| adjustment.num_seconds()),
+                tags: tags
+                    .iter()
+                    .filter(|(tag, _)| !tag.starts_with("resume:"))
+                    .map(|(tag, tag_duration)| Tag {
+                        name: tag.clone(),
+                        duration: tag_duration.num_seconds(),
+                        total: proportion(&total, tag_duration, duration),
+                    })
+                    .collect(),
+                metadata: summary
+                    .metadata
+                    .get(name)
+                    .into_iter()
+                    .flatten()
+                    .map(|((key, value), duration)| {
+                        (format!("{}={}", key, value), duration.num_seconds())
+                    })
+                    .collect(),
+                notes: summary.notes.get(name).cloned().unwrap_or_default(),
+            }
+        })
+        .collect();
+    let tracks = summary
+        .tracks
+        .iter()
+        .sorted_by_key(|(name, _)| name.to_string())
+        .map(|(name, (duration, tags))| Track {
+            name: name.clone(),
+            duration: duration.num_seconds(),
+            tags: tags
+                .iter()
+                .map(|(tag, tag_duration)| Tag {
+                    name: tag.clone(),
+                    duration: tag_duration.num_seconds(),
+                    total: tag_duration.num_seconds(),
+                })
+                .collect(),
+        })
+        .collect();
+    DaySummary {
+        start: time(&summary.start),
+        end: time(&summary.end),
+        breaks: summary.breaks.num_seconds(),
+        work_time: summary.work_time.num_seconds(),
+        distribute: summary.distribute.num_seconds(),
+        activities,
+        tracks,
+        groups: group_by.as_ref().map(|key| {
+            group_durations(summary, &distributed, key)
+                .into_iter()
+                .map(|(group, duration)| (group, duration.num_seconds()))
+                .collect()
+        }),
+    }
+}
+
+/// the table of the report over several days
+pub(crate) fn table(
+    summaries: &[(NaiveDate, Summary)],
+    cutoff: Option<Duration>,
+    all: bool,
+    activity_map: &ActivityMap,
+    group_by: &Option<String>,
+) -> Table {
+    let rows = table_rows(summaries, cutoff, all, group_by)
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(name, daily)| Row {
+            shortname: activity_map
+                .get(name.as_str())
+                .and_then(|activity| activity.shortname.clone()),
+            durations: daily
+                .durations
+                .iter()
+                .map(|(date, duration)| (date.to_string(), duration.num_seconds()))
+                .collect(),
+            total: daily.total.num_seconds(),
+            manual: match daily.manual.is_zero() {
+                true => None,
+                false => Some(daily.manual.num_seconds()),
+            },
+            name,
+        })
+        .collect();
+    Table {
+        dates: summaries.iter().map(|(date, _)| date.to_string()).collect(),
+        work_time: summaries
+            .iter()
+            .map(|(_, summary)| summary.work_time.num_seconds())
+            .sum(),
+        rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activities::read_activities;
+    use crate::collector::collect_blocks;
+    use crate::utils::setup_line_reader;
+
+    fn collected() -> Option<CollectResult> {
+        let lines = setup_line_reader(vec![
+            "8:00 JIRA-1 review customer=acme -- fixed the login",
+            "9:00 JIRA-2",
+            "10:00 _meeting",
+            "11:00 break",
+            "12:00 JIRA-1 =j1",
+        ]);
+        collect_blocks(lines, Some(&NaiveTime::from_hms_opt(13, 0, 0).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn day_with_distribution() {
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let day = day(
+            &date,
+            &collected(),
+            None,
+            false,
+            &Some("customer".to_string()),
+        );
+        let current = day.current.unwrap();
+        assert_eq!(current.activity, "JIRA-1");
+        assert_eq!(current.shortname, Some("j1".to_string()));
+        assert_eq!(current.since, "12:00:00");
+        let summary = day.summary.unwrap();
+        assert_eq!(summary.work_time, 4 * 3600);
+        assert_eq!(summary.breaks, 3600);
+        let names: Vec<&str> = summary.activities.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["JIRA-1", "JIRA-2", "_meeting", "break"]);
+        let jira_1 = &summary.activities[0];
+        // two of three hours of work get the hour of the meeting
+        assert_eq!(jira_1.duration, 2 * 3600);
+        assert_eq!(jira_1.share, 2400);
+        assert_eq!(jira_1.total, 2 * 3600 + 2400);
+        let review = jira_1.tags.iter().find(|tag| tag.name == "review").unwrap();
+        assert_eq!(review.total, 3600 + 1200);
+        assert_eq!(jira_1.metadata["customer=acme"], 3600);
+        assert_eq!(jira_1.notes, vec!["fixed the login"]);
+        let meeting = &summary.activities[2];
+        assert!(meeting.distributed);
+        assert_eq!(meeting.total, 0);
+        assert!(summary.activities[3].is_break);
+        let total: i64 = summary.activities.iter().map(|a| a.total).sum();
+        assert_eq!(total, summary.work_time);
+        assert_eq!(summary.groups.unwrap()["customer=acme"], 3600 + 1200);
+    }
+
+    #[test]
+    fn versioned_document() {
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let summaries = vec![(date, collected().unwrap().summary)];
+        let activity_map: ActivityMap = read_activities("j1 JIRA-1".as_bytes()).unwrap();
+        let report = Report::new(
+            vec![day(&date.succ_opt().unwrap(), &None, None, false, &None)],
+            Some(table(&summaries, None, false, &activity_map, &None)),
+        );
+        let value = serde_json::to_value(&report).unwrap();
+        assert_eq!(value["version"], 1);
+        assert!(value["days"][0]["summary"].is_null());
+        let table = &value["table"];
+        assert_eq!(table["dates"][0], "2020-06-01");
+        assert_eq!(table["rows"][0]["name"], "JIRA-1");
+        assert_eq!(table["rows"][0]["shortname"], "j1");
+        assert_eq!(table["rows"][0]["durations"]["2020-06-01"], 2 * 3600 + 2400);
+        assert_eq!(table["rows"][1]["shortname"], serde_json::Value::Null);
+        assert_eq!(table["rows"][0]["manual"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn manual_durations_in_the_table() {
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let lines = setup_line_reader(vec![
+            "8:00 JIRA-1",
+            "+1:30 JIRA-1",
+            "-0:20 JIRA-1",
+            "9:00 break",
+        ]);
+        let summary = collect_blocks(lines, None).unwrap().unwrap().summary;
+        let table = table(&[(date, summary)], None, false, &ActivityMap::new(), &None);
+        assert_eq!(table.rows[0].total, 3600 + 4200);
+        assert_eq!(table.rows[0].manual, Some(4200));
+    }
+}
@@ -0,0 +1,272 @@
+//! the roll-up of the activities into their projects, for `tt report --projects <level>`.
+//!
+//! Every activity is in the tree under its projects and sub-projects from the activities file,
+//! activities without a project are at the top. The level selects the nodes that the
+//! distributable time is distributed to: `1` the top-level projects, `2` their sub-projects and
+//! so on, `activities` the activities like without projects. An activity whose projects do not
+//! reach the level is a node itself. The cutoff applies to the nodes, too: the nodes below it are
+//! left out and their time is distributed like the distributable time. Nodes and everything
+//! above them get their share of the distributed time, the activities and projects below them
+//! are shown with the time that has been logged.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use chrono::Duration;
+
+use crate::activities::ActivityMap;
+use crate::collector::Summary;
+use crate::log_parser;
+use crate::utils;
+
+/// the level of the projects that the distributable time is distributed to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Activities,
+    /// the depth of the projects, the top-level projects have 1
+    Depth(usize),
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match (s, s.parse::<usize>()) {
+            ("activities", _) => Ok(Level::Activities),
+            (_, Ok(depth)) if depth > 0 => Ok(Level::Depth(depth)),
+            _ => Err(format!(
+                "the level must be activities or a depth from 1 on, but is {}",
+                s
+            )),
+        }
+    }
+}
+
+/// a project or activity of the tree: the logged time and the share of the distributed time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Node {
+    pub duration: Duration,
+    pub share: Duration,
+}
+
+impl Node {
+    pub fn total(&self) -> Duration {
+        self.duration + self.share
+    }
+}
+
+/// the path of the projects and the activity at the end, in the order of the tree
+pub type Tree = BTreeMap<Vec<String>, Node>;
+
+// the projects of the activity and the activity
+fn path(activity_map: &ActivityMap, name: &str) -> Vec<String> {
+    let mut path: Vec<String> = activity_map
+        .get(name)
+        .map(|activity| activity.projects())
+        .unwrap_or_default()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    path.push(name.to_string());
+    path
+}
+
+/// the activities of the summary in the tree of their projects, with the distributable time
+/// distributed at the level
+pub fn tree(
+    summary: &Summary,
+    cutoff: Option<Duration>,
+    all: bool,
+    activity_map: &ActivityMap,
+    level: Level,
+) -> Tree {
+    let activities: Vec<(Vec<String>, Duration)> = summary
+        .activities
+        .iter()
+        .filter(|(name, _)| {
+            !log_parser::is_break(name) && (all || !log_parser::is_distributable(name))
+        })
+        .map(|(name, (duration, _))| (path(activity_map, name), *duration))
+        .collect();
+
+    // the nodes at the level and the time logged for them
+    let node = |path: &[String]| -> Vec<String> {
+        match level {
+            Level::Depth(depth) if path.len() > depth => path[..depth].to_vec(),
+            _ => path.to_vec(),
+        }
+    };
+    let mut nodes: BTreeMap<Vec<String>, Duration> = BTreeMap::new();
+    for (path, duration) in activities.iter() {
+        *nodes.entry(node(path)).or_insert_with(Duration::zero) += *duration;
+    }
+    let cutoff = cutoff.unwrap_or_else(Duration::zero);
+    let cut: Duration = nodes
+        .values()
+        .filter(|duration| **duration < cutoff)
+        .fold(Duration::zero(), |sum, duration| sum + *duration);
+    let distribute = match all {
+        true => cut,
+        false => cut + summary.distribute,
+    };
+    let shared: Duration = nodes
+        .values()
+        .filter(|duration| **duration >= cutoff)
+        .fold(Duration::zero(), |sum, duration| sum + *duration);
+
+    let mut tree = Tree::new();
+    let zero = Node {
+        duration: Duration::zero(),
+        share: Duration::zero(),
+    };
+    for (path, duration) in activities {
+        if nodes[&node(&path)] < cutoff {
+            continue;
+        }
+        for depth in 1..=path.len() {
+            tree.entry(path[..depth].to_vec()).or_insert(zero).duration += duration;
+        }
+    }
+    // the shares are taken from the running sum, so that they add up to the distributed time
+    let mut running = Duration::zero();
+    let mut distributed = Duration::zero();
+    for (path, duration) in nodes {
+        if duration < cutoff || shared.is_zero() {
+            continue;
+        }
+        running += duration;
+        let until_here = Duration::seconds(
+            (distribute.num_seconds() as f64 * running.num_seconds() as f64
+                / shared.num_seconds() as f64) as i64,
+        );
+        let share = until_here - distributed;
+        distributed = until_here;
+        for depth in 1..=path.len() {
+            tree.entry(path[..depth].to_vec()).or_insert(zero).share += share;
+        }
+    }
+    tree
+}
+
+/// the lines of the tree for the long report, indented by depth
+pub fn report_lines(tree: &Tree) -> Vec<String> {
+    tree.iter()
+        .map(|(path, node)| {
+            let indent = "  ".repeat(path.len() - 1);
+            let name = &path[path.len() - 1];
+            let width = 16usize.saturating_sub(indent.len());
+            match node.share.is_zero() {
+                true => format!(
+                    "{}- {:width$} {}",
+                    indent,
+                    name,
+                    utils::format_duration(&node.duration),
+                    width = width
+                ),
+                false => format!(
+                    "{}- {:width$} {} + {} = {}",
+                    indent,
+                    name,
+                    utils::format_duration(&node.duration),
+                    utils::format_duration(&node.share),
+                    utils::format_duration(&node.total()),
+                    width = width
+                ),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activities::read_activities;
+    use crate::utils::setup_line_reader;
+
+    fn summary() -> Summary {
+        let lines = setup_line_reader(vec![
+            "8:00 JIRA-1",
+            "9:00 JIRA-2",
+            "9:30 JIRA-3",
+            "10:00 JIRA-4",
+            "10:10 email",
+            "11:00 _meeting",
+            "11:40 break",
+        ]);
+        crate::collector::collect_blocks(lines, None)
+            .unwrap()
+            .unwrap()
+            .summary
+    }
+
+    fn activity_map() -> ActivityMap {
+        let activities = "JIRA-1 JIRA-1 project=acme/portal\n\
+                          JIRA-2 JIRA-2 project=acme/portal\n\
+                          JIRA-3 JIRA-3 project=acme/api\n\
+                          JIRA-4 JIRA-4 project=acme\n";
+        read_activities(activities.as_bytes()).unwrap()
+    }
+
+    // the top-level nodes add up to the work time
+    fn assert_work_time(tree: &Tree) {
+        let total = tree
+            .iter()
+            .filter(|(path, _)| path.len() == 1)
+            .fold(Duration::zero(), |sum, (_, node)| sum + node.total());
+        assert_eq!(total, summary().work_time);
+    }
+
+    fn minutes(tree: &Tree, path: &[&str]) -> (i64, i64) {
+        let path: Vec<String> = path.iter().map(|p| p.to_string()).collect();
+        let node = tree[&path];
+        (node.duration.num_minutes(), node.share.num_minutes())
+    }
+
+    #[test]
+    fn distributed_to_the_top_level() {
+        let tree = tree(&summary(), None, false, &activity_map(), Level::Depth(1));
+        // 40 minutes of _meeting for 130 minutes of acme and 50 of email
+        assert_eq!(minutes(&tree, &["acme"]), (130, 28));
+        assert_eq!(minutes(&tree, &["acme", "portal"]), (90, 0));
+        assert_eq!(minutes(&tree, &["acme", "portal", "JIRA-1"]), (60, 0));
+        assert_eq!(minutes(&tree, &["acme", "JIRA-4"]), (10, 0));
+        assert_eq!(minutes(&tree, &["email"]), (50, 11));
+        assert!(!tree.contains_key(&vec!["_meeting".to_string()]));
+        assert_work_time(&tree);
+    }
+
+    #[test]
+    fn cutoff_at_the_level() {
+        let cutoff = Some(Duration::minutes(15));
+        let tree = tree(&summary(), cutoff, false, &activity_map(), Level::Depth(2));
+        // JIRA-4 is a node of its own at level 2, and below the cutoff
+        assert!(!tree.contains_key(&vec!["acme".to_string(), "JIRA-4".to_string()]));
+        assert_eq!(minutes(&tree, &["acme"]).0, 120);
+        let portal = minutes(&tree, &["acme", "portal"]);
+        let api = minutes(&tree, &["acme", "api"]);
+        assert_eq!((portal.0, api.0), (90, 30));
+        // 50 minutes distributed for 170 minutes
+        assert_eq!((portal.1, api.1), (26, 8));
+        let share =
+            |path: &[&str]| tree[&path.iter().map(|p| p.to_string()).collect::<Vec<_>>()].share;
+        assert_eq!(
+            share(&["acme"]),
+            share(&["acme", "portal"]) + share(&["acme", "api"])
+        );
+        let lines = report_lines(&tree);
+        assert_eq!(lines[0], "- acme             2:00 + 0:35 = 2:35");
+        assert_eq!(lines[1], "  - api            0:30 + 0:09 = 0:39");
+        assert_work_time(&tree);
+
+        let tree = super::tree(&summary(), cutoff, true, &activity_map(), Level::Depth(1));
+        assert_eq!(minutes(&tree, &["_meeting"]), (40, 0));
+        assert_work_time(&tree);
+    }
+
+    #[test]
+    fn levels() {
+        assert_eq!("activities".parse::<Level>(), Ok(Level::Activities));
+        assert_eq!("2".parse::<Level>(), Ok(Level::Depth(2)));
+        assert!("0".parse::<Level>().is_err());
+    }
+}
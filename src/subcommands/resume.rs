@@ -1,10 +1,14 @@
-use crate::error::TTError;
+use crate::activities;
+use crate::collector;
+use crate::error::{Diagnostic, TTError};
+use crate::journal::Journal;
 use crate::log_parser::{self, is_distributable, Block, BlockData};
+use crate::storage::Storage;
 use crate::subcommands;
 use crate::utils;
 use crate::utils::FileProxy;
-use chrono::{DateTime, Local, NaiveTime};
-use std::io::{BufRead, Write};
+use chrono::{DateTime, Duration, Local, NaiveTime};
+use std::io::{self, BufRead, Write};
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -25,35 +29,55 @@ pub struct ResumeOpt {
 pub(crate) fn run<'a, R: BufRead, W: Write, F: FileProxy<R, W>>(
     resume_opt: ResumeOpt,
     now: &DateTime<Local>,
-    default_logfile: &F,
+    storage: &dyn Storage<R, W, F>,
     activitiesfile: &F,
+    journalfile: &F,
 ) -> Result<i32, TTError> {
+    let journal = Journal::new(journalfile, now, "resume");
+    let _lock = journal.lock()?;
     resume(
         activitiesfile,
-        default_logfile,
+        storage,
         now,
         resume_opt.timestamp,
         resume_opt.n,
         resume_opt.really,
+        &journal,
     )?;
     Ok(0)
 }
-pub fn find_resume_activities<R: BufRead>(logfile: R) -> Vec<(String, Vec<String>, usize)> {
-    let blocks: Vec<BlockData> = logfile
-        .lines()
-        .map(log_parser::Block::from_line)
-        .filter_map(|b| match b {
-            Ok(Block::NormalBlock(data)) | Ok(Block::ReallyBlock(data)) => Some(Ok(data)),
-            Ok(Block::CommentBlock(())) => None,
-            Ok(Block::TimeCorrection(_)) => None,
-            Err(err) => Some(Err(err)),
-        })
-        .collect::<Result<Vec<_>, TTError>>()
-        .unwrap();
+// returns the data of the activities that can be resumed and their offset on the resume stack.
+// The activity still open at the end of the previous day counts as the first block of the day.
+// Lines that cannot be parsed are skipped and returned as diagnostics.
+pub fn find_resume_activities<T: Iterator<Item = io::Result<String>>>(
+    previous_logfile: T,
+    logfile: T,
+) -> (Vec<(BlockData, usize)>, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    let carried = collector::open_activity(previous_logfile);
+    let blocks: Vec<BlockData> = carried
+        .into_iter()
+        .chain(
+            logfile
+                .map(log_parser::Block::from_line)
+                .enumerate()
+                .filter_map(|(index, b)| match b {
+                    Ok(Block::NormalBlock(data)) | Ok(Block::ReallyBlock(data)) => Some(data),
+                    Ok(Block::CommentBlock(_)) => None,
+                    Ok(Block::TimeCorrection(_))
+                    | Ok(Block::TrackBlock(_, _))
+                    | Ok(Block::DurationBlock(_, _)) => None,
+                    Err(err) => {
+                        diagnostics.push(Diagnostic::error(err).at_line(index + 1));
+                        None
+                    }
+                }),
+        )
+        .collect();
     let size = blocks.len();
     let mut result: Vec<_> = Vec::new();
     if size <= 1 {
-        return result;
+        return (result, diagnostics);
     }
     let mut pos = size - 1;
 
@@ -62,52 +86,61 @@ pub fn find_resume_activities<R: BufRead>(logfile: R) -> Vec<(String, Vec<String
         let block = &blocks[pos];
         // println!("Now at {}: {:?}", pos, block);
         if block.activity.as_str() != current_activity && !log_parser::is_break(&block.activity) {
-            result.push((block.activity.clone(), block.tags.clone(), size - pos));
+            result.push((block.clone(), size - pos));
             current_activity = block.activity.as_str();
         }
-        let offset: usize = match block.tags.iter().find(|s| s.starts_with("resume:")) {
-            None => 1,
-            Some(s) => s[7..].parse::<usize>().unwrap_or(1),
-        };
+        let offset: usize = block.resume_offset().unwrap_or(1);
         if pos < offset {
             break;
         }
         pos -= offset;
     }
-    result
+    (result, diagnostics)
 }
 
+// resumes the activity at n on the resume stack, the caller holds the lock of the journal
 pub fn resume<R: BufRead, W: Write, F: FileProxy<R, W>>(
     activitiesfile: &F,
-    logfile: &F,
+    storage: &dyn Storage<R, W, F>,
     now: &DateTime<Local>,
     timestamp: Option<NaiveTime>,
     n: usize,
     really: bool,
+    journal: &Journal<F>,
 ) -> Result<(), TTError> {
-    let resume_stack = find_resume_activities(logfile.reader()?);
+    let workday = utils::workday(&now.naive_local());
+    let previous_day = storage.load_day(&(workday - Duration::days(1)))?;
+    let day = storage.load_day(&workday)?;
+    let (resume_stack, diagnostics) =
+        find_resume_activities(previous_day.iter_lines(), day.iter_lines());
+    subcommands::report::print_diagnostics(&storage.path(&workday), diagnostics);
     if resume_stack.is_empty() {
         println!("Nothing to resume.");
         return Ok(());
     }
-    let (activity, original_tags, offset) = resume_stack
+    let (original, offset) = resume_stack
         .get(n)
         .expect("Wrong number. Aborting.")
         .clone();
-    let mut tags: Vec<String> = original_tags
+    let mut tags: Vec<String> = original
+        .tags
         .into_iter()
         .filter(|s| !s.starts_with("resume:"))
         .collect();
     tags.push(format!("resume:{}", offset).to_string());
 
     let data = BlockData {
-        start: timestamp.unwrap_or_else(|| now.naive_local().time()),
-        activity: format!("+{}", activity),
+        start: timestamp.unwrap_or_else(|| utils::without_seconds(&now.naive_local().time())),
+        activity: format!("+{}", original.activity),
         tags,
-        distribute: is_distributable(activity.as_ref()),
+        metadata: original.metadata,
+        note: None,
+        distribute: is_distributable(original.activity.as_ref()),
+        utc_offset: None,
+        start_seconds: false,
     };
-    let activity_map = subcommands::add::read_activities(activitiesfile.reader()?)
-        .expect("Cannot read config file");
+    let activity_map =
+        activities::read_activities(activitiesfile.reader()?).expect("Cannot read config file");
     let block = match really {
         true => Block::ReallyBlock,
         false => Block::NormalBlock,
@@ -116,8 +149,9 @@ pub fn resume<R: BufRead, W: Write, F: FileProxy<R, W>>(
         block,
         Some(&activity_map),
         activitiesfile,
-        logfile,
-        &timestamp.unwrap_or_else(|| now.time()),
+        storage,
+        &timestamp.unwrap_or_else(|| utils::without_seconds(&now.time())),
         now,
+        journal,
     )
 }
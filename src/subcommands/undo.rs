@@ -0,0 +1,132 @@
+use std::io::{BufRead, Write};
+use std::result::Result;
+
+use chrono::{DateTime, Local};
+use structopt::StructOpt;
+
+use crate::error::{TTError, TTErrorKind};
+use crate::journal::{self, Change, Rewrite};
+use crate::storage::Storage;
+use crate::utils::FileProxy;
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct UndoOpt {
+    #[structopt(short, long)]
+    /// show the operations that can be undone, the most recent first
+    pub list: bool,
+
+    #[structopt(name = "N", default_value = "1")]
+    /// how many operations to undo
+    pub n: usize,
+}
+
+// interface for the runner, removes the lines of the last operations in the journal
+// from the files they have been added to, and puts back what they have rewritten.
+pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    opt: UndoOpt,
+    _now: &DateTime<Local>,
+    storage: &dyn Storage<R, W, F>,
+    default_logfile: &F,
+    activitiesfile: &F,
+    journalfile: &F,
+) -> Result<i32, TTError> {
+    let lock = journalfile.lock()?;
+    let operations = journal::read_operations(journalfile.reader()?)
+        .map_err(|err| err.in_file(journalfile.pathname()))?;
+    if opt.list {
+        for (i, operation) in operations.iter().rev().enumerate() {
+            println!("{:>2} {} {}", i + 1, operation.time, operation.command);
+            for (path, change) in operation.changes.iter() {
+                match change {
+                    Change::Append(line) => println!("     {}: {}", path.display(), line),
+                    Change::Rewrite(rewrite) => {
+                        println!("     {}: {}", path.display(), describe(rewrite))
+                    }
+                }
+            }
+        }
+        return Ok(0);
+    }
+    if opt.n > operations.len() {
+        return Err(TTError::new(TTErrorKind::UsageError(
+            "there are not that many operations to undo, see `tt undo --list`",
+        )));
+    }
+    let keep = operations.len() - opt.n;
+    for operation in operations[keep..].iter().rev() {
+        println!("undo {} {}", operation.time, operation.command);
+        for (path, change) in operation.changes.iter().rev() {
+            // the files of the runner are used if possible, they might not be named files
+            let other_file;
+            let file = if path == default_logfile.pathname() {
+                default_logfile
+            } else if path == activitiesfile.pathname() {
+                activitiesfile
+            } else {
+                other_file = F::new(path.to_path_buf());
+                &other_file
+            };
+            match change {
+                Change::Append(line) => match journal::remove_line(file, line)? {
+                    true => println!("removed from {}: {}", path.display(), line),
+                    false => eprintln!(
+                        "warning: the line has been changed in the meantime, it stays in {}: {}",
+                        path.display(),
+                        line
+                    ),
+                },
+                Change::Rewrite(rewrite) => {
+                    let current = match rewrite.date {
+                        Some(date) => storage.load_day(&date)?.lines,
+                        None => file.reader()?.lines().collect::<Result<Vec<_>, _>>()?,
+                    };
+                    if current != rewrite.after {
+                        eprintln!(
+                            "warning: {} has been changed in the meantime, it stays as it is",
+                            describe_target(path, rewrite)
+                        );
+                        continue;
+                    }
+                    match rewrite.date {
+                        Some(date) => storage.rewrite(&date, &rewrite.before, &lock)?,
+                        None => {
+                            let content: String = rewrite
+                                .before
+                                .iter()
+                                .map(|line| format!("{}\n", line))
+                                .collect();
+                            file.replace_content(content.as_bytes())?
+                        }
+                    }
+                    println!("restored {}", describe_target(path, rewrite));
+                }
+            }
+        }
+    }
+    let mut content: Vec<u8> = Vec::new();
+    journal::write_operations(&mut content, &operations[..keep])?;
+    journalfile.replace_content(&content)?;
+    Ok(0)
+}
+
+// the file or the day of the rewrite, for messages
+fn describe_target(path: &std::path::Path, rewrite: &Rewrite) -> String {
+    match rewrite.date {
+        Some(date) => format!("the log of {} in {}", date, path.display()),
+        None => path.display().to_string(),
+    }
+}
+
+// what the rewrite has changed, for the list of operations
+fn describe(rewrite: &Rewrite) -> String {
+    let day = match rewrite.date {
+        Some(date) => format!("the log of {}", date),
+        None => "the file".to_string(),
+    };
+    format!(
+        "rewrote {} from {} to {} lines",
+        day,
+        rewrite.before.len(),
+        rewrite.after.len()
+    )
+}
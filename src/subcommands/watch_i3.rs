@@ -4,19 +4,22 @@ use std::ops::Sub;
 use std::process::Command;
 use std::thread::sleep;
 
-use chrono::{self, DateTime, Local, NaiveTime};
+use chrono::{self, DateTime, Local, NaiveTime, SubsecRound};
 use if_chain::if_chain;
 use itertools::all;
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::Deserialize;
 
+use crate::activities::{read_activities, ActivityMap};
 use crate::collector::collect_blocks;
 use crate::configfile::TTConfig;
 use crate::error::TTError;
-use crate::log_parser::{is_break, is_distributable, is_start, Block, BlockData, TTInfo};
-use crate::subcommands::add::{add, read_activities, ActivityMap};
-use crate::utils::FileProxy;
+use crate::journal::Journal;
+use crate::log_parser::{is_break, is_distributable, is_start, Block, BlockData, Metadata, TTInfo};
+use crate::storage::Storage;
+use crate::subcommands::add::add;
+use crate::utils::{self, FileProxy};
 use std::cmp::min;
 
 #[derive(Copy, Clone, Deserialize, Debug)]
@@ -68,10 +71,11 @@ pub(crate) fn as_str(opt: &Option<String>) -> Option<&str> {
 // public interface is the function add()
 pub(crate) fn run<R: BufRead, W: Write, F: FileProxy<R, W>>(
     _now: &DateTime<Local>,
-    default_logfile: &F,
+    storage: &dyn Storage<R, W, F>,
     activitiesfile: &F,
+    journalfile: &F,
 ) -> Result<i32, TTError> {
-    watch_i3(default_logfile, activitiesfile)?;
+    watch_i3(storage, activitiesfile, journalfile)?;
     Ok(0)
 }
 
@@ -84,9 +88,10 @@ fn get_workspaces() -> Result<Vec<I3Workspace>, TTError> {
     Ok(serde_json::from_slice(output.stdout.as_slice())?)
 }
 
-pub(crate) fn watch_i3<R: BufRead, W: Write>(
-    logfile: &impl FileProxy<R, W>,
-    activitiesfile: &impl FileProxy<R, W>,
+pub(crate) fn watch_i3<R: BufRead, W: Write, F: FileProxy<R, W>>(
+    storage: &dyn Storage<R, W, F>,
+    activitiesfile: &F,
+    journalfile: &F,
 ) -> Result<(), TTError> {
     let mut focus_counter: HashMap<String, u16> = HashMap::new();
     let mut prev_tt_activity: Option<TTInfo> = None;
@@ -104,15 +109,18 @@ pub(crate) fn watch_i3<R: BufRead, W: Write>(
     let mut prev_focus_name: Option<String> = None;
 
     loop {
-        let now = chrono::Local::now();
+        // log entries of watch-i3 have a precision of seconds
+        let now = chrono::Local::now().trunc_subsecs(0);
+        let journal = Journal::new(journalfile, &now, "watch-i3");
+        // held from before the day is read until the block that is based on it has been added
+        let lock = journal.lock()?;
         let activity_map: Option<ActivityMap> = activitiesfile
             .reader()
             .ok()
             .and_then(|file| read_activities(file).ok());
         let collected = (|| {
-            collect_blocks(logfile.reader().ok()?.lines(), None)
-                .ok()
-                .flatten()
+            let day = storage.load_day(&utils::workday(&now.naive_local())).ok()?;
+            collect_blocks(day.iter_lines(), None).ok().flatten()
         })();
         let tt_activity = collected
             .as_ref()
@@ -204,15 +212,20 @@ pub(crate) fn watch_i3<R: BufRead, W: Write>(
                                     start: if really { now.time() } else { start },
                                     activity: focus_activity.as_block_activity(),
                                     tags: focus_activity.tags(),
+                                    metadata: Metadata::new(),
+                                    note: None,
                                     distribute: is_distributable(&focus_activity.activity),
+                                    utc_offset: None,
+                                    start_seconds: false,
                                 };
                                 add(
                                     Block::from_data(data, really),
                                     activity_map.as_ref(),
                                     activitiesfile,
-                                    logfile,
+                                    storage,
                                     &now.time(),
                                     &now,
+                                    &journal,
                                 )?;
                             }
                         }
@@ -220,6 +233,7 @@ pub(crate) fn watch_i3<R: BufRead, W: Write>(
                 }
             }
         }
+        drop(lock);
         sleep(granularity);
     }
 }
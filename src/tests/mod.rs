@@ -1,17 +1,22 @@
 mod test_add;
+mod test_import;
+mod test_list;
 mod test_log_parser;
+mod test_report;
+mod test_undo;
 
 use crate::error::TTError;
 use crate::utils::{FakeFile, FileProxy};
 use chrono::prelude::*;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct TestData {
     pub now: DateTime<Local>,
     pub configfile: FakeFile,
     pub logfile: FakeFile,
     pub activitiesfile: FakeFile,
+    pub journalfile: FakeFile,
     pub args: String,
 }
 
@@ -22,6 +27,7 @@ impl TestData {
             configfile: FakeFile::new(PathBuf::from("config")),
             logfile: FakeFile::new(PathBuf::from("logfile")),
             activitiesfile: FakeFile::new(PathBuf::from("configfile")),
+            journalfile: FakeFile::new(PathBuf::from("journal")),
             args: "timetracker".to_string(),
         }
     }
@@ -32,9 +38,11 @@ impl TestData {
         crate::run(
             &args,
             &self.now,
+            Path::new("data"),
             &self.configfile,
             &self.logfile,
             &self.activitiesfile,
+            &self.journalfile,
         )
         .map(|_| self)
     }
@@ -24,6 +24,7 @@ fn add_unknown() {
         Err(TTError {
             kind: TTErrorKind::UsageError(_),
             context: _,
+            ..
         }) => (),
         _ => panic!("Expected a TTError::UsageError"),
     }
@@ -102,3 +103,198 @@ fn add_expand_prefix() {
         .unwrap();
     assert_eq!(data.logfile.close().unwrap(), "08:00 PREFIX-123\n");
 }
+
+#[test]
+fn add_with_metadata() {
+    let data = TestData::new()
+        .with_args("shortcut phase=review")
+        .write_activitiesfile("shortcut something customer=acme phase=dev\n".to_string())
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.logfile.close().unwrap(),
+        "08:00 something =shortcut customer=acme phase=review\n"
+    );
+}
+
+#[test]
+fn add_force_with_metadata() {
+    let data = TestData::new()
+        .with_args("+something =shortcut customer=acme")
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.activitiesfile.close().unwrap(),
+        "shortcut something customer=acme\n"
+    );
+}
+
+#[test]
+fn add_with_note() {
+    let data = TestData::new()
+        .with_args("+test-simple tag-1 -- fixed the bug")
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.logfile.close().unwrap(),
+        "08:00 test-simple tag-1 -- fixed the bug\n"
+    );
+}
+
+#[test]
+fn add_with_utc_offset() {
+    let data = TestData::new()
+        .write_configfile("record-utc-offset = true".to_string())
+        .with_args("+test-offset")
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.logfile.close().unwrap(),
+        format!("08:00{} test-offset\n", data.now.offset())
+    );
+}
+
+#[test]
+fn add_track() {
+    let data = TestData::new()
+        .with_args("add --track oncall customer=acme")
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.logfile.close().unwrap(),
+        "08:00 @oncall customer=acme\n"
+    );
+    let data = TestData::new()
+        .with_args("add --track oncall break")
+        .run()
+        .unwrap();
+    assert_eq!(data.logfile.close().unwrap(), "08:00 @oncall break\n");
+    assert!(data.activitiesfile.close().unwrap().is_empty());
+}
+
+#[test]
+fn add_force_track_name() {
+    let result = TestData::new().with_args("+@oncall =oc").run();
+    assert!(result.is_err());
+}
+
+#[test]
+fn add_duration() {
+    let data = TestData::new()
+        .write_activitiesfile("travel JIRA-7\n".to_string())
+        .with_args("add --duration 1:30 travel -- train to the customer")
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.logfile.close().unwrap(),
+        "+1:30 JIRA-7 =travel -- train to the customer\n"
+    );
+}
+
+#[test]
+fn add_negative_duration() {
+    let data = TestData::new()
+        .write_activitiesfile("email email\n".to_string())
+        .with_args("add --duration -0:20 email")
+        .run()
+        .unwrap();
+    assert_eq!(data.logfile.close().unwrap(), "-0:20 email\n");
+}
+
+#[test]
+fn add_duration_of_more_than_a_day() {
+    let data = TestData::new()
+        .write_activitiesfile("travel JIRA-7\n".to_string())
+        .with_args("add --duration 26:30 travel")
+        .run()
+        .unwrap();
+    assert_eq!(data.logfile.close().unwrap(), "+26:30 JIRA-7 =travel\n");
+}
+
+#[test]
+fn add_known_activity_from_toml() {
+    let data = TestData::new()
+        .with_args("api tag-1")
+        .write_activitiesfile(
+            "[JIRA-123]\nshortname = \"api\"\ntags = [\"review\"]\ncustomer = \"acme\"\nrate = 95\n"
+                .to_string(),
+        )
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.logfile.close().unwrap(),
+        "08:00 JIRA-123 =api tag-1 review customer=acme\n"
+    );
+}
+
+#[test]
+fn add_force_to_toml() {
+    let data = TestData::new()
+        .with_args("+something =shortcut customer=acme")
+        .write_activitiesfile("# activities\n[email]\n".to_string())
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.activitiesfile.close().unwrap(),
+        "# activities\n[email]\n[something]\nshortname = \"shortcut\"\ntags = [\"customer=acme\"]\n"
+    );
+}
+
+#[test]
+fn add_archived_activity() {
+    let data = TestData::new()
+        .with_args("mail")
+        .write_activitiesfile("mail email archived=true\n".to_string())
+        .run()
+        .unwrap();
+    assert_eq!(data.logfile.close().unwrap(), "08:00 email =mail\n");
+}
+
+#[test]
+fn add_alias_logs_preferred_shortname() {
+    let data = TestData::new()
+        .with_args("pagination")
+        .write_activitiesfile(
+            "[JIRA-812]\nshortname = \"api\"\naliases = [\"pagination\"]\n".to_string(),
+        )
+        .run()
+        .unwrap();
+    assert_eq!(data.logfile.close().unwrap(), "08:00 JIRA-812 =api\n");
+}
+
+#[test]
+fn add_alias_recorded() {
+    let data = TestData::new()
+        .with_args("pagination")
+        .write_configfile("record-alias = true\n".to_string())
+        .write_activitiesfile("api JIRA-812\npagination JIRA-812\n".to_string())
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.logfile.close().unwrap(),
+        "08:00 JIRA-812 =api alias:pagination\n"
+    );
+}
+
+#[test]
+fn add_force_shortname_to_toml_table() {
+    let data = TestData::new()
+        .with_args("+email =mail")
+        .write_activitiesfile("# activities\n[email]\ntags = [\"inbox\"]\n[meeting]\n".to_string())
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.activitiesfile.close().unwrap(),
+        "# activities\n[email]\nshortname = \"mail\"\ntags = [\"inbox\"]\n[meeting]\n"
+    );
+
+    let data = TestData::new()
+        .with_args("+email =inbox")
+        .write_activitiesfile("[email]\nshortname = \"mail\"\n".to_string())
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.activitiesfile.close().unwrap(),
+        "[email]\nshortname = \"mail\"\naliases = [\"inbox\"]\n"
+    );
+}
@@ -0,0 +1,115 @@
+use crate::collector::collect_workday;
+use crate::export::{records, write_records, Format};
+use crate::tests::TestData;
+use crate::utils::{setup_line_reader, FileProxy};
+use chrono::prelude::*;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+const LOG: &str = "8:00 email =mail customer=acme\n9:00 meeting\n10:00 break\n";
+
+// writes the file to import, named after the test
+fn import_file(name: &str, content: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("tt-{}-{}.csv", name, std::process::id()));
+    fs::write(&path, content).unwrap();
+    path
+}
+
+fn logfile(data: &TestData) -> String {
+    let mut content = String::new();
+    data.logfile
+        .reader()
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    content
+}
+
+#[test]
+fn import_exported_day() {
+    let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+    let lines = setup_line_reader(LOG.lines().collect());
+    let (collected, _) = collect_workday(&date, vec![].into_iter(), lines, None);
+    let exported = write_records(Format::Csv, &records(&date, &collected.unwrap().summary));
+    let file = import_file("reimport", &exported.unwrap());
+    let data = TestData::new()
+        .write_logfile(LOG.to_string())
+        .with_args(&format!("import {}", file.display()))
+        .run()
+        .unwrap();
+    fs::remove_file(&file).unwrap();
+    assert_eq!(data.logfile.close().unwrap(), LOG);
+    assert!(data.journalfile.close().unwrap().is_empty());
+}
+
+#[test]
+fn import_overlap_refused() {
+    let file = import_file(
+        "refuse",
+        "date,start,end,duration,activity,shortname,tags,distributable,break,note\n\
+         2020-06-01,09:30:00,11:00:00,5400,review,,,false,false,\n",
+    );
+    let data = TestData::new()
+        .write_logfile(LOG.to_string())
+        .with_args(&format!("import {}", file.display()))
+        .run()
+        .unwrap();
+    assert_eq!(logfile(&data), LOG);
+
+    let data = data
+        .with_args(&format!("import --merge {}", file.display()))
+        .run()
+        .unwrap();
+    fs::remove_file(&file).unwrap();
+    let merged = logfile(&data);
+    assert!(merged.starts_with("<<<<<<< this machine\n"));
+    assert!(merged.contains("09:30 review\n11:00 break\n"));
+
+    let data = data.with_args("undo").run().unwrap();
+    assert_eq!(data.logfile.close().unwrap(), LOG);
+}
+
+#[test]
+fn import_new_entries() {
+    let file = import_file(
+        "new",
+        "date,start,end,duration,activity,shortname,tags,distributable,break,note\n\
+         2020-06-01,11:00:00,12:00:00,3600,review,,,false,false,\n",
+    );
+    let data = TestData::new()
+        .write_logfile(LOG.to_string())
+        .with_args(&format!("import {}", file.display()))
+        .run()
+        .unwrap();
+    fs::remove_file(&file).unwrap();
+    assert_eq!(
+        data.logfile.close().unwrap(),
+        format!("{}11:00 review\n12:00 break\n", LOG)
+    );
+}
+
+#[test]
+fn import_differing_entry_refused() {
+    let file = import_file(
+        "differing",
+        "date,start,end,duration,activity,shortname,tags,distributable,break,note\n\
+         2020-06-01,08:00:00,09:00:00,3600,email,mail,customer=other,false,false,\n",
+    );
+    let data = TestData::new()
+        .write_logfile(LOG.to_string())
+        .with_args(&format!("import {}", file.display()))
+        .run()
+        .unwrap();
+    assert_eq!(logfile(&data), LOG);
+
+    let data = data
+        .with_args(&format!("import --merge {}", file.display()))
+        .run()
+        .unwrap();
+    fs::remove_file(&file).unwrap();
+    let merged = data.logfile.close().unwrap();
+    assert!(merged.starts_with("<<<<<<< this machine\n"));
+    assert!(merged.contains("=======\n08:00 email =mail customer=other\n"));
+}
@@ -0,0 +1,61 @@
+use super::TestData;
+use crate::utils::FileProxy;
+use std::io::Read;
+
+#[test]
+fn list_does_not_archive() {
+    let data = TestData::new()
+        .with_args("list")
+        .write_configfile("archive-activities-after = 7\n".to_string())
+        .write_activitiesfile("mail email\nmeeting\n".to_string())
+        .write_logfile("07:00 email =mail\n".to_string())
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.activitiesfile.close().unwrap(),
+        "mail email\nmeeting\n"
+    );
+}
+
+#[test]
+fn add_does_not_archive_with_a_short_history() {
+    // the log only has today, so the activities cannot have been unused for 7 days
+    let data = TestData::new()
+        .with_args("pager")
+        .write_configfile("archive-activities-after = 7\n".to_string())
+        .write_activitiesfile("mail email\nmeeting\npager\n".to_string())
+        .write_logfile("07:00 email =mail\n".to_string())
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.activitiesfile.close().unwrap(),
+        "mail email\nmeeting\npager\n"
+    );
+}
+
+#[test]
+fn prune_after_configured_days_and_undo() {
+    let data = TestData::new()
+        .with_args("activities prune")
+        .write_configfile("archive-activities-after = 7\n".to_string())
+        .write_activitiesfile("mail email\nmeeting\npager\n".to_string())
+        .write_logfile("07:00 email =mail\n07:30 @pager\n".to_string())
+        .run()
+        .unwrap();
+    let mut content = String::new();
+    data.activitiesfile
+        .reader()
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(
+        content,
+        "mail email\nmeeting meeting archived=true\npager\n"
+    );
+
+    let data = data.with_args("undo").run().unwrap();
+    assert_eq!(
+        data.activitiesfile.close().unwrap(),
+        "mail email\nmeeting\npager\n"
+    );
+}
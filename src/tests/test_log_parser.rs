@@ -1,7 +1,8 @@
 use crate::error::{TTError, TTErrorKind};
 use crate::log_parser::*;
-use chrono::NaiveTime;
+use chrono::{FixedOffset, NaiveTime};
 use std::io;
+use std::path::Path;
 
 // tests for normal behaviour
 
@@ -18,10 +19,200 @@ fn normal_block() {
     }
 }
 
+#[test]
+fn block_with_metadata() {
+    match Block::from_line(Ok(
+        "11:30 JIRA-12 =api customer=acme billable=no urgent".to_string()
+    )) {
+        Ok(Block::NormalBlock(data)) => {
+            assert_eq!(data.tags, vec!["=api", "urgent"]);
+            assert_eq!(data.metadata.len(), 2);
+            assert_eq!(data.metadata["customer"], "acme");
+            assert_eq!(data.metadata["billable"], "no");
+            assert_eq!(data.shortname(), Some("api"));
+        }
+        _ => panic!("should have been a NormalBlock"),
+    }
+}
+
+#[test]
+fn block_with_note() {
+    let line = "10:15 JIRA-12 =api -- fixed  pagination -- bug";
+    match Block::from_line(Ok(line.to_string())) {
+        Ok(block @ Block::NormalBlock(_)) => {
+            if let Block::NormalBlock(data) = &block {
+                assert_eq!(data.activity, "JIRA-12");
+                assert_eq!(data.tags, vec!["=api"]);
+                assert_eq!(data.note.as_deref(), Some("fixed  pagination -- bug"));
+            }
+            assert_eq!(
+                block.to_string(&NaiveTime::from_hms_opt(10, 15, 0).unwrap()),
+                line
+            );
+        }
+        _ => panic!("should have been a NormalBlock"),
+    }
+}
+
+#[test]
+fn block_with_seconds() {
+    match Block::from_line(Ok("11:30:15 11:29:50 email".to_string())) {
+        Ok(Block::NormalBlock(data)) => {
+            assert_eq!(data.start, NaiveTime::from_hms_opt(11, 29, 50).unwrap());
+            assert_eq!(data.activity, "email");
+        }
+        _ => panic!("should have been a NormalBlock"),
+    }
+    match Block::from_line(Ok("11:30:15 really 11:29:50".to_string())) {
+        Ok(Block::TimeCorrection(time)) => {
+            assert_eq!(time, NaiveTime::from_hms_opt(11, 29, 50).unwrap())
+        }
+        _ => panic!("should have been a time correction block"),
+    }
+}
+
+#[test]
+fn block_with_seconds_to_string() {
+    let block = Block::from_line(Ok("11:30:15 11:29:50 email".to_string())).unwrap();
+    assert_eq!(
+        block.to_string(&NaiveTime::from_hms_opt(11, 30, 15).unwrap()),
+        "11:30:15 11:29:50 email"
+    );
+    let block = Block::from_line(Ok("11:30 11:29:50 email".to_string())).unwrap();
+    assert_eq!(
+        block.to_string(&NaiveTime::from_hms_opt(11, 30, 0).unwrap()),
+        "11:30 11:29:50 email"
+    );
+}
+
+#[test]
+fn block_with_zero_seconds_round_trip() {
+    for (line, timestamp) in &[
+        ("10:13:00 started", NaiveTime::from_hms_opt(10, 13, 0)),
+        ("11:30 11:29:00 email", NaiveTime::from_hms_opt(11, 30, 0)),
+        (
+            "11:30 11:29:00+02:00 email",
+            NaiveTime::from_hms_opt(11, 30, 0),
+        ),
+        ("11:30:00 really email", NaiveTime::from_hms_opt(11, 30, 0)),
+        ("10:13 started", NaiveTime::from_hms_opt(10, 13, 0)),
+    ] {
+        let block = Block::from_line(Ok(line.to_string())).unwrap();
+        assert_eq!(&block.to_string(&timestamp.unwrap()), line);
+    }
+}
+
+#[test]
+fn block_with_utc_offset() {
+    match Block::from_line(Ok("11:30+02:00 email".to_string())) {
+        Ok(Block::NormalBlock(data)) => {
+            assert_eq!(data.start, NaiveTime::from_hms_opt(11, 30, 0).unwrap());
+            assert_eq!(
+                data.utc_offset,
+                Some(FixedOffset::east_opt(2 * 3600).unwrap())
+            );
+            assert_eq!(data.activity, "email");
+        }
+        _ => panic!("should have been a NormalBlock"),
+    }
+    match Block::from_line(Ok("11:30 11:10-05:00 email".to_string())) {
+        Ok(Block::NormalBlock(data)) => {
+            assert_eq!(data.start, NaiveTime::from_hms_opt(11, 10, 0).unwrap());
+            assert_eq!(
+                data.utc_offset,
+                Some(FixedOffset::west_opt(5 * 3600).unwrap())
+            );
+        }
+        _ => panic!("should have been a NormalBlock"),
+    }
+}
+
+#[test]
+fn block_with_utc_offset_to_string() {
+    let block = Block::from_line(Ok("11:30+02:00 email".to_string())).unwrap();
+    assert_eq!(
+        block.to_string(&NaiveTime::from_hms_opt(11, 30, 0).unwrap()),
+        "11:30+02:00 email"
+    );
+    let block = Block::from_line(Ok("11:30 11:10Z email".to_string())).unwrap();
+    assert_eq!(
+        block.to_string(&NaiveTime::from_hms_opt(11, 30, 0).unwrap()),
+        "11:30 11:10+00:00 email"
+    );
+}
+
+#[test]
+fn track_block() {
+    match Block::from_line(Ok("10:00 @oncall customer=acme -- pager".to_string())) {
+        Ok(Block::TrackBlock(track, data)) => {
+            assert_eq!(track, "oncall");
+            assert_eq!(data.activity, "oncall");
+            assert_eq!(data.metadata.get("customer").unwrap(), "acme");
+            assert_eq!(data.note, Some("pager".to_string()));
+        }
+        _ => panic!("should have been a TrackBlock"),
+    }
+    match Block::from_line(Ok("18:00 17:45 @oncall break".to_string())) {
+        Ok(Block::TrackBlock(track, data)) => {
+            assert_eq!(track, "oncall");
+            assert_eq!(data.start, NaiveTime::from_hms_opt(17, 45, 0).unwrap());
+            assert_eq!(data.activity, "break");
+        }
+        _ => panic!("should have been a TrackBlock"),
+    }
+}
+
+#[test]
+fn track_block_to_string() {
+    for line in &[
+        "10:00 @oncall customer=acme -- pager",
+        "18:00 @oncall break",
+    ] {
+        let block = Block::from_line(Ok(line.to_string())).unwrap();
+        let timestamp = crate::utils::parse_time(&line[..5]).unwrap();
+        assert_eq!(&block.to_string(&timestamp), line);
+    }
+}
+
+#[test]
+fn duration_block() {
+    match Block::from_line(Ok("+1:30 JIRA-7 travel -- to the customer".to_string())) {
+        Ok(Block::DurationBlock(duration, data)) => {
+            assert_eq!(duration.num_minutes(), 90);
+            assert_eq!(data.activity, "JIRA-7");
+            assert_eq!(data.tags, vec!["travel".to_string()]);
+            assert_eq!(data.note, Some("to the customer".to_string()));
+        }
+        _ => panic!("should have been a DurationBlock"),
+    }
+    match Block::from_line(Ok("-0:20 JIRA-7".to_string())) {
+        Ok(Block::DurationBlock(duration, _)) => assert_eq!(duration.num_minutes(), -20),
+        _ => panic!("should have been a DurationBlock"),
+    }
+    match Block::from_line(Ok("+1:30".to_string())) {
+        Err(TTError {
+            kind: TTErrorKind::ParseError(_, _),
+            ..
+        }) => (),
+        _ => panic!("a duration without activity should be a parse error"),
+    }
+}
+
+#[test]
+fn duration_block_to_string() {
+    for line in &["+1:30 JIRA-7 travel -- to the customer", "-0:20 JIRA-7"] {
+        let block = Block::from_line(Ok(line.to_string())).unwrap();
+        assert_eq!(
+            &block.to_string(&NaiveTime::from_hms_opt(10, 0, 0).unwrap()),
+            line
+        );
+    }
+}
+
 #[test]
 fn comment_block() {
     match Block::from_line(Ok("# bla".to_string())) {
-        Ok(Block::CommentBlock(())) => (),
+        Ok(Block::CommentBlock(text)) => assert_eq!(text, "# bla"),
         _ => panic!("should have been a comment block"),
     }
 }
@@ -46,6 +237,93 @@ fn test_really_block() {
     }
 }
 
+// tests for the log file model
+
+#[test]
+fn logfile_roundtrip() {
+    let content =
+        "#comment  \r\n\n  \n08:00 email   =mail -- note\r\n09:00:30 really 08:59\n10:00 break";
+    let logfile = LogFile::read(content.as_bytes()).unwrap();
+    assert_eq!(logfile.len(), 6);
+    assert_eq!(logfile.entries().count(), 3);
+    assert_eq!(logfile.lines()[5].line_number, Some(6));
+    assert_eq!(
+        logfile.lines()[4].timestamp,
+        Some(NaiveTime::from_hms_opt(9, 0, 30).unwrap())
+    );
+    assert_eq!(logfile.lines()[0].text(), "#comment  ");
+    assert_eq!(logfile.to_string(), content);
+}
+
+#[test]
+fn logfile_edit() {
+    let content = "# first\n08:00  email\n09:00 JIRA-1\n10:00 break";
+    let mut logfile = LogFile::read(content.as_bytes()).unwrap();
+    let block = Block::from_line(Ok("09:00 JIRA-2 -- oops".to_string())).unwrap();
+    let old = logfile.replace(
+        2,
+        LogLine::new(NaiveTime::from_hms_opt(9, 5, 0).unwrap(), block),
+    );
+    assert_eq!(old.text(), "09:00 JIRA-1");
+    assert_eq!(logfile.lines()[2].line_number, Some(3));
+    assert!(!logfile.lines()[2].is_original());
+    let block = Block::from_line(Ok("11:00 JIRA-3".to_string())).unwrap();
+    logfile.push(LogLine::new(
+        NaiveTime::from_hms_opt(11, 0, 0).unwrap(),
+        block,
+    ));
+    assert_eq!(
+        logfile.to_string(),
+        "# first\n08:00  email\n09:05 09:00 JIRA-2 -- oops\n10:00 break\n11:00 JIRA-3\n"
+    );
+    logfile.remove(1);
+    logfile.insert(
+        0,
+        LogLine::new(
+            NaiveTime::from_hms_opt(7, 0, 0).unwrap(),
+            Block::CommentBlock("# inserted".to_string()),
+        ),
+    );
+    assert_eq!(logfile.lines()[0].timestamp, None);
+    assert_eq!(
+        logfile.to_string(),
+        "# inserted\n# first\n09:05 09:00 JIRA-2 -- oops\n10:00 break\n11:00 JIRA-3\n"
+    );
+}
+
+#[test]
+fn logfile_error_line_number() {
+    match LogFile::read("08:00 email\n25:00 bla\n".as_bytes()) {
+        Err(TTError {
+            kind: TTErrorKind::ParseError(_msg, line),
+            details,
+            ..
+        }) => {
+            assert_eq!(line, "25:00 bla");
+            assert_eq!(details.line_number, Some(2));
+            assert_eq!(details.span, Some(0..5));
+        }
+        res => panic!("should have been a parse error, but was {:?}", res),
+    }
+}
+
+#[test]
+fn error_display_with_location() {
+    let err = Block::from_line(Ok("10:05 really 10:00 extra".to_string()))
+        .unwrap_err()
+        .at_line(12)
+        .in_file(Path::new("2020-06-01"));
+    assert_eq!(
+        err.to_string(),
+        "parse error: time correction cannot have further data, i.e. use only <time> really <time>
+  --> 2020-06-01:12:20
+   |
+12 | 10:05 really 10:00 extra
+   |                    ^^^^^
+   = hint: use `HH:MM really HH:MM` for time corrections"
+    );
+}
+
 // tests for error results
 
 #[test]
@@ -55,6 +333,7 @@ fn test_io_error() {
         Err(TTError {
             kind: TTErrorKind::IoError(err),
             context: _,
+            ..
         }) => {
             assert_eq!(err.kind(), io::ErrorKind::Other);
             assert_eq!(err.to_string(), "message");
@@ -69,6 +348,7 @@ fn test_parse_time_error() {
         Err(TTError {
             kind: TTErrorKind::ParseError(msg, line),
             context: _,
+            ..
         }) => {
             assert_eq!(line, "25:00 bla");
             assert_eq!(msg, "cannot parse start time");
@@ -83,6 +363,7 @@ fn test_parse_no_activity_error() {
         Err(TTError {
             kind: TTErrorKind::ParseError(msg, _line),
             context: _,
+            ..
         }) => {
             assert_eq!(msg, "Line does not contain at least 2 words");
         }
@@ -96,6 +377,7 @@ fn test_really_no_activity_error() {
         Err(TTError {
             kind: TTErrorKind::ParseError(msg, _line),
             context: _,
+            ..
         }) => {
             assert_eq!(msg, "really block does not contain an activity");
         }
@@ -109,6 +391,7 @@ fn test_timecorrection_error() {
         Err(TTError {
             kind: TTErrorKind::ParseError(msg, _line),
             context: _,
+            ..
         }) => {
             assert_eq!(
                 msg,
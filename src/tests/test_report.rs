@@ -0,0 +1,13 @@
+use super::TestData;
+use chrono::prelude::*;
+
+#[test]
+fn report_week_by_projects() {
+    let data = TestData::new()
+        .with_now(Local.with_ymd_and_hms(2020, 6, 1, 12, 0, 0).unwrap())
+        .write_activitiesfile("JIRA-1 JIRA-1 project=acme/portal\n".to_string())
+        .write_logfile("08:00 JIRA-1\n10:00 _meeting\n11:00 email\n".to_string())
+        .with_args("report -w -p 1 -c 0:30")
+        .run();
+    assert!(data.is_ok());
+}
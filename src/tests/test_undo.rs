@@ -0,0 +1,143 @@
+use crate::activities::read_activities;
+use crate::journal::Journal;
+use crate::tests::TestData;
+use crate::utils::FileProxy;
+use chrono::prelude::*;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+#[test]
+fn undo_add() {
+    let data = TestData::new()
+        .with_args("+first")
+        .run()
+        .unwrap()
+        .with_now(Local.with_ymd_and_hms(2020, 6, 1, 9, 0, 0).unwrap())
+        .with_args("+second =snd")
+        .run()
+        .unwrap()
+        .with_args("undo")
+        .run()
+        .unwrap();
+    assert_eq!(data.logfile.close().unwrap(), "08:00 first\n");
+    assert!(data.activitiesfile.close().unwrap().is_empty());
+    assert_eq!(data.journalfile.close().unwrap().lines().count(), 1);
+}
+
+#[test]
+fn undo_several() {
+    let data = TestData::new()
+        .with_args("+first")
+        .run()
+        .unwrap()
+        .with_now(Local.with_ymd_and_hms(2020, 6, 1, 9, 0, 0).unwrap())
+        .with_args("+second")
+        .run()
+        .unwrap()
+        .with_args("undo 2")
+        .run()
+        .unwrap();
+    assert!(data.logfile.close().unwrap().is_empty());
+    assert!(data.journalfile.close().unwrap().is_empty());
+}
+
+#[test]
+fn undo_keeps_other_lines() {
+    let data = TestData::new()
+        .write_logfile("07:00 manual\n".to_string())
+        .with_args("+first")
+        .run()
+        .unwrap()
+        .write_logfile("08:30 manual\n".to_string())
+        .with_args("undo")
+        .run()
+        .unwrap();
+    assert_eq!(
+        data.logfile.close().unwrap(),
+        "07:00 manual\n08:30 manual\n"
+    );
+}
+
+#[test]
+fn undo_too_much() {
+    let result = TestData::new()
+        .with_args("+first")
+        .run()
+        .unwrap()
+        .with_args("undo 2")
+        .run();
+    assert!(result.is_err());
+}
+
+#[test]
+fn undo_merge() {
+    let other = env::temp_dir().join(format!("tt-undo-merge-{}", std::process::id()));
+    fs::create_dir_all(&other).unwrap();
+    fs::write(other.join("2020-06-01"), "09:00 email\n11:00 break\n").unwrap();
+    let data = TestData::new()
+        .write_logfile("08:00 email\n10:00 break\n".to_string())
+        .with_args(&format!("merge {}", other.display()))
+        .run()
+        .unwrap();
+    let mut merged = String::new();
+    data.logfile
+        .reader()
+        .unwrap()
+        .read_to_string(&mut merged)
+        .unwrap();
+    assert_eq!(merged, "08:00 email\n09:00 email\n11:00 break\n");
+    let data = data.with_args("undo").run().unwrap();
+    fs::remove_dir_all(&other).unwrap();
+    assert_eq!(data.logfile.close().unwrap(), "08:00 email\n10:00 break\n");
+    assert!(data.journalfile.close().unwrap().is_empty());
+}
+
+#[test]
+fn undo_merge_of_a_conflicting_activity() {
+    let other = env::temp_dir().join(format!("tt-undo-merge-activities-{}", std::process::id()));
+    fs::create_dir_all(&other).unwrap();
+    fs::write(other.join("activities"), "jf jour-fixe\n").unwrap();
+    let activities = "[jira]\nshortname = \"jf\"\n";
+    let data = TestData::new()
+        .write_activitiesfile(activities.to_string())
+        .with_args(&format!("merge {}", other.display()))
+        .run()
+        .unwrap();
+    let mut merged = String::new();
+    data.activitiesfile
+        .reader()
+        .unwrap()
+        .read_to_string(&mut merged)
+        .unwrap();
+    let marker = format!(
+        "# merge conflict with {}: [jour-fixe] shortname = \"jf\"",
+        other.display()
+    );
+    assert_eq!(merged, format!("{}{}\n", activities, marker));
+    // the shortname still means the activity here
+    assert_eq!(
+        read_activities(merged.as_bytes()).unwrap()["jf"].name,
+        "jira"
+    );
+    let data = data.with_args("undo").run().unwrap();
+    fs::remove_dir_all(&other).unwrap();
+    assert_eq!(data.activitiesfile.close().unwrap(), activities);
+}
+
+#[test]
+fn undo_keeps_a_rewrite_that_has_been_changed() {
+    let data = TestData::new().write_logfile("08:00 email\n10:00 break\n".to_string());
+    let journal = Journal::new(&data.journalfile, &data.now, "merge");
+    journal
+        .record_rewrite(
+            Path::new("logfile"),
+            Some(NaiveDate::from_ymd_opt(2020, 6, 1).unwrap()),
+            vec!["08:00 email".to_string()],
+            &["08:00 email".to_string(), "09:00 break".to_string()],
+        )
+        .unwrap();
+    let data = data.with_args("undo").run().unwrap();
+    assert_eq!(data.logfile.close().unwrap(), "08:00 email\n10:00 break\n");
+}
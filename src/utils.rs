@@ -1,41 +1,143 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::io::{self, BufReader, BufWriter, Chain, Cursor, Read, Write};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::result::Result;
 use std::string::FromUtf8Error;
 use std::{
-    fs::{File, OpenOptions},
+    fs::{self, File, OpenOptions},
     str,
 };
 
 use crate::configfile::TTConfig;
 use chrono::Duration;
-use chrono::NaiveTime;
 use chrono::Timelike;
+use chrono::{FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
 
 // use crate::SummaryFormat::{Short, Long};
 
-/// parses the time as HH:MM
+/// parses the time as HH:MM or HH:MM:SS
 /// ```
 /// use timetracker::utils;
 /// use chrono::NaiveTime;
-/// assert_eq!(utils::parse_time("15:23"), Ok(NaiveTime::from_hms(15,23,00)))
+/// assert_eq!(utils::parse_time("15:23"), Ok(NaiveTime::from_hms_opt(15,23,00).unwrap()));
+/// assert_eq!(utils::parse_time("15:23:42"), Ok(NaiveTime::from_hms_opt(15,23,42).unwrap()));
 /// ```
 pub fn parse_time(s: &str) -> Result<NaiveTime, chrono::format::ParseError> {
-    NaiveTime::parse_from_str(s, "%H:%M")
+    NaiveTime::parse_from_str(s, "%H:%M:%S").or_else(|_| NaiveTime::parse_from_str(s, "%H:%M"))
 }
 
-/// formats time as HH:MM
+/// formats time as HH:MM, or as HH:MM:SS if the seconds are not zero
 /// ```
 /// use timetracker::utils;
 /// use chrono::NaiveTime;
 /// assert_eq!(format!("{}", utils::format_time(&NaiveTime::from_hms(15,23,00))), "15:23");
+/// let time = NaiveTime::from_hms_opt(15, 23, 42).unwrap();
+/// assert_eq!(format!("{}", utils::format_time(&time)), "15:23:42");
 /// ```
 pub fn format_time(
     t: &NaiveTime,
-) -> chrono::format::DelayedFormat<chrono::format::strftime::StrftimeItems> {
-    t.format("%H:%M")
+) -> chrono::format::DelayedFormat<chrono::format::strftime::StrftimeItems<'static>> {
+    if t.second() == 0 {
+        t.format("%H:%M")
+    } else {
+        t.format("%H:%M:%S")
+    }
+}
+
+/// parses a time with an optional UTC offset, as HH:MM[:SS] followed by +HH:MM, -HH:MM or Z
+/// ```
+/// use timetracker::utils;
+/// use chrono::{FixedOffset, NaiveTime};
+/// let time = |hour, minute, second| NaiveTime::from_hms_opt(hour, minute, second).unwrap();
+/// let east = |seconds| FixedOffset::east_opt(seconds);
+/// assert_eq!(utils::parse_time_with_offset("15:23"), Ok((time(15, 23, 0), None)));
+/// assert_eq!(
+///     utils::parse_time_with_offset("15:23+02:00"),
+///     Ok((time(15, 23, 0), east(2 * 3600)))
+/// );
+/// assert_eq!(
+///     utils::parse_time_with_offset("15:23:42Z"),
+///     Ok((time(15, 23, 42), east(0)))
+/// );
+/// ```
+pub fn parse_time_with_offset(
+    s: &str,
+) -> Result<(NaiveTime, Option<FixedOffset>), chrono::format::ParseError> {
+    if let Some(time) = s.strip_suffix('Z') {
+        return Ok((parse_time(time)?, FixedOffset::east_opt(0)));
+    }
+    match s.find(&['+', '-'][..]) {
+        Some(pos) => Ok((parse_time(&s[..pos])?, Some(s[pos..].parse()?))),
+        None => Ok((parse_time(s)?, None)),
+    }
+}
+
+/// formats time like format_time, or always with seconds if `seconds` is set, followed by
+/// the UTC offset if there is one
+/// ```
+/// use timetracker::utils;
+/// use chrono::{FixedOffset, NaiveTime};
+/// let time = NaiveTime::from_hms_opt(15, 23, 0).unwrap();
+/// assert_eq!(utils::format_time_with_offset(&time, None, false), "15:23");
+/// assert_eq!(utils::format_time_with_offset(&time, None, true), "15:23:00");
+/// assert_eq!(
+///     utils::format_time_with_offset(&time, FixedOffset::west_opt(5 * 3600), false),
+///     "15:23-05:00"
+/// );
+/// ```
+pub fn format_time_with_offset(
+    t: &NaiveTime,
+    utc_offset: Option<FixedOffset>,
+    seconds: bool,
+) -> String {
+    let time = if seconds {
+        t.format("%H:%M:%S").to_string()
+    } else {
+        format_time(t).to_string()
+    };
+    match utc_offset {
+        Some(offset) => format!("{}{}", time, offset),
+        None => time,
+    }
+}
+
+/// whether a time as parsed by parse_time_with_offset is written with seconds
+/// ```
+/// use timetracker::utils;
+/// assert!(utils::has_seconds("15:23:00"));
+/// assert!(utils::has_seconds("15:23:00+02:00"));
+/// assert!(!utils::has_seconds("15:23Z"));
+/// assert!(!utils::has_seconds("15:23-05:00"));
+/// ```
+pub fn has_seconds(s: &str) -> bool {
+    let time = s.strip_suffix('Z').unwrap_or(s);
+    let time = match time.find(&['+', '-'][..]) {
+        Some(pos) => &time[..pos],
+        None => time,
+    };
+    time.matches(':').count() == 2
+}
+
+/// the UTC offset of the system's local time zone at that local time. A time that is skipped
+/// by the switch to daylight saving time gets the offset after the switch.
+pub fn local_offset(datetime: &NaiveDateTime) -> FixedOffset {
+    Local
+        .offset_from_local_datetime(datetime)
+        .earliest()
+        .unwrap_or_else(|| Local.offset_from_utc_datetime(datetime))
+}
+
+/// drops seconds and fractions of a second, for log entries with minute precision
+/// ```
+/// use timetracker::utils;
+/// use chrono::NaiveTime;
+/// let time = NaiveTime::from_hms_milli_opt(15, 23, 42, 500).unwrap();
+/// assert_eq!(utils::without_seconds(&time), NaiveTime::from_hms_opt(15, 23, 0).unwrap());
+/// ```
+pub fn without_seconds(t: &NaiveTime) -> NaiveTime {
+    NaiveTime::from_num_seconds_from_midnight_opt(t.num_seconds_from_midnight() / 60 * 60, 0)
+        .unwrap_or(*t)
 }
 
 /// parses a duration from HH:MM
@@ -49,16 +151,102 @@ pub fn parse_duration(s: &str) -> Result<Duration, chrono::format::ParseError> {
         .map(|t| Duration::seconds(t.num_seconds_from_midnight() as i64))
 }
 
-/// formats a duration as HH:MM
+/// parses a duration with a sign from +HH:MM or -HH:MM, like for manual entries in the log.
+/// The hours are not limited to a day.
+/// ```
+/// use timetracker::utils;
+/// assert_eq!(utils::parse_signed_duration("+1:30").map(|d| d.num_minutes()), Ok(90));
+/// assert_eq!(utils::parse_signed_duration("-0:20").map(|d| d.num_minutes()), Ok(-20));
+/// assert_eq!(utils::parse_signed_duration("+30:00").map(|d| d.num_hours()), Ok(30));
+/// assert!(utils::parse_signed_duration("1:30").is_err());
+/// assert!(utils::parse_signed_duration("+1:60").is_err());
+/// ```
+pub fn parse_signed_duration(s: &str) -> Result<Duration, String> {
+    let error = || {
+        format!(
+            "a duration must have the form +H:MM or -H:MM, but is {:?}",
+            s
+        )
+    };
+    let (sign, rest) = match s.strip_prefix('+') {
+        Some(rest) => (1, rest),
+        None => (-1, s.strip_prefix('-').ok_or_else(error)?),
+    };
+    let (hours, minutes) = rest.split_once(':').ok_or_else(error)?;
+    let number = |digits: &str| match digits.chars().all(|c| c.is_ascii_digit()) {
+        true => digits.parse::<i64>().ok(),
+        false => None,
+    };
+    let hours = number(hours).ok_or_else(error)?;
+    let minutes = number(minutes).filter(|m| *m < 60).ok_or_else(error)?;
+    hours
+        .checked_mul(60)
+        .and_then(|m| m.checked_add(minutes))
+        .and_then(|m| Duration::try_minutes(sign * m))
+        .ok_or_else(error)
+}
+
+/// formats a duration with its sign as +HH:MM or -HH:MM
+/// ```
+/// use timetracker::utils;
+/// use chrono::Duration;
+/// assert_eq!(utils::format_signed_duration(&Duration::minutes(90)), "+1:30");
+/// assert_eq!(utils::format_signed_duration(&Duration::minutes(-20)), "-0:20");
+/// ```
+pub fn format_signed_duration(duration: &Duration) -> String {
+    match *duration < Duration::zero() {
+        true => format!("-{}", format_duration(&-*duration)),
+        false => format!("+{}", format_duration(duration)),
+    }
+}
+
+/// formats a duration as HH:MM, with a leading minus if it is negative
 /// ```
 /// use timetracker::utils;
 /// use chrono::Duration;
 /// assert_eq!(format!("{}", utils::format_duration(&Duration::minutes(90))), "1:30");
+/// assert_eq!(utils::format_duration(&Duration::minutes(-20)), "-0:20");
+/// assert_eq!(utils::format_duration(&Duration::hours(1200)), "1200:00");
 /// ```
 pub fn format_duration(duration: &chrono::Duration) -> String {
-    let mins = ((duration.num_seconds() + 30) / 60) as u16;
-    let hours = (mins / 60) as u16;
-    format!("{}:{:02}", hours, mins - hours * 60)
+    let seconds = duration.num_seconds();
+    let mins = (seconds.abs() + 30) / 60;
+    let sign = if seconds < 0 && mins > 0 { "-" } else { "" };
+    format!("{}{}:{:02}", sign, mins / 60, mins % 60)
+}
+
+/// the time when a new logical workday starts (day-change-hour in the config file, default 0)
+pub fn day_change() -> NaiveTime {
+    NaiveTime::from_hms_opt(TTConfig::get().day_change_hour, 0, 0).unwrap_or(NaiveTime::MIN)
+}
+
+/// the logical workday: before the day change, it is still the previous day
+/// ```
+/// use timetracker::utils;
+/// use timetracker::utils::{FakeFile, FileProxy};
+/// use timetracker::configfile::TTConfig;
+/// use chrono::NaiveDate;
+/// use std::io::Write;
+/// use std::path::PathBuf;
+/// let configfile = FakeFile::new(PathBuf::from("configfile"));
+/// configfile.writer().unwrap().write("day-change-hour = 4".as_bytes());
+/// TTConfig::init(configfile.reader().unwrap()).unwrap();
+/// let date = NaiveDate::from_ymd_opt(2020, 3, 14).unwrap();
+/// assert_eq!(utils::workday(&date.and_hms_opt(1, 30, 0).unwrap()), date.pred_opt().unwrap());
+/// assert_eq!(utils::workday(&date.and_hms_opt(4, 0, 0).unwrap()), date);
+/// ```
+pub fn workday(datetime: &NaiveDateTime) -> NaiveDate {
+    (*datetime - Duration::hours(TTConfig::get().day_change_hour as i64)).date()
+}
+
+/// the time since the start of the logical workday, times before the day change are at its end
+pub fn workday_offset(t: &NaiveTime) -> Duration {
+    let offset = *t - day_change();
+    if offset < Duration::zero() {
+        offset + Duration::days(1)
+    } else {
+        offset
+    }
 }
 
 /// if the activity is just a bare jira ticket number, return a proper jira ticket ID
@@ -87,9 +275,26 @@ pub trait FileProxy<R: Read, W: Write> {
     fn pathname(&self) -> &Path;
     fn reader(&self) -> io::Result<R>;
     fn writer(&self) -> io::Result<W>;
+    /// replaces the content of the file at once: readers see either the old or the new content
+    fn replace_content(&self, content: &[u8]) -> io::Result<()>;
+    /// an exclusive advisory lock on the file, it is held until the FileLock is dropped.
+    /// Commands lock before they read and change files, the functions they call do not lock.
+    fn lock(&self) -> io::Result<FileLock>;
     fn new(pathname: PathBuf) -> Self;
 }
 
+/// an advisory lock, see FileProxy::lock
+pub struct FileLock {
+    _file: Option<File>,
+}
+
+// the file next to the given one, with a suffix added to its name
+fn sibling(pathname: &Path, suffix: &str) -> PathBuf {
+    let mut name = pathname.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    pathname.with_file_name(name)
+}
+
 pub struct NamedFile {
     pathname: PathBuf,
 }
@@ -110,17 +315,47 @@ impl FileProxy<BufReader<File>, BufWriter<File>> for NamedFile {
         }
     }
     fn writer(&self) -> io::Result<BufWriter<File>> {
+        create_parent_dir(self.pathname())?;
         OpenOptions::new()
             .append(true)
             .create(true)
             .open(self.pathname())
             .map(BufWriter::new)
     }
+    // writes a temporary file and renames it, so that the file is never half written
+    fn replace_content(&self, content: &[u8]) -> io::Result<()> {
+        create_parent_dir(self.pathname())?;
+        let temporary = sibling(self.pathname(), ".tmp");
+        let mut file = File::create(&temporary)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&temporary, self.pathname())
+    }
+    // the lock is on a separate file, since replace_content replaces the file itself
+    fn lock(&self) -> io::Result<FileLock> {
+        let lockfile = sibling(self.pathname(), ".lock");
+        create_parent_dir(&lockfile)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lockfile)?;
+        file.lock()?;
+        Ok(FileLock { _file: Some(file) })
+    }
     fn new(pathname: PathBuf) -> NamedFile {
         NamedFile { pathname }
     }
 }
 
+// the directories of a new file, like the month directory of the nested layout
+fn create_parent_dir(pathname: &Path) -> io::Result<()> {
+    match pathname.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
 pub struct RcBuffer {
     buffer: Rc<RefCell<Vec<u8>>>,
 }
@@ -159,7 +394,7 @@ impl RcBuffer {
 
 pub struct FakeFile {
     pathname: PathBuf,
-    source: &'static [u8],
+    source: Cell<&'static [u8]>,
     sink: RcBuffer,
 }
 
@@ -171,13 +406,21 @@ impl FileProxy<Chain<BufReader<&'static [u8]>, Cursor<Vec<u8>>>, FakeWriter> for
         self.pathname.as_ref()
     }
     fn reader(&self) -> io::Result<ReadChain> {
-        let read_source = BufReader::new(self.source);
+        let read_source = BufReader::new(self.source.get());
         let read_buffer = Cursor::new(self.sink.clone().close());
         Ok(read_source.chain(read_buffer))
     }
     fn writer(&self) -> io::Result<FakeWriter> {
         Ok(BufWriter::new(self.sink.clone()))
     }
+    fn replace_content(&self, content: &[u8]) -> io::Result<()> {
+        self.source.set(b"");
+        self.sink.buffer.replace(content.to_vec());
+        Ok(())
+    }
+    fn lock(&self) -> io::Result<FileLock> {
+        Ok(FileLock { _file: None })
+    }
     fn new(pathname: PathBuf) -> FakeFile {
         FakeFile::with_content(pathname, b"")
     }
@@ -186,7 +429,7 @@ impl FileProxy<Chain<BufReader<&'static [u8]>, Cursor<Vec<u8>>>, FakeWriter> for
 impl FakeFile {
     #[allow(unused)]
     pub(crate) fn close(self) -> Result<String, FromUtf8Error> {
-        Ok(String::from_utf8(self.source.to_vec())?
+        Ok(String::from_utf8(self.source.get().to_vec())?
             + String::from_utf8(self.sink.close())?.as_ref())
     }
 
@@ -194,7 +437,7 @@ impl FakeFile {
     pub(crate) fn with_content(pathname: PathBuf, content: &'static [u8]) -> FakeFile {
         FakeFile {
             pathname,
-            source: content,
+            source: Cell::new(content),
             sink: RcBuffer::new(),
         }
     }
@@ -241,6 +484,23 @@ Karl liebt Kartoffeln.\n";
         assert_eq!(logfile.close().unwrap(), expected);
     }
 
+    #[test]
+    fn named_file_replace_content() {
+        let dir = std::env::temp_dir().join(format!("tt-replace-{}", std::process::id()));
+        let file = NamedFile::new(dir.join("2020-06-01"));
+        writeln!(file.writer().unwrap(), "08:00 email").unwrap();
+        {
+            let _lock = file.lock().unwrap();
+            file.replace_content(b"09:00 meeting\n").unwrap();
+        }
+        let mut content = String::new();
+        file.reader().unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(content, "09:00 meeting\n");
+        assert!(!dir.join("2020-06-01.tmp").exists());
+        assert!(dir.join("2020-06-01.lock").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
     #[test]
     fn format_duration_almost_one_hour() {
         assert_eq!(format_duration(&Duration::milliseconds(3599999)), "1:00");